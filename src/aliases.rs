@@ -13,7 +13,7 @@ use std::fs;
 use std::path::PathBuf;
 
 /// Alias definition
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Alias {
     /// The alias name
     pub name: String,
@@ -102,6 +102,16 @@ impl AliasManager {
         }
     }
 
+    /// Re-read the config file, dropping aliases it no longer has
+    ///
+    /// Returns whether anything changed.
+    pub fn reload(&mut self) -> bool {
+        let before = std::mem::take(&mut self.aliases);
+        self.load();
+        self.add_default_aliases();
+        self.aliases != before
+    }
+
     /// Save aliases to config file
     pub fn save(&self) -> std::io::Result<()> {
         if let Some(parent) = self.config_path.parent() {
@@ -233,7 +243,7 @@ pub fn handle_alias_command(
                         .collect();
                     Some(output.join("\n"))
                 }
-            } else if args.len() == 1 {
+            } else if args.len() == 1 && !args[0].contains('=') {
                 // Show specific alias
                 let name = args[0];
                 if let Some(alias) = manager.get(name) {
@@ -313,4 +323,16 @@ mod tests {
         manager.remove("test");
         assert!(!manager.is_alias("test"));
     }
+
+    #[test]
+    fn test_alias_command_with_quoted_value() {
+        let mut manager = AliasManager {
+            aliases: HashMap::new(),
+            config_path: std::env::temp_dir().join("sc-test-aliases-quoted.yaml"),
+        };
+
+        // `alias ll="ls -la"` reaches the builtin as a single word
+        handle_alias_command(&mut manager, "alias", &["ll=ls -la"]);
+        assert_eq!(manager.get("ll").unwrap().command, "ls -la");
+    }
 }
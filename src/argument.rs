@@ -5,7 +5,8 @@
 
 #![allow(dead_code)]
 
-use crate::command_def::{ArgumentType, PathFilterConfig};
+use crate::command_def::{ArgumentType, CommandSpec, PathFilterConfig};
+use crate::flags::{self, Violation};
 use regex::Regex;
 use std::path::Path;
 
@@ -333,50 +334,95 @@ impl ArgumentValidator {
 pub struct CommandValidator;
 
 impl CommandValidator {
-    /// Validate all arguments in a command
-    pub fn validate_command(
-        args: &[String],
-        arg_specs: &[crate::command_def::ArgumentSpec],
-    ) -> Vec<(usize, ValidationResult)> {
-        let mut results = Vec::new();
-
-        for (idx, value) in args.iter().enumerate() {
-            // Find the argument spec for this position
-            let spec = arg_specs.iter().find(|s| {
-                s.position == Some(idx) || (s.variadic && s.position.map(|p| idx >= p).unwrap_or(false))
+    /// Everything in `words`, the words after the command path of `spec`,
+    /// that doesn't fit the spec
+    ///
+    /// Reports unknown flags (only when the spec says its flag list is
+    /// complete, as most definitions list just the common ones), flags
+    /// missing their value, flag values and positional arguments their type rejects,
+    /// missing required arguments (on the word after the last one, and not
+    /// with `--help`) and the constraints between flags.
+    pub fn validate_command(spec: &CommandSpec, words: &[&str]) -> Vec<Violation> {
+        let scan = flags::scan(spec, words);
+        let mut found = Vec::new();
+
+        // Definitions rarely list --help, though nearly everything takes it
+        let help = words.contains(&"--help");
+        if spec.flags_complete {
+            let unknown = scan.unknown.iter().filter(|&&word| words[word] != "--help");
+            found.extend(unknown.map(|&word| Violation {
+                word,
+                message: format!("unknown flag {}", words[word]),
+            }));
+        }
+        if let Some(flag) = scan.pending {
+            found.push(Violation {
+                word: words.len() - 1,
+                message: format!("{} needs a value", flags::label(flag)),
             });
-
-            if let Some(spec) = spec {
-                // Get the type from either provider shorthand or arg_type
-                let arg_type = if spec.provider.is_some() {
-                    &ArgumentType::Any // Provider-validated
-                } else {
-                    &spec.arg_type
-                };
-
-                let result = ArgumentValidator::validate(value, arg_type);
-                if result != ValidationResult::Valid {
-                    results.push((idx, result));
-                }
+        }
+        for value in &scan.values {
+            let Some(value_type) = &value.flag.value_type else {
+                continue;
+            };
+            if let Some(problem) = Self::problem(&words[value.word][value.offset..], value_type) {
+                found.push(Violation {
+                    word: value.word,
+                    message: format!("{}: {}", flags::label(value.flag), problem),
+                });
             }
         }
 
-        // Check for required arguments
-        for spec in arg_specs {
-            if spec.required {
-                if let Some(pos) = spec.position {
-                    if args.get(pos).map(|s| s.is_empty()).unwrap_or(true) {
-                        let name = spec.name.as_deref().unwrap_or("argument");
-                        results.push((
-                            pos,
-                            ValidationResult::Invalid(format!("Required: {}", name)),
-                        ));
-                    }
-                }
+        for (position, &word) in scan.positionals.iter().enumerate() {
+            let Some(argument) = spec.argument_at(position) else {
+                continue;
+            };
+            if let Some(problem) = Self::problem(words[word], &argument.arg_type) {
+                found.push(Violation {
+                    word,
+                    message: format!("{}: {}", argument.placeholder(), problem),
+                });
             }
         }
+        for (i, argument) in spec.arguments.iter().enumerate() {
+            if argument.required
+                && !help
+                && argument.position.unwrap_or(i) >= scan.positionals.len()
+            {
+                found.push(Violation {
+                    word: words.len(),
+                    message: format!("missing {}", argument.placeholder()),
+                });
+            }
+        }
+
+        found.extend(flags::violations(&scan.flags));
+        found.sort_by_key(|v| v.word);
+        found
+    }
 
-        results
+    /// Why `value` is not a valid `arg_type`, once the line is complete
+    ///
+    /// Paths are only checked for being a file or a directory: one that
+    /// doesn't exist yet may be created by the command, and the other path
+    /// filters only narrow down completion.
+    fn problem(value: &str, arg_type: &ArgumentType) -> Option<String> {
+        if let ArgumentType::Path { filter } = arg_type {
+            let path = Path::new(value);
+            return if filter.files_only && path.is_dir() {
+                Some("Expected a file, not a directory".to_string())
+            } else if filter.dirs_only && path.is_file() {
+                Some("Expected a directory, not a file".to_string())
+            } else {
+                None
+            };
+        }
+        match ArgumentValidator::validate(value, arg_type) {
+            ValidationResult::Valid => None,
+            ValidationResult::Invalid(message) | ValidationResult::Incomplete(message) => {
+                Some(message)
+            }
+        }
     }
 }
 
@@ -520,4 +566,67 @@ mod tests {
             "<a|b>"
         );
     }
+
+    #[test]
+    fn test_validate_command() {
+        let spec: CommandSpec = serde_yaml::from_str(
+            r#"
+name: scale
+description: Scale
+flags_complete: true
+flags:
+  - long: replicas
+    short: r
+    description: Replica count
+    takes_value: true
+    value_type:
+      type: number
+      min: 0
+      max: 10
+  - long: mode
+    description: Mode
+    takes_value: true
+    value_type:
+      type: choice
+      values: [fast, safe]
+arguments:
+  - name: target
+    required: true
+  - name: output
+    type:
+      type: path
+      filter:
+        files_only: true
+"#,
+        )
+        .unwrap();
+        let messages = |words: &[&str]| -> Vec<String> {
+            CommandValidator::validate_command(&spec, words)
+                .into_iter()
+                .map(|v| format!("{} {}", v.word, v.message))
+                .collect()
+        };
+
+        assert!(messages(&["web", "-r", "3", "--mode=safe", "out/new.txt"]).is_empty());
+        assert_eq!(
+            messages(&["-r", "12", "--mode=slow", "--force"]),
+            [
+                "1 --replicas: Value 12 is greater than maximum 10",
+                "2 --mode: Expected one of: fast, safe",
+                "3 unknown flag --force",
+                "4 missing <target>",
+            ]
+        );
+        assert_eq!(messages(&["web", "--mode"]), ["1 --mode needs a value"]);
+        assert!(messages(&["--help"]).is_empty());
+        assert_eq!(
+            messages(&["web", "src"]),
+            ["1 <output>: Expected a file, not a directory"]
+        );
+
+        // Unless the flag list is marked complete, a flag it lacks may be real
+        let mut partial = spec.clone();
+        partial.flags_complete = false;
+        assert!(CommandValidator::validate_command(&partial, &["web", "--force"]).is_empty());
+    }
 }
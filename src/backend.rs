@@ -0,0 +1,595 @@
+//! Execution Backends
+//!
+//! Chooses the program that runs command lines the executor does not run
+//! natively (`exec.shell` in config.toml):
+//! - `native` (default): the built-in executor, `sh -c` only for syntax it
+//!   does not support
+//! - `sh`, `bash`, `zsh`, `fish` (or a path to one): every line that does not
+//!   use an sc builtin runs through that shell
+//! - A custom argv template such as `nu -c {}` (`{}` is the command line)
+//! - `exec.persistent = true` keeps one shell alive between lines (a
+//!   coprocess), so functions and variables defined in it survive; sc's own
+//!   variables (`export`ed or plain `NAME=value`) are sent to it before each
+//!   line
+//!
+//! After each line the shell reports its exit status and final directory, so
+//! `$?`, the prompt and `OLDPWD` follow a `cd` made inside the shell.
+//! Custom templates only report their exit status.
+
+#![allow(dead_code)]
+
+use crate::environment::session_env;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, PipeReader, PipeWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::Instant;
+
+/// Variables the shells keep up to date themselves
+const UNSYNCED_VARS: &[&str] = &["PWD", "OLDPWD", "SHLVL", "_"];
+
+/// Shells with a known command syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellKind {
+    Sh,
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl ShellKind {
+    /// Recognise a shell by name or path (`bash`, `/usr/local/bin/zsh`)
+    pub fn from_program(program: &str) -> Option<Self> {
+        match Path::new(program).file_name()?.to_str()? {
+            "sh" | "dash" | "ash" | "ksh" => Some(Self::Sh),
+            "bash" => Some(Self::Bash),
+            "zsh" => Some(Self::Zsh),
+            "fish" => Some(Self::Fish),
+            _ => None,
+        }
+    }
+
+    /// Quote `text` as a single word
+    pub fn quote(self, text: &str) -> String {
+        match self {
+            Self::Fish => format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'")),
+            _ => format!("'{}'", text.replace('\'', "'\\''")),
+        }
+    }
+
+    /// `-c` script that runs a line once and writes the final directory to a file
+    ///
+    /// Arguments are the directory file, the line and the previous exit
+    /// status (for `$?`): `$1`-`$3` in POSIX shells, `$argv` in fish.
+    fn one_shot_script(self) -> &'static str {
+        match self {
+            Self::Fish => {
+                "function __sc_return; return $argv[1]; end; __sc_return $argv[3]; \
+                 eval $argv[2]; set -l __sc_status $status; pwd > $argv[1]; exit $__sc_status"
+            }
+            _ => {
+                r#"__sc_dir_file=$1
+__sc_return() { return "$1"; }
+__sc_return "$3"
+eval "$2"
+__sc_status=$?
+pwd > "$__sc_dir_file"
+exit $__sc_status"#
+            }
+        }
+    }
+
+    /// `-c` script of a coprocess
+    ///
+    /// Each request on fd 3 is a line count followed by that many lines; the
+    /// reply on fd 4 is the exit status and the directory, one per line. The
+    /// shell survives Ctrl-C (its command does not) and ignores Ctrl-Z.
+    fn coprocess_script(self) -> &'static str {
+        match self {
+            Self::Fish => {
+                r#"function __sc_ignore_int --on-signal INT; end
+function __sc_return; return $argv[1]; end
+while read -l __sc_count <&3
+    set -l __sc_lines
+    for __sc_i in (seq $__sc_count)
+        read -l __sc_line <&3
+        set -a __sc_lines $__sc_line
+    end
+    eval (string join \n -- $__sc_lines | string collect) 3<&- 4>&-
+    printf '%s\n%s\n' $status $PWD >&4
+end"#
+            }
+            _ => {
+                r#"trap : INT
+trap '' TSTP
+__sc_return() { return "$1"; }
+while IFS= read -r __sc_count <&3; do
+    __sc_cmd=
+    while [ "$__sc_count" -gt 0 ]; do
+        IFS= read -r __sc_line <&3
+        __sc_cmd="$__sc_cmd$__sc_line
+"
+        __sc_count=$((__sc_count - 1))
+    done
+    eval "$__sc_cmd" 3<&- 4>&-
+    printf '%s\n%s\n' "$?" "$PWD" >&4
+done"#
+            }
+        }
+    }
+
+    fn export(self, name: &str, value: &str) -> String {
+        match self {
+            Self::Fish => format!("set -gx {} {}", name, self.quote(value)),
+            _ => format!("export {}={}", name, self.quote(value)),
+        }
+    }
+
+    /// Set a shell variable without exporting it
+    fn assign(self, name: &str, value: &str) -> String {
+        match self {
+            Self::Fish => format!("set -g {} {}", name, self.quote(value)),
+            _ => format!("{}={}", name, self.quote(value)),
+        }
+    }
+
+    fn unset(self, name: &str) -> String {
+        match self {
+            Self::Fish => format!("set -e {}", name),
+            _ => format!("unset {}", name),
+        }
+    }
+
+    fn cd(self, dir: &Path) -> String {
+        let dir = self.quote(&dir.display().to_string());
+        match self {
+            Self::Fish => format!("cd {}", dir),
+            _ => format!("cd -- {}", dir),
+        }
+    }
+}
+
+/// Where command lines run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// The built-in executor; `sh` only for syntax it does not support
+    #[default]
+    Native,
+    /// A known shell, by name or path
+    Shell { kind: ShellKind, program: String },
+    /// A custom argv; `{}` is replaced by the command line
+    Custom(Vec<String>),
+}
+
+impl Backend {
+    /// Parse the `exec.shell` setting
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let argv: Vec<String> = spec.split_whitespace().map(String::from).collect();
+        match argv.as_slice() {
+            [] => Ok(Self::Native),
+            [name] if name == "native" => Ok(Self::Native),
+            [program] => match ShellKind::from_program(program) {
+                Some(kind) => Ok(Self::Shell {
+                    kind,
+                    program: program.clone(),
+                }),
+                None => Err(format!(
+                    "exec.shell: unknown shell '{}' (use a template such as \"{} -c {{}}\")",
+                    program, program
+                )),
+            },
+            _ if argv.iter().any(|arg| arg.contains("{}")) => Ok(Self::Custom(argv)),
+            _ => Err(format!(
+                "exec.shell: '{}' has no {{}} placeholder for the command line",
+                spec
+            )),
+        }
+    }
+
+    pub fn is_native(&self) -> bool {
+        matches!(self, Self::Native)
+    }
+
+    /// Shell used for a one-shot run (`sh` for the native backend)
+    fn shell(&self) -> Option<(ShellKind, &str)> {
+        match self {
+            Self::Native => Some((ShellKind::Sh, "sh")),
+            Self::Shell { kind, program } => Some((*kind, program.as_str())),
+            Self::Custom(_) => None,
+        }
+    }
+
+    /// Whether the backend reports the directory a line ends in
+    pub fn reports_directory(&self) -> bool {
+        self.shell().is_some()
+    }
+
+    /// Command running `line` once
+    ///
+    /// Known shells write their final directory to `dir_file` and see
+    /// `last_status` as `$?`.
+    pub fn command(&self, line: &str, dir_file: Option<&Path>, last_status: i32) -> Command {
+        match (self.shell(), dir_file) {
+            (Some((kind, program)), Some(dir_file)) => {
+                let mut cmd = Command::new(program);
+                cmd.arg("-c").arg(kind.one_shot_script());
+                if kind != ShellKind::Fish {
+                    // `$0` for POSIX shells
+                    cmd.arg("sc");
+                }
+                cmd.arg(dir_file).arg(line).arg(last_status.to_string());
+                cmd
+            }
+            (Some((_, program)), None) => {
+                let mut cmd = Command::new(program);
+                cmd.arg("-c").arg(line);
+                cmd
+            }
+            (None, _) => {
+                let Self::Custom(template) = self else {
+                    unreachable!("only custom backends have no shell");
+                };
+                let mut cmd = Command::new(&template[0]);
+                cmd.args(template[1..].iter().map(|arg| arg.replace("{}", line)));
+                cmd
+            }
+        }
+    }
+}
+
+/// How a coprocess answered a command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// The line finished; the shell is ready for the next one
+    Done { status: i32, dir: PathBuf },
+    /// The deadline passed before the line finished
+    TimedOut,
+    /// The shell itself exited (e.g. the line ran `exit`)
+    Exited(i32),
+}
+
+/// A long-lived shell that runs one command line at a time
+pub struct Coprocess {
+    kind: ShellKind,
+    child: Child,
+    /// Requests to the shell (its fd 3)
+    requests: PipeWriter,
+    /// Replies from the shell (its fd 4)
+    replies: BufReader<PipeReader>,
+    /// Whether the shell leads its own process group
+    own_group: bool,
+    /// Session variables (value, exported) as last sent to the shell
+    vars: HashMap<String, (String, bool)>,
+    /// Directory the shell was last in
+    dir: Option<PathBuf>,
+}
+
+impl Coprocess {
+    /// Start a shell with sc's exported variables
+    ///
+    /// With `own_group` the shell gets its own process group, so it can be
+    /// given the terminal while a line runs.
+    pub fn spawn(kind: ShellKind, program: &str, own_group: bool) -> io::Result<Self> {
+        let (request_reader, requests) = io::pipe()?;
+        let (reply_reader, reply_writer) = io::pipe()?;
+        // Only exported variables reach it through the environment
+        let vars = synced_vars()
+            .into_iter()
+            .filter(|(_, (_, exported))| *exported)
+            .collect();
+
+        let mut cmd = Command::new(program);
+        cmd.arg("-c")
+            .arg(kind.coprocess_script())
+            .env_clear()
+            .envs(session_env().exported());
+        sys::prepare(&mut cmd, &request_reader, &reply_writer, own_group)?;
+        let child = cmd.spawn()?;
+
+        Ok(Self {
+            kind,
+            child,
+            requests,
+            replies: BufReader::new(reply_reader),
+            own_group,
+            vars,
+            dir: std::env::current_dir().ok(),
+        })
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    pub fn own_group(&self) -> bool {
+        self.own_group
+    }
+
+    /// Whether the shell is still running
+    pub fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Send a command line, preceded by whatever brings the shell's
+    /// directory, exported variables and `$?` in line with sc's
+    pub fn send(&mut self, line: &str, last_status: i32) -> io::Result<()> {
+        let mut script = self.sync_commands();
+        script.push(format!("__sc_return {}", last_status));
+        script.push(line.to_string());
+        let script = script.join("\n");
+        let lines: Vec<&str> = script.lines().collect();
+
+        writeln!(self.requests, "{}", lines.len())?;
+        for line in lines {
+            writeln!(self.requests, "{}", line)?;
+        }
+        self.requests.flush()
+    }
+
+    /// Commands that update the shell's directory and variables
+    fn sync_commands(&mut self) -> Vec<String> {
+        let mut commands = Vec::new();
+
+        let current = synced_vars();
+        for (name, var @ (value, exported)) in &current {
+            let sent = self.vars.get(name);
+            if sent == Some(var) {
+                continue;
+            }
+            // Only unsetting takes the export flag off again
+            if sent.is_some_and(|(_, was_exported)| *was_exported && !exported) {
+                commands.push(self.kind.unset(name));
+            }
+            if *exported {
+                commands.push(self.kind.export(name, value));
+            } else {
+                commands.push(self.kind.assign(name, value));
+            }
+        }
+        for name in self.vars.keys() {
+            if !current.contains_key(name) {
+                commands.push(self.kind.unset(name));
+            }
+        }
+        self.vars = current;
+
+        let cwd = std::env::current_dir().ok();
+        if let Some(cwd) = cwd
+            .as_ref()
+            .filter(|cwd| !same_dir(self.dir.as_deref(), cwd))
+        {
+            commands.push(self.kind.cd(cwd));
+        }
+        self.dir = cwd;
+
+        commands
+    }
+
+    /// Wait for the reply to the last line, until `deadline` if there is one
+    pub fn wait_reply(&mut self, deadline: Option<Instant>) -> io::Result<Reply> {
+        if self.replies.buffer().is_empty() && !sys::wait_readable(&self.replies, deadline)? {
+            return Ok(Reply::TimedOut);
+        }
+
+        let mut status = String::new();
+        let mut dir = String::new();
+        if self.replies.read_line(&mut status)? == 0 || self.replies.read_line(&mut dir)? == 0 {
+            let status = self.child.wait()?;
+            return Ok(Reply::Exited(crate::executor::status_code(status)));
+        }
+
+        let dir = PathBuf::from(dir.trim_end_matches('\n'));
+        self.dir = Some(dir.clone());
+        Ok(Reply::Done {
+            status: status.trim().parse().unwrap_or(1),
+            dir,
+        })
+    }
+
+    /// Stop the shell and everything it started
+    pub fn kill(mut self) {
+        sys::kill(&mut self.child, self.own_group);
+        let _ = self.child.wait();
+    }
+}
+
+/// Session variables that are sent to a coprocess, with their export flag
+fn synced_vars() -> HashMap<String, (String, bool)> {
+    let env = session_env();
+    env.vars()
+        .into_iter()
+        .filter(|(name, _)| !UNSYNCED_VARS.contains(&name.as_str()))
+        .map(|(name, value)| {
+            let exported = env.is_exported(&name);
+            (name, (value, exported))
+        })
+        .collect()
+}
+
+/// Compare directories, ignoring symlinks
+fn same_dir(known: Option<&Path>, dir: &Path) -> bool {
+    known.is_some_and(|known| known == dir || known.canonicalize().ok() == dir.canonicalize().ok())
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::io::{self, BufReader, PipeReader, PipeWriter};
+    use std::os::fd::{AsFd, AsRawFd};
+    use std::os::unix::process::CommandExt;
+    use std::process::{Child, Command};
+    use std::time::Instant;
+
+    /// Put the request pipe on the shell's fd 3 and the reply pipe on fd 4
+    pub fn prepare(
+        cmd: &mut Command,
+        requests: &PipeReader,
+        replies: &PipeWriter,
+        own_group: bool,
+    ) -> io::Result<()> {
+        let (request_fd, reply_fd) = (requests.as_raw_fd(), replies.as_raw_fd());
+        if own_group {
+            cmd.process_group(0);
+        }
+        unsafe {
+            cmd.pre_exec(move || {
+                // Move both ends out of the way first, so neither is clobbered
+                let request = libc::fcntl(request_fd, libc::F_DUPFD_CLOEXEC, 10);
+                let reply = libc::fcntl(reply_fd, libc::F_DUPFD_CLOEXEC, 10);
+                if request < 0
+                    || reply < 0
+                    || libc::dup2(request, 3) < 0
+                    || libc::dup2(reply, 4) < 0
+                {
+                    return Err(io::Error::last_os_error());
+                }
+                for signal in [
+                    libc::SIGINT,
+                    libc::SIGQUIT,
+                    libc::SIGTSTP,
+                    libc::SIGTTIN,
+                    libc::SIGTTOU,
+                ] {
+                    libc::signal(signal, libc::SIG_DFL);
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    /// Wait until a reply can be read; `false` when the deadline passed
+    pub fn wait_readable(
+        reader: &BufReader<PipeReader>,
+        deadline: Option<Instant>,
+    ) -> io::Result<bool> {
+        let mut pollfd = libc::pollfd {
+            fd: reader.get_ref().as_fd().as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        loop {
+            let timeout = match deadline {
+                Some(at) => at
+                    .saturating_duration_since(Instant::now())
+                    .as_millis()
+                    .min(i32::MAX as u128) as i32,
+                None => -1,
+            };
+            match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+                0 => return Ok(false),
+                n if n > 0 => return Ok(true),
+                _ => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn kill(child: &mut Child, own_group: bool) {
+        if own_group {
+            unsafe {
+                libc::killpg(child.id() as libc::pid_t, libc::SIGKILL);
+            }
+        } else {
+            let _ = child.kill();
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    use std::io::{self, BufReader, PipeReader, PipeWriter};
+    use std::process::{Child, Command};
+    use std::time::Instant;
+
+    pub fn prepare(
+        _cmd: &mut Command,
+        _requests: &PipeReader,
+        _replies: &PipeWriter,
+        _own_group: bool,
+    ) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "persistent shells are only supported on Unix",
+        ))
+    }
+
+    pub fn wait_readable(
+        _reader: &BufReader<PipeReader>,
+        _deadline: Option<Instant>,
+    ) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    pub fn kill(child: &mut Child, _own_group: bool) {
+        let _ = child.kill();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_backend() {
+        assert_eq!(Backend::parse("native"), Ok(Backend::Native));
+        assert_eq!(Backend::parse(""), Ok(Backend::Native));
+        assert_eq!(
+            Backend::parse("/usr/local/bin/zsh"),
+            Ok(Backend::Shell {
+                kind: ShellKind::Zsh,
+                program: "/usr/local/bin/zsh".to_string(),
+            })
+        );
+        assert_eq!(
+            Backend::parse("nu -c {}"),
+            Ok(Backend::Custom(vec![
+                "nu".to_string(),
+                "-c".to_string(),
+                "{}".to_string()
+            ]))
+        );
+        assert!(Backend::parse("nu").is_err());
+        assert!(Backend::parse("nu -c").is_err());
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(ShellKind::Bash.quote("it's"), r#"'it'\''s'"#);
+        assert_eq!(ShellKind::Fish.quote(r"it's \n"), r"'it\'s \\n'");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_coprocess_keeps_state() {
+        let mut shell = Coprocess::spawn(ShellKind::Sh, "sh", false).unwrap();
+
+        shell
+            .send("greet() { return 7; }; SC_TEST_COPROC=kept", 0)
+            .unwrap();
+        assert!(matches!(
+            shell.wait_reply(None).unwrap(),
+            Reply::Done { status: 0, .. }
+        ));
+
+        shell
+            .send("test \"$SC_TEST_COPROC\" = kept && greet", 0)
+            .unwrap();
+        assert!(matches!(
+            shell.wait_reply(None).unwrap(),
+            Reply::Done { status: 7, .. }
+        ));
+
+        shell.send("sleep 5", 0).unwrap();
+        let deadline = Instant::now() + std::time::Duration::from_millis(100);
+        assert_eq!(shell.wait_reply(Some(deadline)).unwrap(), Reply::TimedOut);
+        shell.kill();
+
+        let mut shell = Coprocess::spawn(ShellKind::Sh, "sh", false).unwrap();
+        shell.send("exit $?", 3).unwrap();
+        assert_eq!(shell.wait_reply(None).unwrap(), Reply::Exited(3));
+    }
+}
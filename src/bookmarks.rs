@@ -154,6 +154,22 @@ impl BookmarkManager {
         }
     }
 
+    /// Re-read the config file, dropping bookmarks it no longer has
+    ///
+    /// Returns whether a name, path or description changed; visit counts
+    /// and timestamps don't count.
+    pub fn reload(&mut self) -> bool {
+        let before = std::mem::take(&mut self.bookmarks);
+        self.load();
+        self.add_default_bookmarks();
+        self.bookmarks.len() != before.len()
+            || before.iter().any(|(name, old)| {
+                self.bookmarks
+                    .get(name)
+                    .is_none_or(|new| new.path != old.path || new.description != old.description)
+            })
+    }
+
     /// Save bookmarks to config file
     pub fn save(&self) -> std::io::Result<()> {
         if let Some(parent) = self.config_path.parent() {
@@ -212,8 +228,7 @@ impl BookmarkManager {
 
     /// Check if input is a bookmark reference (@name)
     pub fn try_resolve(&self, input: &str) -> Option<&PathBuf> {
-        if input.starts_with('@') {
-            let name = &input[1..];
+        if let Some(name) = input.strip_prefix('@') {
             self.bookmarks.get(name).map(|b| &b.path)
         } else {
             None
@@ -315,7 +330,7 @@ pub fn handle_bookmark_command(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::Path;
+    
 
     #[test]
     fn test_bookmark_creation() {
@@ -0,0 +1,204 @@
+//! Shell Builtins
+//!
+//! Commands that must run inside the REPL process rather than as
+//! child processes, so they can be used anywhere in a chain or pipe:
+//! - `cd` (with `cd -` / `cd -N`), `pushd`, `popd`, `dirs`
+//! - `export`, `unset`, `set`, `env` on the session environment
+//! - `config set-lang`
+//! - `alias` / `unalias`, `snippet`, `bookmark` / `bm`
+//! - `time` / `timer` (`time CMD` is timed by the executor), `plugin`, `example`
+
+use crate::aliases::{self, AliasManager};
+use crate::bookmarks::{self, BookmarkManager};
+use crate::completer::SmartCompleter;
+use crate::dirstack::{self, dir_stack};
+use crate::environment::{self, session_env};
+use crate::executor::Builtins;
+use crate::output::Output;
+use crate::plugins::{self, PluginManager};
+use crate::snippets::{self, SnippetManager};
+use crate::timer::{self, CommandTimer};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+/// Names handled by [`ShellBuiltins`]
+const BUILTIN_NAMES: &[&str] = &[
+    "cd",
+    "pushd",
+    "popd",
+    "dirs",
+    "config",
+    "alias",
+    "unalias",
+    "bookmark",
+    "bm",
+    "unbookmark",
+    "unbm",
+    "snippet",
+    "snip",
+    "time",
+    "timer",
+    "plugin",
+    "plugins",
+    "example",
+    "examples",
+    "ex",
+    "export",
+    "unset",
+    "set",
+    "env",
+];
+
+/// Builtin commands and the managers they operate on
+pub struct ShellBuiltins {
+    pub completer: SmartCompleter,
+    pub current_lang: Arc<RwLock<String>>,
+    pub alias_manager: AliasManager,
+    pub snippet_manager: SnippetManager,
+    pub bookmark_manager: BookmarkManager,
+    pub plugin_manager: PluginManager,
+    pub command_timer: CommandTimer,
+}
+
+impl ShellBuiltins {
+    pub fn new(completer: SmartCompleter, current_lang: Arc<RwLock<String>>) -> Self {
+        Self {
+            completer,
+            current_lang,
+            alias_manager: AliasManager::new(),
+            snippet_manager: SnippetManager::new(),
+            bookmark_manager: BookmarkManager::new(),
+            plugin_manager: PluginManager::new(),
+            command_timer: CommandTimer::new(),
+        }
+    }
+
+    /// Change directory, pushing the previous one on the directory stack
+    fn cd(&mut self, args: &[&str]) -> i32 {
+        let target_path: Option<PathBuf> = match args.first() {
+            // `cd -` / `cd -N`: jump to an entry of `dirs -v`
+            Some(arg) if dirstack::parse_back_ref(arg).is_some() => {
+                let n = dirstack::parse_back_ref(arg).unwrap_or(1);
+                if let Some(path) = dir_stack().get(n) {
+                    Output::dim(&dirstack::display_path(&path));
+                    Some(path)
+                } else if *arg == "-" {
+                    Output::error("cd: OLDPWD not set");
+                    return 1;
+                } else {
+                    Output::error(&format!("cd: {}: no such entry in the directory stack", arg));
+                    return 1;
+                }
+            }
+            Some(path) => Some(PathBuf::from(path)),
+            None => dirs::home_dir(),
+        };
+
+        let Some(target) = target_path else {
+            Output::error("cd: HOME not set");
+            return 1;
+        };
+
+        if let Err(e) = dir_stack().change_dir(&target) {
+            Output::error(&format!("cd: {}", e));
+            return 1;
+        }
+        0
+    }
+
+    /// Handle 'config' command
+    fn config(&mut self, args: &[&str]) -> i32 {
+        match args.first() {
+            Some(&"set-lang") => {
+                if let Some(lang) = args.get(1) {
+                    *self.current_lang.write().unwrap() = lang.to_string();
+                    Output::success(&format!("Language switched to: {}", lang));
+                    0
+                } else {
+                    Output::info("Usage: config set-lang <lang>");
+                    Output::dim("Available languages: en, zh");
+                    1
+                }
+            }
+            Some(sub) => {
+                Output::error(&format!("Unknown config subcommand: {}", sub));
+                Output::dim("Available: set-lang");
+                1
+            }
+            None => {
+                Output::info("Usage: config set-lang <lang>");
+                0
+            }
+        }
+    }
+}
+
+impl Builtins for ShellBuiltins {
+    fn is_builtin(&self, argv: &[String]) -> bool {
+        match argv[0].as_str() {
+            // `env CMD ...` runs the external env
+            "env" => argv.len() == 1,
+            // `time CMD ...` is timed by the executor
+            "time" => argv
+                .get(1)
+                .is_none_or(|arg| timer::TIME_SUBCOMMANDS.contains(&arg.as_str())),
+            name => BUILTIN_NAMES.contains(&name),
+        }
+    }
+
+    fn run_builtin(&mut self, argv: &[String], out: &mut dyn Write) -> i32 {
+        let cmd = argv[0].as_str();
+        let args: Vec<&str> = argv[1..].iter().map(String::as_str).collect();
+
+        let output = match cmd {
+            "cd" => return self.cd(&args),
+            "config" => return self.config(&args),
+            "pushd" | "popd" | "dirs" => {
+                match dirstack::handle_dirstack_command(dir_stack(), cmd, &args) {
+                    Ok(output) => output,
+                    Err(e) => {
+                        Output::error(&e);
+                        return 1;
+                    }
+                }
+            }
+            "export" | "unset" | "set" | "env" => {
+                match environment::handle_env_command(session_env(), cmd, &args) {
+                    Ok(output) => output,
+                    Err(e) => {
+                        Output::error(&e);
+                        return 1;
+                    }
+                }
+            }
+            "alias" | "unalias" => {
+                aliases::handle_alias_command(&mut self.alias_manager, cmd, &args)
+            }
+            "bookmark" | "bm" | "unbookmark" | "unbm" => {
+                let cwd = std::env::current_dir().unwrap_or_default();
+                bookmarks::handle_bookmark_command(&mut self.bookmark_manager, cmd, &args, &cwd)
+            }
+            "snippet" | "snip" => {
+                snippets::handle_snippet_command(&mut self.snippet_manager, cmd, &args)
+            }
+            "time" | "timer" => timer::handle_timer_command(&self.command_timer, cmd, &args),
+            "plugin" | "plugins" => {
+                plugins::handle_plugin_command(&mut self.plugin_manager, cmd, &args)
+            }
+            "example" | "examples" | "ex" => {
+                let lang = self.current_lang.read().unwrap().clone();
+                // The reader may have gone away (e.g. `example git | head -3`)
+                crate::handle_example_command(&self.completer, &args, &lang, out).ok();
+                None
+            }
+            _ => return 1,
+        };
+
+        if let Some(output) = output {
+            // The reader may have gone away (e.g. `alias | head -1`)
+            writeln!(out, "{}", output).ok();
+        }
+        0
+    }
+}
@@ -0,0 +1,266 @@
+//! The loaded command set, its search index and the on-disk cache of both
+//!
+//! Parsing every definition with serde_yaml on each start gets noticeable as
+//! definitions pile up, and the one-shot paths (`sc -c`, `sc search`,
+//! `sc examples`) pay for it too. The merged specs and a flat search index
+//! are therefore cached as JSON in the cache dir. The cache is keyed on the
+//! sc version, the executable and the path, size and mtime of every
+//! definition file, in layer order; when any of them differs the cache is
+//! rebuilt transparently. `sc defs rebuild-cache` forces a rebuild.
+
+use crate::command_def::{CommandSpec, Example, I18nString};
+use crate::definitions;
+use crate::loader::{self, Definitions, Layer};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::debug;
+
+/// One command or subcommand, flattened for searching
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Full command path (e.g., "git commit")
+    pub path: String,
+    /// Last word of the path
+    pub name: String,
+    pub description: I18nString,
+    pub examples: Vec<Example>,
+}
+
+/// Every command path of a command set, sorted by path
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchIndex {
+    entries: Vec<IndexEntry>,
+}
+
+impl SearchIndex {
+    pub fn build<'a>(specs: impl IntoIterator<Item = &'a CommandSpec>) -> Self {
+        fn add(spec: &CommandSpec, path: String, entries: &mut Vec<IndexEntry>) {
+            for sub in &spec.subcommands {
+                add(sub, format!("{} {}", path, sub.name), entries);
+            }
+            entries.push(IndexEntry {
+                path,
+                name: spec.name.clone(),
+                description: spec.description.clone(),
+                examples: spec.examples.clone(),
+            });
+        }
+
+        let mut entries = Vec::new();
+        for spec in specs {
+            add(spec, spec.name.clone(), &mut entries);
+        }
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Self { entries }
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Entry for a full command path
+    pub fn get(&self, path: &str) -> Option<&IndexEntry> {
+        self.entries
+            .binary_search_by(|e| e.path.as_str().cmp(path))
+            .ok()
+            .map(|i| &self.entries[i])
+    }
+}
+
+/// The global command set: loaded definitions plus the built-in specs they
+/// don't override, with its search index
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Catalog {
+    pub specs: Vec<CommandSpec>,
+    pub index: SearchIndex,
+}
+
+impl Catalog {
+    pub fn new(loaded: Vec<CommandSpec>) -> Self {
+        let mut specs = loaded;
+        for spec in definitions::other_specs() {
+            if !specs.iter().any(|s| s.name == spec.name) {
+                specs.push(spec);
+            }
+        }
+        specs.sort_by(|a, b| a.name.cmp(&b.name));
+        let index = SearchIndex::build(&specs);
+        Self { specs, index }
+    }
+}
+
+/// Size and mtime of a file the cache depends on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Stamp {
+    path: PathBuf,
+    len: u64,
+    modified_nanos: u128,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            path: path.to_path_buf(),
+            len: meta.len(),
+            modified_nanos: modified.as_nanos(),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: String,
+    stamps: Vec<Stamp>,
+    catalog: Catalog,
+}
+
+/// Default cache location
+pub fn cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|d| d.join("smart-command").join("definitions-cache.json"))
+}
+
+/// Everything the cached result depends on, in load order
+fn stamps(layers: &[Layer]) -> Vec<Stamp> {
+    let mut files: Vec<PathBuf> = std::env::current_exe().into_iter().collect();
+    for layer in layers {
+        files.extend(loader::yaml_files(&layer.dir));
+    }
+    files.iter().filter_map(|f| Stamp::of(f)).collect()
+}
+
+/// Load the catalog for these layers, from the cache when it is current
+pub fn load(layers: &[Layer], cache: Option<&Path>) -> Catalog {
+    let stamps = stamps(layers);
+    if let Some(catalog) = cache.and_then(|path| read_cache(path, &stamps)) {
+        debug!(
+            "Loaded {} commands from the definition cache",
+            catalog.specs.len()
+        );
+        return catalog;
+    }
+
+    let catalog = Catalog::new(Definitions::load(layers).into_specs());
+    if let Some(path) = cache {
+        if let Err(e) = write_cache(path, stamps, &catalog) {
+            debug!("Cannot write definition cache {}: {}", path.display(), e);
+        }
+    }
+    catalog
+}
+
+/// Rebuild the cache from the definition files, ignoring what is there
+pub fn rebuild(layers: &[Layer], cache: &Path) -> anyhow::Result<Catalog> {
+    let catalog = Catalog::new(Definitions::load(layers).into_specs());
+    write_cache(cache, stamps(layers), &catalog)?;
+    Ok(catalog)
+}
+
+fn read_cache(path: &Path, stamps: &[Stamp]) -> Option<Catalog> {
+    let content = fs::read(path).ok()?;
+    let cache: CacheFile = serde_json::from_slice(&content).ok()?;
+    (cache.version == env!("CARGO_PKG_VERSION") && cache.stamps == stamps).then_some(cache.catalog)
+}
+
+/// Write through a temporary file so a concurrent start never reads half a cache
+fn write_cache(path: &Path, stamps: Vec<Stamp>, catalog: &Catalog) -> anyhow::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    serde_json::to_writer(
+        &mut file,
+        &CacheFile {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            stamps,
+            catalog: catalog.clone(),
+        },
+    )?;
+    file.flush()?;
+    file.persist(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::LayerKind;
+
+    fn layer(dir: &Path) -> Vec<Layer> {
+        vec![Layer {
+            kind: LayerKind::Custom,
+            dir: dir.to_path_buf(),
+        }]
+    }
+
+    #[test]
+    fn test_search_index() {
+        let git: CommandSpec = serde_yaml::from_str(
+            r#"
+name: git
+description: Git
+subcommands:
+  - name: commit
+    description: Record changes
+    examples:
+      - scenario: Amend
+        cmd: git commit --amend
+"#,
+        )
+        .unwrap();
+        let index = SearchIndex::build([&git]);
+        let paths: Vec<&str> = index.entries().iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, ["git", "git commit"]);
+
+        let commit = index.get("git commit").unwrap();
+        assert_eq!(commit.name, "commit");
+        assert_eq!(commit.examples[0].cmd, "git commit --amend");
+        assert!(index.get("git push").is_none());
+    }
+
+    #[test]
+    fn test_cache_is_rebuilt_when_stale() {
+        let defs = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = cache_dir.path().join("cache.json");
+        fs::write(
+            defs.path().join("tool.yaml"),
+            "name: tool\ndescription: Tool\n",
+        )
+        .unwrap();
+
+        let catalog = load(&layer(defs.path()), Some(&cache));
+        assert!(catalog.specs.iter().any(|s| s.name == "tool"));
+        assert!(cache.exists());
+
+        // A cache that still matches is used as is
+        let stamps = stamps(&layer(defs.path()));
+        let cached = read_cache(&cache, &stamps).unwrap();
+        assert_eq!(cached.specs.len(), catalog.specs.len());
+
+        // A new file makes it stale
+        fs::write(
+            defs.path().join("other.yaml"),
+            "name: other\ndescription: Other\n",
+        )
+        .unwrap();
+        let stamps = super::stamps(&layer(defs.path()));
+        assert!(read_cache(&cache, &stamps).is_none());
+
+        let catalog = load(&layer(defs.path()), Some(&cache));
+        assert!(catalog.specs.iter().any(|s| s.name == "other"));
+        assert!(catalog.index.get("other").is_some());
+        assert!(read_cache(&cache, &stamps).is_some());
+    }
+
+    #[test]
+    fn test_builtin_specs_fill_gaps() {
+        let catalog = Catalog::new(vec![CommandSpec::new("ls", "Custom ls")]);
+        let ls: Vec<_> = catalog.specs.iter().filter(|s| s.name == "ls").collect();
+        assert_eq!(ls.len(), 1);
+        assert_eq!(ls[0].description.get("en"), "Custom ls");
+    }
+}
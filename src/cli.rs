@@ -7,6 +7,7 @@
 
 use clap::{CommandFactory, Parser, Subcommand};
 use clap_complete::{generate, Shell};
+use crate::import::ImportFormat;
 use std::io;
 use std::path::PathBuf;
 
@@ -32,10 +33,18 @@ pub struct Cli {
     #[arg(long)]
     pub no_danger_protection: bool,
 
+    /// Skip init.sc and .sc-init startup files
+    #[arg(long)]
+    pub norc: bool,
+
     /// Execute a single command and exit
     #[arg(short = 'c', long)]
     pub command: Option<String>,
 
+    /// Script to run (shebang mode: `#!/usr/bin/env sc`)
+    #[arg(value_name = "SCRIPT")]
+    pub script: Option<PathBuf>,
+
     #[command(subcommand)]
     pub subcommand: Option<Commands>,
 }
@@ -106,6 +115,20 @@ pub enum Commands {
         target_version: Option<String>,
     },
 
+    /// Run a script of sc command lines
+    Run {
+        /// Script file to run
+        script: PathBuf,
+
+        /// Keep running after a line fails
+        #[arg(short = 'k', long)]
+        continue_on_error: bool,
+
+        /// Print each expanded line instead of running it
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+
     /// Show examples for commands
     Example {
         /// Command to show examples for (e.g., "git", "git commit")
@@ -117,6 +140,62 @@ pub enum Commands {
         #[arg(short, long)]
         search: Option<String>,
     },
+
+    /// Inspect and maintain command definitions
+    Defs {
+        #[command(subcommand)]
+        action: DefsAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DefsAction {
+    /// Show which definition file supplied each part of a command
+    Which {
+        /// Command or subcommand path (e.g., "git", "git commit")
+        #[arg(required = true, trailing_var_arg = true)]
+        command: Vec<String>,
+    },
+
+    /// Check definition files for errors (default: every definitions layer)
+    Lint {
+        /// Definition files or directories to check
+        paths: Vec<PathBuf>,
+    },
+
+    /// Print the JSON Schema for definition files
+    Schema,
+
+    /// Draft a definition from a command's --help output or man page
+    Generate {
+        /// Command to generate a definition for
+        command: String,
+
+        /// Read the man page instead of --help
+        #[arg(long)]
+        man: bool,
+
+        /// Write the YAML to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Rebuild the cache of parsed definitions and their search index
+    RebuildCache,
+
+    /// Convert a Fig, carapace or fish completion spec into a definition
+    Import {
+        /// Format of the spec file
+        #[arg(long = "from", value_enum)]
+        from: ImportFormat,
+
+        /// Spec file to convert
+        file: PathBuf,
+
+        /// Write the YAML to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -196,11 +275,37 @@ mod tests {
         let cli = Cli::parse_from(["smart-command"]);
         assert!(cli.lang.is_none());
         assert!(!cli.verbose);
+        assert!(!cli.norc);
 
         // Test with flags
         let cli = Cli::parse_from(["smart-command", "-l", "zh", "-v"]);
         assert_eq!(cli.lang, Some("zh".to_string()));
         assert!(cli.verbose);
+
+        let cli = Cli::parse_from(["smart-command", "--norc"]);
+        assert!(cli.norc);
+    }
+
+    #[test]
+    fn test_script_parsing() {
+        let cli = Cli::parse_from(["smart-command", "run", "deploy.sc", "-k", "--dry-run"]);
+        match cli.subcommand {
+            Some(Commands::Run {
+                script,
+                continue_on_error,
+                dry_run,
+            }) => {
+                assert_eq!(script, PathBuf::from("deploy.sc"));
+                assert!(continue_on_error);
+                assert!(dry_run);
+            }
+            other => panic!("expected run subcommand, got {:?}", other),
+        }
+
+        // Shebang mode passes the script path directly
+        let cli = Cli::parse_from(["smart-command", "./deploy.sc"]);
+        assert_eq!(cli.script, Some(PathBuf::from("./deploy.sc")));
+        assert!(cli.subcommand.is_none());
     }
 
     #[test]
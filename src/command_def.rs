@@ -1,7 +1,8 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(untagged)]
 pub enum I18nString {
     Simple(String),
@@ -9,6 +10,13 @@ pub enum I18nString {
 }
 
 impl I18nString {
+    /// An `en`-only string, for drafts that still need a `zh` translation
+    pub fn english(text: &str) -> Self {
+        let mut map = HashMap::new();
+        map.insert("en".to_string(), text.to_string());
+        I18nString::Map(map)
+    }
+
     pub fn get(&self, lang: &str) -> &str {
         match self {
             I18nString::Simple(s) => s,
@@ -21,6 +29,12 @@ impl I18nString {
     }
 }
 
+impl Default for I18nString {
+    fn default() -> Self {
+        I18nString::Simple(String::new())
+    }
+}
+
 // Implement From<String> for easier construction
 impl From<String> for I18nString {
     fn from(s: String) -> Self {
@@ -34,15 +48,16 @@ impl From<&str> for I18nString {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Example {
     pub scenario: I18nString,
     pub cmd: String,
 }
 
 /// Argument type for validation and completion
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
+#[derive(Default)]
 pub enum ArgumentType {
     /// Plain string argument
     String,
@@ -73,17 +88,13 @@ pub enum ArgumentType {
     /// JSON string
     Json,
     /// Any value (default, no validation)
+    #[default]
     Any,
 }
 
-impl Default for ArgumentType {
-    fn default() -> Self {
-        ArgumentType::Any
-    }
-}
 
 /// Path filter configuration for YAML
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct PathFilterConfig {
     /// Only show files with these extensions
     #[serde(default)]
@@ -103,7 +114,7 @@ pub struct PathFilterConfig {
 }
 
 /// Argument specification for commands
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ArgumentSpec {
     /// Position of this argument (0-indexed, after command/subcommand)
     #[serde(default)]
@@ -128,33 +139,136 @@ pub struct ArgumentSpec {
     pub provider: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl ArgumentSpec {
+    /// Provider completing this argument: the `provider` shorthand, or a
+    /// `type: provider`
+    pub fn provider_name(&self) -> Option<&str> {
+        if let Some(ref provider) = self.provider {
+            return Some(provider.as_str());
+        }
+        if let ArgumentType::Provider { ref name } = self.arg_type {
+            return Some(name.as_str());
+        }
+        None
+    }
+
+    /// `<name>` for placeholders
+    pub fn placeholder(&self) -> String {
+        format!("<{}>", self.name.as_deref().unwrap_or("arg"))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CommandSpec {
     pub name: String,
     pub description: I18nString,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub subcommands: Vec<CommandSpec>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub flags: Vec<FlagSpec>,
-    #[serde(default)]
+    /// `flags` lists every flag the tool takes, so checking a line reports
+    /// any other as unknown
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub flags_complete: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub examples: Vec<Example>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub is_path_completion: bool,
     /// Arguments specification for validation and dynamic completion
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub arguments: Vec<ArgumentSpec>,
+    /// Timeout in seconds, overriding `exec.timeout_secs` (0 = no timeout)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_timeout: Option<u64>,
+    /// Named flag groups this spec, its subcommands and other files can `use`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub flag_sets: BTreeMap<String, Vec<FlagSpec>>,
+    /// Named argument groups, like `flag_sets`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub argument_sets: BTreeMap<String, Vec<ArgumentSpec>>,
+    /// Sets to include: `name` from this spec or a parent, or `command.name`
+    /// from another file's top level
+    #[serde(default, rename = "use", skip_serializing_if = "Vec::is_empty")]
+    pub uses: Vec<String>,
+    /// Command path to inherit flags, arguments, examples and subcommands
+    /// from (e.g., "kubectl get")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    /// Command that prints the tool's version (e.g., "git --version"), run
+    /// when `since` or `until` need checking
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_command: Option<String>,
+    /// Regex picking the version out of `version_command`'s output; the first
+    /// capture group (or the whole match) is used. Defaults to the first
+    /// dotted number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_regex: Option<String>,
+    /// First tool version that has it (e.g., "2.23")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    /// First tool version that no longer has it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+    /// Still works but should not be used; says what to use instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
+    /// Runs the command given in its arguments (sudo, env, xargs), which is
+    /// then completed with its own definition
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub wraps: bool,
+    /// Arguments before the wrapped command (1 for `timeout 5 cmd`); flags
+    /// and `NAME=value` words are skipped anyway
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wraps_after: Option<usize>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct FlagSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub long: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub short: Option<char>,
     pub description: I18nString,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub takes_value: bool,
     /// Type of the flag's value for validation
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub value_type: Option<ArgumentType>,
+    /// Flags that cannot be combined with this one, by long name (or short
+    /// letter when there is no long name)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts_with: Vec<String>,
+    /// Flags that must also be given when this one is
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+    /// May be given more than once (e.g., `-v -v`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub repeatable: bool,
+    /// Also accepts `--no-<long>` to turn it off
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub negatable: bool,
+    /// The value is attached with `=` (`--format=json`) rather than passed as
+    /// the next word
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub equals: bool,
+    /// First tool version that has it (e.g., "2.23")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    /// First tool version that no longer has it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+    /// Still works but should not be used; says what to use instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
+}
+
+impl FlagSpec {
+    /// Whether `name` (a long name or a short letter) refers to this flag
+    pub fn is_named(&self, name: &str) -> bool {
+        let name = name.trim_start_matches('-');
+        self.long.as_deref() == Some(name)
+            || self.short.is_some_and(|c| name.chars().eq([c]))
+    }
 }
 
 impl CommandSpec {
@@ -164,29 +278,36 @@ impl CommandSpec {
             description: I18nString::Simple(description.to_string()),
             subcommands: vec![],
             flags: vec![],
+            flags_complete: false,
             examples: vec![],
             is_path_completion: false,
             arguments: vec![],
+            default_timeout: None,
+            flag_sets: BTreeMap::new(),
+            argument_sets: BTreeMap::new(),
+            uses: vec![],
+            extends: None,
+            version_command: None,
+            version_regex: None,
+            since: None,
+            until: None,
+            deprecated: None,
+            wraps: false,
+            wraps_after: None,
         }
     }
 
-    /// Get the provider name for a given argument position
-    #[allow(dead_code)]
-    pub fn get_provider_for_position(&self, position: usize) -> Option<&str> {
-        self.arguments
-            .iter()
-            .find(|arg| arg.position == Some(position) || (arg.variadic && arg.position.map(|p| position >= p).unwrap_or(false)))
-            .and_then(|arg| {
-                // Check explicit provider field first
-                if let Some(ref provider) = arg.provider {
-                    return Some(provider.as_str());
-                }
-                // Then check argument type
-                if let ArgumentType::Provider { ref name } = arg.arg_type {
-                    return Some(name.as_str());
-                }
-                None
-            })
+    /// The argument at a position (0 = first word after the command path)
+    ///
+    /// Arguments without a `position` take the place they are listed at; a
+    /// variadic argument also covers every position after its own.
+    pub fn argument_at(&self, position: usize) -> Option<&ArgumentSpec> {
+        let at = |(i, arg): &(usize, &ArgumentSpec)| arg.position.unwrap_or(*i);
+        let arguments = || self.arguments.iter().enumerate();
+        arguments()
+            .find(|entry| at(entry) == position)
+            .or_else(|| arguments().find(|entry| entry.1.variadic && position >= at(entry)))
+            .map(|(_, arg)| arg)
     }
 
     #[allow(dead_code)]
@@ -213,3 +334,37 @@ impl CommandSpec {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_argument_at() {
+        let spec: CommandSpec = serde_yaml::from_str(
+            r#"
+name: push
+description: Push
+arguments:
+  - name: remote
+    provider: git_remote
+  - name: refspec
+    type:
+      type: provider
+      name: git_branch
+    variadic: true
+"#,
+        )
+        .unwrap();
+        let name = |position| spec.argument_at(position).and_then(|a| a.name.as_deref());
+        assert_eq!(name(0), Some("remote"));
+        assert_eq!(name(1), Some("refspec"));
+        assert_eq!(name(5), Some("refspec"));
+        assert_eq!(spec.arguments[0].provider_name(), Some("git_remote"));
+        assert_eq!(spec.arguments[1].provider_name(), Some("git_branch"));
+        assert_eq!(spec.arguments[1].placeholder(), "<refspec>");
+
+        let single = CommandSpec::new("tool", "Tool");
+        assert!(single.argument_at(0).is_none());
+    }
+}
@@ -1,108 +1,195 @@
-use crate::command_def::CommandSpec;
+use crate::argument::CommandValidator;
+use crate::catalog::{Catalog, IndexEntry, SearchIndex};
+use crate::command_def::{ArgumentType, CommandSpec, PathFilterConfig};
 use crate::context::tracker;
-use crate::definitions;
+use crate::flags::{self, Violation};
+use crate::loader::{self, Layer, LayerKind};
+use crate::pipeline::PipelineSuggestions;
+use crate::providers::path::{PathFilter, PathProvider};
 use crate::providers::{self, ProviderContext, ProviderSuggestion};
+use crate::tokenizer::{self, Token, TokenKind};
+use crate::versions::{self, versions};
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
 use reedline::{Completer, Span, Suggestion};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Loaded specs, shared by every clone of the completer
+#[derive(Default)]
+struct CommandSet {
+    /// Specs from the global layers, with their search index
+    global: Catalog,
+    /// Project definitions directory merged into `commands`
+    project_dir: Option<PathBuf>,
+    /// Whether the user trusted each project definitions directory met so
+    /// far; only trusted ones are merged
+    project_trust: HashMap<PathBuf, bool>,
+    /// Global specs with the current project's specs on top
+    commands: HashMap<String, CommandSpec>,
+    /// Search index of `commands`
+    index: SearchIndex,
+}
+
+impl CommandSet {
+    /// Recompute `commands` and `index` from the global specs and project
+    fn merge_project(&mut self) {
+        let global: HashMap<String, CommandSpec> = self
+            .global
+            .specs
+            .iter()
+            .map(|spec| (spec.name.clone(), spec.clone()))
+            .collect();
+        match &self.project_dir {
+            Some(dir) => {
+                self.commands = loader::overlay_layer(
+                    &global,
+                    &Layer {
+                        kind: LayerKind::Project,
+                        dir: dir.clone(),
+                    },
+                );
+                self.index = SearchIndex::build(self.commands.values());
+            }
+            None => {
+                self.commands = global;
+                self.index = self.global.index.clone();
+            }
+        }
+    }
+}
 
 pub struct SmartCompleter {
-    commands: HashMap<String, CommandSpec>,
+    set: Arc<RwLock<CommandSet>>,
     current_lang: Arc<RwLock<String>>,
 }
 
 impl Clone for SmartCompleter {
     fn clone(&self) -> Self {
         Self {
-            commands: self.commands.clone(),
+            set: self.set.clone(),
             current_lang: self.current_lang.clone(),
         }
     }
 }
 
 impl SmartCompleter {
-    pub fn new(loaded_commands: Vec<CommandSpec>, current_lang: Arc<RwLock<String>>) -> Self {
-        let mut commands = HashMap::new();
-        for cmd in loaded_commands {
-            commands.insert(cmd.name.clone(), cmd);
-        }
-
-        for cmd in definitions::other_specs() {
-            if !commands.contains_key(&cmd.name) {
-                commands.insert(cmd.name.clone(), cmd);
-            }
-        }
-
-        Self {
-            commands,
+    pub fn new(catalog: Catalog, current_lang: Arc<RwLock<String>>) -> Self {
+        let mut set = CommandSet {
+            global: catalog,
+            ..Default::default()
+        };
+        set.merge_project();
+        let completer = Self {
+            set: Arc::new(RwLock::new(set)),
             current_lang,
-        }
+        };
+        completer.sync_project();
+        completer
     }
 
-    /// Get examples for a specific command path (e.g., "git", "git commit")
-    pub fn get_examples(&self, command_path: &str, lang: &str) -> Vec<(String, String)> {
-        let parts: Vec<&str> = command_path.split_whitespace().collect();
-        if parts.is_empty() {
-            return vec![];
+    /// Replace the global specs after a definition file changed
+    ///
+    /// The current project's specs are re-read and merged on top again.
+    pub fn reload(&self, catalog: Catalog) {
+        let mut set = self.set.write().unwrap();
+        set.global = catalog;
+        set.merge_project();
+    }
+
+    /// Merge or drop project definitions to match the current directory
+    ///
+    /// Cheap to call often: the directory lookup is cached per cwd by the
+    /// context tracker, and specs are only reloaded when the project changes.
+    /// Definitions the user hasn't trusted (see [`Self::set_project_trust`])
+    /// are left out.
+    pub fn sync_project(&self) {
+        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let project_dir = tracker()
+            .get_project_definitions(&cwd)
+            .filter(|dir| self.set.read().unwrap().project_trust.get(dir) == Some(&true));
+        if self.set.read().unwrap().project_dir == project_dir {
+            return;
         }
 
-        // Find root command
-        let root_name = parts[0];
-        let root_spec = match self.commands.get(root_name) {
-            Some(spec) => spec,
-            None => return vec![],
-        };
+        let mut set = self.set.write().unwrap();
+        set.project_dir = project_dir;
+        set.merge_project();
+    }
 
-        // Descend along path
-        let mut current = root_spec;
-        for part in &parts[1..] {
-            match current.subcommands.iter().find(|s| s.name == *part) {
-                Some(sub) => current = sub,
-                None => return vec![],
-            }
-        }
+    /// Project definitions directory of the current directory that has been
+    /// neither trusted nor refused yet
+    pub fn untrusted_project(&self) -> Option<PathBuf> {
+        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let dir = tracker().get_project_definitions(&cwd)?;
+        let decided = self.set.read().unwrap().project_trust.contains_key(&dir);
+        (!decided).then_some(dir)
+    }
 
-        // Return examples
-        current
-            .examples
-            .iter()
-            .map(|e| (e.cmd.clone(), e.scenario.get(lang).to_string()))
-            .collect()
+    /// Record whether the definitions in `dir` may be used, for this session
+    pub fn set_project_trust(&self, dir: PathBuf, trusted: bool) {
+        self.set.write().unwrap().project_trust.insert(dir, trusted);
+        self.sync_project();
     }
 
-    /// Get all commands that have examples
-    pub fn get_commands_with_examples(&self) -> Vec<String> {
-        let mut result = Vec::new();
-        for (name, cmd) in &self.commands {
-            Self::collect_commands_with_examples(cmd, name, &mut result);
-        }
-        result.sort();
-        result
+    /// Project definitions directory merged into the current specs
+    pub fn project_dir(&self) -> Option<PathBuf> {
+        self.set.read().unwrap().project_dir.clone()
     }
 
-    fn collect_commands_with_examples(cmd: &CommandSpec, path: &str, result: &mut Vec<String>) {
-        if !cmd.examples.is_empty() {
-            result.push(path.to_string());
-        }
-        for sub in &cmd.subcommands {
-            let sub_path = format!("{} {}", path, sub.name);
-            Self::collect_commands_with_examples(sub, &sub_path, result);
+    /// Get examples for a specific command path (e.g., "git", "git commit")
+    pub fn get_examples(&self, command_path: &str, lang: &str) -> Vec<(String, String)> {
+        let path = command_path.split_whitespace().collect::<Vec<_>>().join(" ");
+        let set = self.set.read().unwrap();
+        match set.index.get(&path) {
+            Some(entry) => entry
+                .examples
+                .iter()
+                .map(|e| (e.cmd.clone(), e.scenario.get(lang).to_string()))
+                .collect(),
+            None => vec![],
         }
     }
 
+    /// Get all commands that have examples
+    pub fn get_commands_with_examples(&self) -> Vec<String> {
+        self.set
+            .read()
+            .unwrap()
+            .index
+            .entries()
+            .iter()
+            .filter(|entry| !entry.examples.is_empty())
+            .map(|entry| entry.path.clone())
+            .collect()
+    }
+
     /// Search examples by query (returns command path, example cmd, scenario)
     pub fn search_examples(&self, query: &str, lang: &str) -> Vec<(String, String, String)> {
         let mut results: Vec<(i64, String, String, String)> = Vec::new();
 
-        for (name, cmd) in &self.commands {
-            self.search_examples_recursive(cmd, query, lang, name, &mut results);
+        for entry in self.set.read().unwrap().index.entries() {
+            for example in &entry.examples {
+                let scenario = example.scenario.get(lang);
+                // Match against scenario or command
+                let score_scenario = self.fuzzy_match(query, scenario);
+                let score_cmd = self.fuzzy_match(query, &example.cmd);
+
+                if let Some(score) = score_scenario.or(score_cmd) {
+                    results.push((
+                        score,
+                        entry.path.clone(),
+                        example.cmd.clone(),
+                        scenario.to_string(),
+                    ));
+                }
+            }
         }
 
         // Sort by score
-        results.sort_by(|a, b| b.0.cmp(&a.0));
+        results.sort_by_key(|r| std::cmp::Reverse(r.0));
 
         results
             .into_iter()
@@ -110,36 +197,6 @@ impl SmartCompleter {
             .collect()
     }
 
-    fn search_examples_recursive(
-        &self,
-        cmd: &CommandSpec,
-        query: &str,
-        lang: &str,
-        path: &str,
-        results: &mut Vec<(i64, String, String, String)>,
-    ) {
-        for example in &cmd.examples {
-            let scenario = example.scenario.get(lang);
-            // Match against scenario or command
-            let score_scenario = self.fuzzy_match(query, scenario);
-            let score_cmd = self.fuzzy_match(query, &example.cmd);
-
-            if let Some(score) = score_scenario.or(score_cmd) {
-                results.push((
-                    score,
-                    path.to_string(),
-                    example.cmd.clone(),
-                    scenario.to_string(),
-                ));
-            }
-        }
-
-        for sub in &cmd.subcommands {
-            let sub_path = format!("{} {}", path, sub.name);
-            self.search_examples_recursive(sub, query, lang, &sub_path, results);
-        }
-    }
-
     /// True fuzzy match - allows non-contiguous character matching
     /// e.g., "cm" matches "commit", "gco" matches "git checkout"
     fn fuzzy_match(&self, input: &str, target: &str) -> Option<i64> {
@@ -160,8 +217,8 @@ impl SmartCompleter {
         let mut results: Vec<(i64, String, String, String)> = Vec::new(); // (score, cmd, desc, match_type)
         let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
 
-        for cmd in self.commands.values() {
-            self.search_command_recursive(cmd, query, lang, &cmd.name, &mut results);
+        for entry in self.set.read().unwrap().index.entries() {
+            self.search_entry(entry, query, lang, &mut results);
         }
 
         // Apply context-aware boosting to scores
@@ -172,7 +229,7 @@ impl SmartCompleter {
         }
 
         // Sort by score (higher is better)
-        results.sort_by(|a, b| b.0.cmp(&a.0));
+        results.sort_by_key(|r| std::cmp::Reverse(r.0));
 
         // Remove duplicates and return without scores
         let mut seen = std::collections::HashSet::new();
@@ -183,37 +240,36 @@ impl SmartCompleter {
             .collect()
     }
 
-    fn search_command_recursive(
+    fn search_entry(
         &self,
-        cmd: &CommandSpec,
+        entry: &IndexEntry,
         query: &str,
         lang: &str,
-        full_cmd: &str,
         results: &mut Vec<(i64, String, String, String)>,
     ) {
         // Search in command name using fuzzy matching
-        if let Some(score) = self.fuzzy_match(query, &cmd.name) {
+        if let Some(score) = self.fuzzy_match(query, &entry.name) {
             results.push((
                 score + 100, // Boost command name matches
-                full_cmd.to_string(),
-                cmd.description.get(lang).to_string(),
-                format!("Command: {}", cmd.name),
+                entry.path.clone(),
+                entry.description.get(lang).to_string(),
+                format!("Command: {}", entry.name),
             ));
         }
 
         // Search in description using fuzzy matching
-        let desc = cmd.description.get(lang);
+        let desc = entry.description.get(lang);
         if let Some(score) = self.fuzzy_match(query, desc) {
             results.push((
                 score,
-                full_cmd.to_string(),
+                entry.path.clone(),
                 desc.to_string(),
                 "Description".to_string(),
             ));
         }
 
         // Search in examples
-        for example in &cmd.examples {
+        for example in &entry.examples {
             let scenario = example.scenario.get(lang);
             if let Some(score) = self.fuzzy_match(query, scenario) {
                 results.push((
@@ -224,12 +280,6 @@ impl SmartCompleter {
                 ));
             }
         }
-
-        // Search in subcommands
-        for sub in &cmd.subcommands {
-            let sub_full_cmd = format!("{} {}", full_cmd, sub.name);
-            self.search_command_recursive(sub, query, lang, &sub_full_cmd, results);
-        }
     }
 
     pub fn search(&self, query: &str) -> Vec<(String, String, String)> {
@@ -239,7 +289,80 @@ impl SmartCompleter {
 
     /// Get all command names for syntax highlighting
     pub fn get_command_names(&self) -> Vec<String> {
-        self.commands.keys().cloned().collect()
+        self.set.read().unwrap().commands.keys().cloned().collect()
+    }
+
+    /// Per-definition timeouts (`default_timeout`), keyed by command path
+    pub fn command_timeouts(&self) -> HashMap<String, Duration> {
+        fn collect(spec: &CommandSpec, path: &str, out: &mut HashMap<String, Duration>) {
+            if let Some(secs) = spec.default_timeout {
+                out.insert(path.to_string(), Duration::from_secs(secs));
+            }
+            for sub in &spec.subcommands {
+                collect(sub, &format!("{} {}", path, sub.name), out);
+            }
+        }
+
+        let mut timeouts = HashMap::new();
+        for (name, spec) in &self.set.read().unwrap().commands {
+            collect(spec, name, &mut timeouts);
+        }
+        timeouts
+    }
+
+    /// Flag constraints broken on one command line
+    ///
+    /// `words` starts with the command name; subcommands are followed as far
+    /// as they are known, and the indices of the violations point into
+    /// `words`.
+    pub fn flag_violations(&self, words: &[&str]) -> Vec<Violation> {
+        self.check(words, |spec, rest| {
+            flags::violations(&flags::parse(spec, rest))
+        })
+    }
+
+    /// Everything about a command's flags and arguments that doesn't fit its
+    /// definition, like [`Self::flag_violations`]; a missing argument is
+    /// reported on `words.len()`
+    pub fn argument_violations(&self, words: &[&str]) -> Vec<Violation> {
+        self.check(words, CommandValidator::validate_command)
+    }
+
+    /// Run `check` on the spec of the command path at the start of `words`
+    /// and the words after it; unknown commands have nothing to check
+    fn check(
+        &self,
+        words: &[&str],
+        check: impl Fn(&CommandSpec, &[&str]) -> Vec<Violation>,
+    ) -> Vec<Violation> {
+        // Check what a wrapper runs; a wrapper alone is checked itself
+        let start = match self.wrapped_start(words, words.len()) {
+            start if start == words.len() => 0,
+            start => start,
+        };
+        let words = &words[start..];
+        let set = self.set.read().unwrap();
+        let Some(mut spec) = words.first().and_then(|name| set.commands.get(*name)) else {
+            return Vec::new();
+        };
+        let mut depth = 1;
+        for word in &words[1..] {
+            match spec.subcommands.iter().find(|s| s.name == *word) {
+                Some(sub) => {
+                    spec = sub;
+                    depth += 1;
+                }
+                None => break,
+            }
+        }
+
+        check(spec, &words[depth..])
+            .into_iter()
+            .map(|v| Violation {
+                word: v.word + start + depth,
+                ..v
+            })
+            .collect()
     }
 
     /// Get completions from dynamic providers
@@ -267,6 +390,75 @@ impl SmartCompleter {
             .collect()
     }
 
+    /// Completions for a positional argument or flag value the definition
+    /// declares: from the named provider, or else from its type
+    fn value_completions(
+        &self,
+        provider: Option<&str>,
+        value_type: &ArgumentType,
+        cmd: &str,
+        args: &[&str],
+        partial: &str,
+    ) -> Vec<Suggestion> {
+        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let context = ProviderContext::new(
+            cwd,
+            cmd,
+            args.iter().map(|s| s.to_string()).collect(),
+            partial,
+        );
+
+        let provider = provider.or(match value_type {
+            ArgumentType::Provider { name } => Some(name.as_str()),
+            _ => None,
+        });
+        let found = if let Some(name) = provider {
+            providers::registry()
+                .complete_named(name, &context)
+                .unwrap_or_default()
+        } else {
+            match value_type {
+                ArgumentType::Choice { values } => values
+                    .iter()
+                    .filter(|v| v.starts_with(partial))
+                    .map(ProviderSuggestion::new)
+                    .collect(),
+                ArgumentType::Boolean => ["true", "false"]
+                    .into_iter()
+                    .filter(|v| v.starts_with(partial))
+                    .map(ProviderSuggestion::new)
+                    .collect(),
+                ArgumentType::Path { filter } => PathProvider::new().complete_filtered(
+                    partial,
+                    &context,
+                    &PathFilter::from(filter),
+                ),
+                _ => Vec::new(),
+            }
+        };
+
+        found
+            .into_iter()
+            .map(|s| self.provider_suggestion_to_reedline(s, partial.len()))
+            .collect()
+    }
+
+    /// Menu entry that inserts nothing and only says what goes at `pos`,
+    /// e.g. `<remote>`
+    fn placeholder(label: &str, description: &str, pos: usize) -> Suggestion {
+        Suggestion {
+            value: String::new(),
+            description: Some(format!("{} {}", label, description).trim_end().to_string()),
+            extra: None,
+            span: Span {
+                start: pos,
+                end: pos,
+            },
+            append_whitespace: false,
+            style: None,
+        }
+    }
+
     /// Convert provider suggestion to reedline suggestion
     fn provider_suggestion_to_reedline(
         &self,
@@ -294,11 +486,44 @@ impl SmartCompleter {
     }
 }
 
+/// Description with a note to use something else, for deprecated items
+fn with_deprecation(description: &str, deprecated: Option<&str>) -> String {
+    match deprecated {
+        Some("") => format!("{} (deprecated)", description),
+        Some(instead) => format!("{} (deprecated, use {})", description, instead),
+        None => description.to_string(),
+    }
+}
+
+/// `NAME=value`, setting a variable for the command after it
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        name.chars().next().is_some_and(|c| !c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// Write a suggestion's value the way the word it replaces is quoted, so
+/// spaces and shell characters in it survive
+///
+/// A directory leaves its quote open, without a space, to be completed
+/// further.
+fn requote(line: &str, mut suggestion: Suggestion) -> Suggestion {
+    if !suggestion.value.is_empty() {
+        let quote = tokenizer::opening_quote(&line[suggestion.span.start..suggestion.span.end]);
+        let close = suggestion.append_whitespace && !suggestion.value.ends_with('/');
+        suggestion.value = tokenizer::quote(&suggestion.value, quote, close);
+        if quote.is_some() && !close {
+            suggestion.append_whitespace = false;
+        }
+    }
+    suggestion
+}
+
 impl Completer for SmartCompleter {
     fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        self.sync_project();
         let lang = self.get_lang();
-        let input = &line[0..pos];
-        let parts: Vec<&str> = input.trim_start().split_whitespace().collect();
 
         if line.starts_with('/') {
             let query = &line[1..pos]; // skip the slash
@@ -358,11 +583,146 @@ impl Completer for SmartCompleter {
             return suggestions;
         }
 
-        if parts.is_empty() || (parts.len() == 1 && !line.ends_with(' ')) {
-            let query = parts.first().unwrap_or(&"");
+        self.complete_line(&line[..pos])
+    }
+}
+
+impl SmartCompleter {
+    /// Completions for the word that ends `line` (or a new word after it),
+    /// quoted like that word
+    fn complete_line(&self, line: &str) -> Vec<Suggestion> {
+        let tokens = tokenizer::tokenize(line);
+        // Inside an unfinished `$(`, complete the command being substituted
+        if let Some(start) = tokens.last().and_then(|t| t.open_substitution) {
+            return self
+                .complete_line(&line[start..])
+                .into_iter()
+                .map(|mut s| {
+                    s.span.start += start;
+                    s.span.end += start;
+                    s
+                })
+                .collect();
+        }
+
+        let command = tokenizer::last_command(&tokens);
+        let pos = line.len();
+        let current = tokens.last().filter(|t| t.span.end == pos);
+        let typing = current.is_some_and(|t| t.kind == TokenKind::Word);
+        let words: Vec<&str> = command.words.iter().map(|t| t.text.as_str()).collect();
+
+        // `> out.txt` always takes a file, whatever the command
+        if command.redirect {
+            let (query, start) = current
+                .filter(|_| typing)
+                .map_or(("", pos), |t| (t.text.as_str(), t.span.start));
+            let path = ArgumentType::Path {
+                filter: PathFilterConfig::default(),
+            };
+            let cmd = words.first().copied().unwrap_or_default();
+            return self
+                .value_completions(None, &path, cmd, words.get(1..).unwrap_or_default(), query)
+                .into_iter()
+                .map(|mut s| {
+                    s.span = Span { start, end: pos };
+                    requote(line, s)
+                })
+                .collect();
+        }
+
+        let start = self.wrapped_start(&words, words.len() - usize::from(typing));
+        let mut suggestions: Vec<Suggestion> = self
+            .complete_words(line, &command.words[start..])
+            .into_iter()
+            .map(|s| requote(line, s))
+            .collect();
+
+        // Right after a pipe, offer what usually reads the previous output
+        if command.after == Some("|") && words.len() - start <= usize::from(typing) {
+            let previous = tokenizer::last_command(&tokens[..command.start - 1]);
+            let previous: Vec<&str> = previous.words.iter().map(|t| t.text.as_str()).collect();
+            let name = previous
+                .get(self.wrapped_start(&previous, previous.len()))
+                .copied()
+                .unwrap_or_default();
+            let (query, start_idx) = current
+                .filter(|_| typing)
+                .map_or(("", pos), |t| (t.text.as_str(), t.span.start));
+            let piped: Vec<Suggestion> = PipelineSuggestions::for_pipe_after(name)
+                .into_iter()
+                .filter(|(value, _)| value.starts_with(query))
+                .map(|(value, description)| Suggestion {
+                    value: value.to_string(),
+                    description: Some(format!("[pipe] {}", description)),
+                    extra: None,
+                    span: Span {
+                        start: start_idx,
+                        end: pos,
+                    },
+                    append_whitespace: true,
+                    style: None,
+                })
+                .collect();
+            suggestions.retain(|s| !piped.iter().any(|p| p.value == s.value));
+            suggestions.splice(0..0, piped);
+        }
+        suggestions
+    }
+
+    /// Index of the command `words` run, past wrappers like `sudo -u root`
+    /// or `env A=1` and leading `NAME=value` words
+    ///
+    /// Only the first `complete` words are finished. When the word after
+    /// them is where the wrapped command goes, that is returned.
+    fn wrapped_start(&self, words: &[&str], complete: usize) -> usize {
+        let set = self.set.read().unwrap();
+        let mut start = words[..complete]
+            .iter()
+            .take_while(|w| is_assignment(w))
+            .count();
+        while start < complete {
+            let Some(spec) = set.commands.get(words[start]).filter(|s| s.wraps) else {
+                break;
+            };
+            let rest = &words[start + 1..complete];
+            let scan = flags::scan(spec, rest);
+            let skip = spec.wraps_after.unwrap_or(0);
+            let commands: Vec<usize> = scan
+                .positionals
+                .into_iter()
+                .filter(|&i| !is_assignment(rest[i]))
+                .collect();
+            match commands.get(skip) {
+                Some(&i) => start += 1 + i,
+                None => {
+                    let flag = words.get(complete).is_some_and(|w| w.starts_with('-'));
+                    if commands.len() == skip && scan.pending.is_none() && !flag {
+                        return complete;
+                    }
+                    break;
+                }
+            }
+        }
+        start
+    }
+
+    /// Completions for `tokens`, the words of a command that ends `line`,
+    /// the line up to the cursor
+    fn complete_words(&self, line: &str, tokens: &[&Token]) -> Vec<Suggestion> {
+        let lang = self.get_lang();
+        let pos = line.len();
+        let parts: Vec<&str> = tokens.iter().map(|t| t.text.as_str()).collect();
+        // The word under the cursor, or none when a new one starts
+        let current = tokens.last().filter(|t| t.span.end == pos);
+        let is_new_arg = current.is_none();
+        let query = current.map_or("", |t| t.text.as_str());
+        let start_idx = current.map_or(pos, |t| t.span.start);
+
+        if parts.is_empty() || (parts.len() == 1 && !is_new_arg) {
             let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
 
-            let mut suggestions: Vec<(i64, Suggestion)> = self
+            let set = self.set.read().unwrap();
+            let mut suggestions: Vec<(i64, Suggestion)> = set
                 .commands
                 .values()
                 .filter_map(|cmd| self.fuzzy_match(query, &cmd.name).map(|score| (score, cmd)))
@@ -376,7 +736,7 @@ impl Completer for SmartCompleter {
                             description: Some(cmd.description.get(&lang).to_string()),
                             extra: None,
                             span: Span {
-                                start: pos - query.len(),
+                                start: start_idx,
                                 end: pos,
                             },
                             append_whitespace: true,
@@ -387,14 +747,13 @@ impl Completer for SmartCompleter {
                 .collect();
 
             // Sort by fuzzy score + context boost (higher is better)
-            suggestions.sort_by(|a, b| b.0.cmp(&a.0));
+            suggestions.sort_by_key(|s| std::cmp::Reverse(s.0));
             return suggestions.into_iter().map(|(_, s)| s).collect();
         }
 
         if let Some(cmd_name) = parts.first() {
             // Special handling for 'example' command - complete with command names that have examples
             if *cmd_name == "example" || *cmd_name == "examples" || *cmd_name == "ex" {
-                let is_new_arg = line.ends_with(' ');
                 let query: String = if is_new_arg {
                     String::new()
                 } else if parts.len() > 1 {
@@ -406,13 +765,8 @@ impl Completer for SmartCompleter {
                 let start_idx = if is_new_arg {
                     pos
                 } else {
-                    // Find start of the argument portion after 'example '
-                    let prefix_len = cmd_name.len() + 1; // "example "
-                    if input.len() > prefix_len {
-                        prefix_len
-                    } else {
-                        pos
-                    }
+                    // The argument portion after 'example '
+                    tokens.get(1).map_or(pos, |t| t.span.start)
                 };
 
                 // Get commands with examples and filter by query
@@ -461,9 +815,10 @@ impl Completer for SmartCompleter {
                 return suggestions;
             }
 
-            if let Some(root_spec) = self.commands.get(*cmd_name) {
+            // A copy, so the set isn't locked while a version command runs
+            let root_spec = self.set.read().unwrap().commands.get(*cmd_name).cloned();
+            if let Some(root_spec) = &root_spec {
                 // Determine which tokens are "completed" and can be used for descent
-                let is_new_arg = line.ends_with(' ');
                 let num_parts_to_descend = if is_new_arg {
                     parts.len()
                 } else {
@@ -473,9 +828,8 @@ impl Completer for SmartCompleter {
                 // Descend the tree
                 let mut current_spec = root_spec;
                 let mut subcommand_depth = 0;
-                for i in 1..num_parts_to_descend {
-                    let sub_name = parts[i];
-                    if let Some(sub) = current_spec.subcommands.iter().find(|s| s.name == sub_name)
+                for sub_name in parts.iter().take(num_parts_to_descend).skip(1) {
+                    if let Some(sub) = current_spec.subcommands.iter().find(|s| s.name == *sub_name)
                     {
                         current_spec = sub;
                         subcommand_depth += 1;
@@ -485,25 +839,67 @@ impl Completer for SmartCompleter {
                     }
                 }
 
-                // Setup Query
-                let last_part = parts.last().unwrap_or(&"");
-                let query = if is_new_arg { "" } else { *last_part };
-                let start_idx = if is_new_arg { pos } else { pos - query.len() };
+                // The positional argument being typed, when the definition
+                // declares one for this position
+                let typed = &parts[1 + subcommand_depth..num_parts_to_descend];
+                let scan = flags::scan(current_spec, typed);
+
+                // A flag's value: nothing else fits there, so only its
+                // `value_type` is completed
+                if let Some((flag, offset)) = flags::value_at(current_spec, scan.pending, query) {
+                    let value = &query[offset..];
+                    // After a bare `--flag=` the value starts at the same
+                    // place in the line; otherwise the whole word is replaced
+                    let (offset, prefix) = if line[start_idx..].starts_with(&query[..offset]) {
+                        (offset, "")
+                    } else {
+                        (0, &query[..offset])
+                    };
+                    let values = match &flag.value_type {
+                        Some(value_type) => {
+                            self.value_completions(None, value_type, cmd_name, &parts[1..], value)
+                        }
+                        None => Vec::new(),
+                    };
+                    if values.is_empty() && value.is_empty() {
+                        let name = flags::label(flag);
+                        return vec![Self::placeholder(
+                            &format!("<{}>", name.trim_start_matches('-')),
+                            flag.description.get(&lang),
+                            pos,
+                        )];
+                    }
+                    return values
+                        .into_iter()
+                        .map(|mut s| {
+                            s.value.insert_str(0, prefix);
+                            s.span = Span {
+                                start: start_idx + offset,
+                                end: pos,
+                            };
+                            s
+                        })
+                        .collect();
+                }
 
-                // Calculate current argument position (after subcommands)
-                // This will be used in future phases for argument type validation
-                let _arg_position = if is_new_arg {
-                    parts.len() - 1 - subcommand_depth
+                let argument = if query.starts_with('-') || scan.pending.is_some() {
+                    None
                 } else {
-                    parts.len() - 2 - subcommand_depth
+                    current_spec.argument_at(scan.positionals.len())
                 };
 
-                // 1. Try dynamic provider completions first
-                let provider_suggestions = self.get_provider_completions(
-                    cmd_name,
-                    &parts[1..],
-                    query,
-                );
+                // 1. Complete the declared argument, or else ask the dynamic
+                // providers whether one of them knows this position
+                let provider_suggestions = match argument {
+                    Some(argument) => self.value_completions(
+                        argument.provider_name(),
+                        &argument.arg_type,
+                        cmd_name,
+                        &parts[1..],
+                        query,
+                    ),
+                    None => self.get_provider_completions(cmd_name, &parts[1..], query),
+                };
 
                 if !provider_suggestions.is_empty() {
                     return provider_suggestions
@@ -518,15 +914,36 @@ impl Completer for SmartCompleter {
                         .collect();
                 }
 
+                // Subcommands and flags the installed version doesn't have
+                // are hidden; the version is only looked up when it matters
+                let versioned = current_spec
+                    .subcommands
+                    .iter()
+                    .map(|s| (&s.since, &s.until))
+                    .chain(current_spec.flags.iter().map(|f| (&f.since, &f.until)))
+                    .any(|(since, until)| since.is_some() || until.is_some());
+                let version = if versioned {
+                    versions().detect(root_spec)
+                } else {
+                    None
+                };
+                let supported = |since: &Option<String>, until: &Option<String>| {
+                    versions::supported(version.as_ref(), since.as_deref(), until.as_deref())
+                };
+
                 // 2. Subcommand completion
                 // Suggest subcommands of the CURRENT spec (use prefix match for speed in tab completion)
                 let sub_suggestions: Vec<Suggestion> = current_spec
                     .subcommands
                     .iter()
+                    .filter(|sub| supported(&sub.since, &sub.until))
                     .filter(|sub| self.prefix_match(query, &sub.name))
                     .map(|sub| Suggestion {
                         value: sub.name.clone(),
-                        description: Some(sub.description.get(&lang).to_string()),
+                        description: Some(with_deprecation(
+                            sub.description.get(&lang),
+                            sub.deprecated.as_deref(),
+                        )),
                         extra: None,
                         span: Span {
                             start: start_idx,
@@ -538,7 +955,9 @@ impl Completer for SmartCompleter {
                     .collect();
 
                 // 3. Flag completion
-                // Suggest flags of the CURRENT spec
+                // Suggest flags of the CURRENT spec that still fit next to
+                // the ones already typed
+                let uses = scan.flags;
                 let mut flag_suggestions = Vec::new();
                 if query.starts_with('-') || is_new_arg {
                     // Copied/Refined flag logic
@@ -564,9 +983,24 @@ impl Completer for SmartCompleter {
                     };
 
                     if !stop_flagging {
-                        for flag in &current_spec.flags {
+                        for flag in current_spec
+                            .flags
+                            .iter()
+                            .filter(|f| supported(&f.since, &f.until))
+                            .filter(|f| flags::is_available(f, &uses))
+                        {
+                            let description = with_deprecation(
+                                flag.description.get(&lang),
+                                flag.deprecated.as_deref(),
+                            );
                             let short = flag.short.map(|c| format!("-{}", c));
-                            let long = flag.long.as_ref().map(|s| format!("--{}", s));
+                            let long = flag.long.as_ref().map(|s| {
+                                if flag.equals {
+                                    format!("--{}=", s)
+                                } else {
+                                    format!("--{}", s)
+                                }
+                            });
                             let match_short = short
                                 .as_ref()
                                 .map(|s| s.starts_with(query))
@@ -577,7 +1011,7 @@ impl Completer for SmartCompleter {
                             if match_short && short.is_some() {
                                 flag_suggestions.push(Suggestion {
                                     value: short.clone().unwrap(),
-                                    description: Some(flag.description.get(&lang).to_string()),
+                                    description: Some(description.clone()),
                                     extra: None,
                                     span: Span {
                                         start: start_idx,
@@ -590,7 +1024,26 @@ impl Completer for SmartCompleter {
                             if match_long && long.is_some() {
                                 flag_suggestions.push(Suggestion {
                                     value: long.clone().unwrap(),
-                                    description: Some(flag.description.get(&lang).to_string()),
+                                    description: Some(description.clone()),
+                                    extra: None,
+                                    span: Span {
+                                        start: start_idx,
+                                        end: pos,
+                                    },
+                                    append_whitespace: !flag.equals,
+                                    style: None,
+                                });
+                            }
+                            if let Some(negated) = flag
+                                .long
+                                .as_ref()
+                                .filter(|_| flag.negatable)
+                                .map(|s| format!("--no-{}", s))
+                                .filter(|s| s.starts_with(query) && query.starts_with("--no"))
+                            {
+                                flag_suggestions.push(Suggestion {
+                                    value: negated,
+                                    description: Some(description.clone()),
                                     extra: None,
                                     span: Span {
                                         start: start_idx,
@@ -604,13 +1057,12 @@ impl Completer for SmartCompleter {
                             // Combined short flags
                             if is_short_chain {
                                 if let Some(c) = flag.short {
-                                    if !used_chars.contains(&c) {
+                                    if flag.repeatable || !used_chars.contains(&c) {
                                         flag_suggestions.push(Suggestion {
                                             value: format!("{}{}", query, c),
                                             description: Some(format!(
                                                 "{} (+{})",
-                                                flag.description.get(&lang),
-                                                c
+                                                description, c
                                             )),
                                             extra: None,
                                             span: Span {
@@ -645,8 +1097,22 @@ impl Completer for SmartCompleter {
                 //     }
                 // }
 
-                // Combine suggestions (subcommands + flags only, no examples)
-                let mut all_suggestions = sub_suggestions;
+                // Combine suggestions (subcommands + flags only, no examples),
+                // after a placeholder saying what the argument here is
+                let mut all_suggestions = Vec::new();
+                if let Some(argument) = argument.filter(|_| query.is_empty()) {
+                    let description = argument
+                        .description
+                        .as_ref()
+                        .map(|d| d.get(&lang))
+                        .unwrap_or("");
+                    all_suggestions.push(Self::placeholder(
+                        &argument.placeholder(),
+                        description,
+                        pos,
+                    ));
+                }
+                all_suggestions.extend(sub_suggestions);
                 all_suggestions.extend(flag_suggestions);
 
                 if !all_suggestions.is_empty() {
@@ -662,13 +1128,6 @@ impl Completer for SmartCompleter {
             }
 
             // Try provider completions for unknown commands too
-            let is_new_arg = line.ends_with(' ');
-            let query = if is_new_arg {
-                ""
-            } else {
-                parts.last().unwrap_or(&"")
-            };
-
             let provider_suggestions = self.get_provider_completions(
                 cmd_name,
                 &parts[1..],
@@ -676,7 +1135,6 @@ impl Completer for SmartCompleter {
             );
 
             if !provider_suggestions.is_empty() {
-                let start_idx = if is_new_arg { pos } else { pos - query.len() };
                 return provider_suggestions
                     .into_iter()
                     .map(|mut s| {
@@ -691,11 +1149,6 @@ impl Completer for SmartCompleter {
         }
 
         // Fallback: Path completion
-        let last_part = parts.last().unwrap_or(&"");
-        let is_new_arg = line.ends_with(' ');
-        let query = if is_new_arg { "" } else { *last_part };
-        let start_idx = if is_new_arg { pos } else { pos - query.len() };
-
         // Use enhanced path provider
         let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
         let cmd = parts.first().unwrap_or(&"");
@@ -754,3 +1207,126 @@ impl Completer for SmartCompleter {
         vec![]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completer(yamls: &[&str]) -> SmartCompleter {
+        let specs = yamls
+            .iter()
+            .map(|yaml| serde_yaml::from_str(yaml).unwrap())
+            .collect();
+        SmartCompleter::new(Catalog::new(specs), Arc::new(RwLock::new("en".to_string())))
+    }
+
+    fn complete(completer: &mut SmartCompleter, line: &str) -> Vec<(String, usize)> {
+        completer
+            .complete(line, line.len())
+            .into_iter()
+            .map(|s| (s.value, s.span.start))
+            .collect()
+    }
+
+    const COMMIT: &str = r#"
+name: commit
+description: Record changes
+flags:
+  - long: message
+    short: m
+    description: Message
+    takes_value: true
+  - long: amend
+    description: Amend
+"#;
+
+    #[test]
+    fn test_complete_quoted_words() {
+        let mut completer = completer(&[COMMIT]);
+        assert_eq!(
+            complete(&mut completer, r#"commit -m "fix bug" --am"#),
+            [("--amend".to_string(), 20)]
+        );
+        assert_eq!(
+            complete(&mut completer, "echo $(commit --am"),
+            [("--amend".to_string(), 14)]
+        );
+
+        // Relative to the working directory, where paths complete without
+        // fuzzy matching
+        let temp = tempfile::Builder::new()
+            .prefix("sc-test")
+            .tempdir_in(".")
+            .unwrap();
+        std::fs::write(temp.path().join("my file.txt"), "").unwrap();
+        let dir = temp.path().file_name().unwrap().to_string_lossy();
+        let file = |quoted: &str| (quoted.replace("DIR", &dir), 4);
+        assert_eq!(
+            complete(&mut completer, &format!("cat {}/my\\ f", dir)),
+            [file(r"DIR/my\ file.txt")]
+        );
+        assert_eq!(
+            complete(&mut completer, &format!("cat '{}/my f", dir)),
+            [file("'DIR/my file.txt'")]
+        );
+        assert_eq!(
+            complete(&mut completer, &format!("cat \"{}", dir)),
+            [file("\"DIR/")]
+        );
+    }
+
+    #[test]
+    fn test_complete_pipelines_and_wrappers() {
+        let mut completer = completer(&[
+            COMMIT,
+            r#"
+name: sudo
+description: Run as another user
+wraps: true
+flags:
+  - long: user
+    short: u
+    description: User
+    takes_value: true
+"#,
+            "{name: env, description: Set variables, wraps: true}",
+            "{name: timeout, description: Limit run time, wraps: true, wraps_after: 1}",
+        ]);
+        let amend = |start| vec![("--amend".to_string(), start)];
+        assert_eq!(
+            complete(&mut completer, "sudo -u root commit --am"),
+            amend(20)
+        );
+        assert_eq!(complete(&mut completer, "env A=1 commit --am"), amend(15));
+        assert_eq!(complete(&mut completer, "timeout 5 commit --am"), amend(17));
+        assert_eq!(
+            complete(&mut completer, "cd src && sudo -u root comm"),
+            [("commit".to_string(), 23)]
+        );
+        assert_eq!(
+            complete(&mut completer, "sudo --us"),
+            [("--user".to_string(), 5)]
+        );
+
+        // Pipe suggestions come first, then the commands themselves
+        let values: Vec<String> = complete(&mut completer, "commit | ")
+            .into_iter()
+            .map(|(value, _)| value)
+            .collect();
+        assert_eq!(values[..5], ["grep", "wc -l", "head", "tail", "sort"]);
+        assert!(values.contains(&"sudo".to_string()));
+        assert_eq!(
+            complete(&mut completer, "commit | he"),
+            [("head".to_string(), 9)]
+        );
+
+        assert_eq!(
+            complete(&mut completer, "commit > src/complet"),
+            [("src/completer.rs".to_string(), 9)]
+        );
+
+        let words = ["sudo", "-u", "root", "commit", "-m"];
+        let violations = completer.argument_violations(&words);
+        assert_eq!(violations.iter().map(|v| v.word).collect::<Vec<_>>(), [4]);
+    }
+}
@@ -28,6 +28,11 @@ pub struct AppConfig {
     #[serde(default = "default_danger_protection")]
     pub danger_protection: bool,
 
+    /// Check flags and arguments against the command's definition before
+    /// running it, and ask before running a line that doesn't fit
+    #[serde(default = "default_true")]
+    pub check_arguments: bool,
+
     /// Log level (trace, debug, info, warn, error)
     #[serde(default = "default_log_level")]
     pub log_level: String,
@@ -47,6 +52,40 @@ pub struct AppConfig {
     /// Upgrade configuration
     #[serde(default)]
     pub upgrade: UpgradeConfig,
+
+    /// Command execution settings
+    #[serde(default)]
+    pub exec: ExecConfig,
+}
+
+/// Command execution configuration
+#[derive(Debug, Deserialize, Clone)]
+pub struct ExecConfig {
+    /// Kill foreground commands after this many seconds (0 = no timeout)
+    #[serde(default)]
+    pub timeout_secs: u64,
+
+    /// Backend: native, sh, bash, zsh, fish, or an argv template (`nu -c {}`)
+    #[serde(default = "default_exec_shell")]
+    pub shell: String,
+
+    /// Keep one backend shell alive between lines
+    #[serde(default)]
+    pub persistent: bool,
+}
+
+impl Default for ExecConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 0,
+            shell: default_exec_shell(),
+            persistent: false,
+        }
+    }
+}
+
+fn default_exec_shell() -> String {
+    "native".to_string()
 }
 
 /// Upgrade configuration
@@ -99,6 +138,10 @@ pub struct PromptConfig {
     #[serde(default = "default_true")]
     pub show_cwd: bool,
 
+    /// Show a failure marker and the last exit status
+    #[serde(default = "default_true")]
+    pub show_exit_status: bool,
+
     /// Prompt indicator character
     #[serde(default = "default_prompt_char")]
     pub indicator: String,
@@ -109,6 +152,7 @@ impl Default for PromptConfig {
         Self {
             show_git_branch: true,
             show_cwd: true,
+            show_exit_status: true,
             indicator: default_prompt_char(),
         }
     }
@@ -171,10 +215,13 @@ impl AppConfig {
             )?
             .set_default("history_size", default_history_size() as i64)?
             .set_default("danger_protection", default_danger_protection())?
+            .set_default("check_arguments", true)?
             .set_default("log_level", default_log_level())?
             .set_default("prompt.show_git_branch", true)?
             .set_default("prompt.show_cwd", true)?
-            .set_default("prompt.indicator", default_prompt_char())?;
+            .set_default("prompt.show_exit_status", true)?
+            .set_default("prompt.indicator", default_prompt_char())?
+            .set_default("exec.shell", default_exec_shell())?;
 
         // Add config file if it exists
         let builder = if config_file.exists() {
@@ -200,11 +247,13 @@ impl AppConfig {
             history_path: default_history_path(),
             history_size: default_history_size(),
             danger_protection: default_danger_protection(),
+            check_arguments: true,
             log_level: default_log_level(),
             definitions_dir: None,
             theme: None,
             prompt: PromptConfig::default(),
             upgrade: UpgradeConfig::default(),
+            exec: ExecConfig::default(),
         }
     }
 
@@ -216,6 +265,30 @@ impl AppConfig {
     }
 }
 
+/// Settings given on the command line
+///
+/// Kept so they can be re-applied when config.toml is reloaded.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    pub lang: Option<String>,
+    pub definitions_dir: Option<PathBuf>,
+    pub no_danger_protection: bool,
+}
+
+impl ConfigOverrides {
+    pub fn apply(&self, config: &mut AppConfig) {
+        if let Some(lang) = &self.lang {
+            config.lang = lang.clone();
+        }
+        if let Some(definitions_dir) = &self.definitions_dir {
+            config.definitions_dir = Some(definitions_dir.clone());
+        }
+        if self.no_danger_protection {
+            config.danger_protection = false;
+        }
+    }
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self::default_config()
@@ -239,6 +312,10 @@ history_size = 1000
 # Enable dangerous command protection (prompts before rm -rf, etc.)
 danger_protection = true
 
+# Check flags and arguments of known commands before running them
+# (unknown flags, missing arguments, bad values); asks "run anyway?"
+check_arguments = true
+
 # Log level: trace, debug, info, warn, error
 log_level = "info"
 
@@ -255,6 +332,9 @@ show_git_branch = true
 # Show current working directory
 show_cwd = true
 
+# Show a failure marker and exit code after a failed command
+show_exit_status = true
+
 # Prompt indicator character
 indicator = "❯"
 
@@ -270,6 +350,21 @@ repository = "skingford/smart-command"
 
 # Include pre-release versions
 include_prerelease = false
+
+[exec]
+# Kill foreground commands after this many seconds (0 = no timeout).
+# Definitions can override it with `default_timeout`, and a single
+# command with `timeout 30 <cmd>`.
+timeout_secs = 0
+
+# Where command lines run: "native" (built-in executor, sh for syntax it
+# does not support), "sh", "bash", "zsh", "fish", or an argv template
+# such as "nu -c {}". sc builtins (cd, export, alias, ...) always run in sc.
+shell = "native"
+
+# Keep one shell alive between lines so its functions and variables
+# survive (sh/bash/zsh/fish only)
+persistent = false
 "#
     .to_string()
 }
@@ -284,6 +379,7 @@ mod tests {
         assert!(!config.lang.is_empty());
         assert!(config.history_size > 0);
         assert!(config.danger_protection);
+        assert!(config.check_arguments);
     }
 
     #[test]
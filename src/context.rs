@@ -8,7 +8,7 @@
 use lru::LruCache;
 use std::collections::HashMap;
 use std::num::NonZeroUsize;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::RwLock;
 use std::time::{Duration, Instant};
@@ -27,7 +27,7 @@ pub enum ProjectType {
 
 impl ProjectType {
     /// Detect project type from current directory
-    pub fn detect(cwd: &PathBuf) -> Self {
+    pub fn detect(cwd: &Path) -> Self {
         // Check for Rust
         if cwd.join("Cargo.toml").exists() {
             return ProjectType::Rust;
@@ -272,6 +272,8 @@ impl Default for CommandUsage {
 pub struct ContextTracker {
     /// Detected project type
     project_type: RwLock<Option<(PathBuf, ProjectType)>>,
+    /// Project definitions directory for the last cwd asked about
+    project_definitions: RwLock<Option<(PathBuf, Option<PathBuf>)>>,
     /// Git state (cached)
     git_state: RwLock<Option<(Instant, GitState)>>,
     /// Command usage tracking
@@ -290,6 +292,7 @@ impl ContextTracker {
     pub fn new() -> Self {
         Self {
             project_type: RwLock::new(None),
+            project_definitions: RwLock::new(None),
             git_state: RwLock::new(None),
             command_usage: RwLock::new(LruCache::new(NonZeroUsize::new(500).unwrap())),
             dir_patterns: RwLock::new(HashMap::new()),
@@ -314,6 +317,21 @@ impl ContextTracker {
         ptype
     }
 
+    /// Get the project definitions directory for the current directory
+    pub fn get_project_definitions(&self, cwd: &PathBuf) -> Option<PathBuf> {
+        let cached = self.project_definitions.read().unwrap();
+        if let Some((ref cached_cwd, ref dir)) = *cached {
+            if cached_cwd == cwd {
+                return dir.clone();
+            }
+        }
+        drop(cached);
+
+        let dir = crate::loader::find_project_definitions(cwd);
+        *self.project_definitions.write().unwrap() = Some((cwd.clone(), dir.clone()));
+        dir
+    }
+
     /// Get git state (with caching)
     pub fn get_git_state(&self) -> Option<GitState> {
         let cached = self.git_state.read().unwrap();
@@ -349,9 +367,9 @@ impl ContextTracker {
     }
 
     /// Record directory pattern
-    pub fn record_dir_pattern(&self, cwd: &PathBuf, command: &str) {
+    pub fn record_dir_pattern(&self, cwd: &Path, command: &str) {
         let mut patterns = self.dir_patterns.write().unwrap();
-        let entry = patterns.entry(cwd.clone()).or_default();
+        let entry = patterns.entry(cwd.to_path_buf()).or_default();
 
         if !entry.contains(&command.to_string()) {
             entry.push(command.to_string());
@@ -9,10 +9,39 @@ pub fn other_specs() -> Vec<CommandSpec> {
                 description: "Include entries starting with .".into(),
                 takes_value: false,
                 value_type: None,
+                ..Default::default()
             })
             .field("is_path_completion", true),
         CommandSpec::new("cd", "Change the shell working directory")
             .field("is_path_completion", true),
+        CommandSpec::new("pushd", "Change directory and push it on the directory stack")
+            .field("is_path_completion", true),
+        CommandSpec::new("popd", "Return to the directory on top of the directory stack"),
+        CommandSpec::new("dirs", "Show the directory stack")
+            .flag(FlagSpec {
+                long: None,
+                short: Some('v'),
+                description: "List entries with their stack index".into(),
+                takes_value: false,
+                value_type: None,
+                ..Default::default()
+            })
+            .flag(FlagSpec {
+                long: None,
+                short: Some('p'),
+                description: "List one entry per line".into(),
+                takes_value: false,
+                value_type: None,
+                ..Default::default()
+            })
+            .flag(FlagSpec {
+                long: None,
+                short: Some('c'),
+                description: "Clear the directory stack".into(),
+                takes_value: false,
+                value_type: None,
+                ..Default::default()
+            }),
         CommandSpec::new("config", "System configuration").subcommand(
             CommandSpec::new("set-lang", "Set display language")
                 .subcommand(CommandSpec::new("en", "English"))
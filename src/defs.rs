@@ -0,0 +1,235 @@
+//! `sc defs` - tooling for command definitions
+//!
+//! Supports:
+//! - `sc defs which <command>` - show which layer and file supplied each piece
+//! - `sc defs lint [paths]` - report problems as `file:line` diagnostics
+//! - `sc defs schema` - print the JSON Schema of a definition file
+//! - `sc defs generate <cmd>` - draft a definition from `--help` or a man page
+//! - `sc defs import --from <format> <file>` - convert a Fig, carapace or fish spec
+//! - `sc defs rebuild-cache` - re-parse every definition into the startup cache
+
+use crate::catalog;
+use crate::cli::DefsAction;
+use crate::command_def::CommandSpec;
+use crate::config::AppConfig;
+use crate::dirstack::display_path;
+use crate::generate::{self, HelpSource};
+use crate::import;
+use crate::lint::{self, Severity};
+use crate::loader::{self, Definitions, Layer};
+use crate::output::Output;
+use crate::providers;
+use nu_ansi_term::{Color, Style};
+use std::path::PathBuf;
+
+/// Long pieces (usually examples) don't widen the whole table past this
+const MAX_PIECE_WIDTH: usize = 40;
+
+/// Run a `sc defs` subcommand
+pub fn run(action: DefsAction, config: &AppConfig) -> anyhow::Result<()> {
+    let definitions_dir = config
+        .definitions_dir
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("definitions"));
+
+    match action {
+        DefsAction::Which { command } => {
+            let defs = Definitions::load(&all_layers(&definitions_dir));
+            which(&defs, &command)
+        }
+        DefsAction::Lint { paths } => {
+            let paths = if paths.is_empty() {
+                all_layers(&definitions_dir)
+                    .into_iter()
+                    .map(|layer| layer.dir)
+                    .collect()
+            } else {
+                paths
+            };
+            lint(&paths)
+        }
+        DefsAction::Schema => {
+            println!("{}", schema());
+            Ok(())
+        }
+        DefsAction::Generate {
+            command,
+            man,
+            output,
+        } => {
+            let source = if man { HelpSource::Man } else { HelpSource::Auto };
+            let spec = generate::generate(&command, source)?;
+            let yaml = generate::to_yaml(&spec)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, yaml)?;
+                    Output::success(&format!(
+                        "Wrote {} ({} flags, {} subcommands)",
+                        display_path(&path),
+                        spec.flags.len(),
+                        spec.subcommands.len()
+                    ));
+                    Output::dim("Review it, add zh descriptions, then run `sc defs lint`.");
+                }
+                None => print!("{}", yaml),
+            }
+            Ok(())
+        }
+        DefsAction::RebuildCache => {
+            let Some(path) = catalog::cache_path() else {
+                anyhow::bail!("No cache directory on this system");
+            };
+            let layers = loader::definition_layers(&definitions_dir);
+            let catalog = catalog::rebuild(&layers, &path)?;
+            Output::success(&format!(
+                "Cached {} commands ({} searchable paths) in {}",
+                catalog.specs.len(),
+                catalog.index.entries().len(),
+                display_path(&path)
+            ));
+            Ok(())
+        }
+        DefsAction::Import { from, file, output } => {
+            let source = std::fs::read_to_string(&file)
+                .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", display_path(&file), e))?;
+            let import = import::import(from, &source)?;
+            let yaml = generate::to_yaml(&import.spec)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, yaml)?;
+                    Output::success(&format!(
+                        "Wrote {} ({} flags, {} subcommands)",
+                        display_path(&path),
+                        import.spec.flags.len(),
+                        import.spec.subcommands.len()
+                    ));
+                }
+                None => print!("{}", yaml),
+            }
+            // Notes go to stderr so stdout stays valid YAML
+            for note in &import.notes {
+                eprintln!("warning: {}", note);
+            }
+            if !import.notes.is_empty() {
+                eprintln!("{} parts could not be represented", import.notes.len());
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Global layers plus the project layer of the current directory
+fn all_layers(definitions_dir: &PathBuf) -> Vec<Layer> {
+    let mut layers = loader::definition_layers(definitions_dir);
+    if let Some(project) = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| loader::project_layer(&cwd))
+    {
+        layers.push(project);
+    }
+    layers
+}
+
+/// JSON Schema for a definition file, pretty-printed
+pub fn schema() -> String {
+    let schema = schemars::schema_for!(CommandSpec);
+    serde_json::to_string_pretty(&schema).unwrap_or_default()
+}
+
+/// Print diagnostics for every definition file and fail on errors
+fn lint(paths: &[PathBuf]) -> anyhow::Result<()> {
+    let registry = providers::registry();
+    let providers = registry.provider_names();
+    let files = lint::yaml_files(paths);
+    let diagnostics = lint::lint_paths(paths, &providers);
+
+    for d in &diagnostics {
+        let label = match d.severity {
+            Severity::Error => Color::Red.bold().paint(d.severity.label()),
+            Severity::Warning => Color::Yellow.paint(d.severity.label()),
+        };
+        println!("{}:{}: {}: {}", display_path(&d.file), d.line, label, d.message);
+    }
+
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    let warnings = diagnostics.len() - errors;
+    let summary = format!(
+        "{} file{} checked: {} error{}, {} warning{}",
+        files.len(),
+        if files.len() == 1 { "" } else { "s" },
+        errors,
+        if errors == 1 { "" } else { "s" },
+        warnings,
+        if warnings == 1 { "" } else { "s" }
+    );
+    if errors > 0 {
+        anyhow::bail!(summary);
+    }
+    Output::success(&summary);
+    Ok(())
+}
+
+/// Print the origin of every piece of a command
+fn which(defs: &Definitions, command: &[String]) -> anyhow::Result<()> {
+    let path: Vec<&str> = command
+        .iter()
+        .flat_map(|part| part.split_whitespace())
+        .collect();
+    let name = path.join(" ");
+    let Some(pieces) = defs.which(&path) else {
+        anyhow::bail!("No definition found for: {}", name);
+    };
+
+    let label_style = Style::new().fg(Color::Cyan);
+    let files = defs.files(path[0]);
+    Output::info(&format!(
+        "{} is defined by {} file{}:",
+        name,
+        files.len(),
+        if files.len() == 1 { "" } else { "s" }
+    ));
+    for origin in files {
+        println!(
+            "  {} {}",
+            label_style.paint(format!("{:<8}", origin.kind.label())),
+            display_path(&origin.file)
+        );
+    }
+    println!();
+
+    let width = pieces
+        .iter()
+        .map(|(piece, _)| piece.chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(MAX_PIECE_WIDTH);
+    for (piece, origin) in pieces {
+        let padding = " ".repeat(width.saturating_sub(piece.chars().count()));
+        println!(
+            "  {}{}  {} {}",
+            Output::command(&piece),
+            padding,
+            label_style.paint(format!("{:<8}", origin.kind.label())),
+            display_path(&origin.file)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_published_schema_is_current() {
+        let published = std::fs::read_to_string("docs/command-spec.schema.json").unwrap();
+        assert_eq!(
+            published.trim_end(),
+            schema(),
+            "run `sc defs schema > docs/command-spec.schema.json`"
+        );
+    }
+}
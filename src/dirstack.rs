@@ -0,0 +1,354 @@
+//! Directory Stack
+//!
+//! Recently visited directories, most recent first:
+//! - Every successful `cd` (and `@bookmark` jump) pushes the previous directory
+//! - `pushd`, `popd` and `dirs [-v|-p|-c]` manage the stack explicitly
+//! - `cd -N` jumps to entry N of `dirs -v` (`cd -` is `cd -1`)
+//! - Persisted to `dirstack.yaml` next to `bookmarks.yaml`
+//!
+//! Entry 0 is always the current directory and is not stored.
+
+#![allow(dead_code)]
+
+use crate::environment::session_env;
+use once_cell::sync::Lazy;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Maximum number of remembered directories
+pub const MAX_ENTRIES: usize = 30;
+
+/// Directory stack shared by the builtins and `PathProvider`
+#[derive(Debug)]
+pub struct DirStack {
+    entries: RwLock<Vec<PathBuf>>,
+    config_path: PathBuf,
+}
+
+impl DirStack {
+    /// Load the stack from `~/.config/smart-command/dirstack.yaml`
+    pub fn new() -> Self {
+        let config_path = dirs::config_dir()
+            .map(|p| p.join("smart-command").join("dirstack.yaml"))
+            .unwrap_or_else(|| PathBuf::from("~/.config/smart-command/dirstack.yaml"));
+        Self::with_path(config_path)
+    }
+
+    /// Load a stack from a specific file
+    pub fn with_path(config_path: PathBuf) -> Self {
+        let entries: Vec<PathBuf> = fs::read_to_string(&config_path)
+            .ok()
+            .and_then(|content| serde_yaml::from_str(&content).ok())
+            .unwrap_or_default();
+
+        Self {
+            entries: RwLock::new(entries),
+            config_path,
+        }
+    }
+
+    /// Save the stack
+    pub fn save(&self) -> std::io::Result<()> {
+        if let Some(parent) = self.config_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_yaml::to_string(&*self.entries.read().unwrap())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        fs::write(&self.config_path, content)
+    }
+
+    /// Stored entries (`dirs -v` entries 1, 2, ...)
+    pub fn entries(&self) -> Vec<PathBuf> {
+        self.entries.read().unwrap().clone()
+    }
+
+    /// Entry `n` of `dirs -v` (n >= 1)
+    pub fn get(&self, n: usize) -> Option<PathBuf> {
+        let index = n.checked_sub(1)?;
+        self.entries.read().unwrap().get(index).cloned()
+    }
+
+    /// Push a directory on top, dropping older duplicates of it
+    pub fn push(&self, dir: PathBuf) {
+        let mut entries = self.entries.write().unwrap();
+        entries.retain(|d| *d != dir);
+        entries.insert(0, dir);
+        entries.truncate(MAX_ENTRIES);
+        drop(entries);
+        let _ = self.save();
+    }
+
+    /// Remove entry `n` of `dirs -v` (n >= 1)
+    pub fn remove(&self, n: usize) -> Option<PathBuf> {
+        let index = n.checked_sub(1)?;
+        let mut entries = self.entries.write().unwrap();
+        if index >= entries.len() {
+            return None;
+        }
+        let dir = entries.remove(index);
+        drop(entries);
+        let _ = self.save();
+        Some(dir)
+    }
+
+    /// Forget every entry
+    pub fn clear(&self) {
+        self.entries.write().unwrap().clear();
+        let _ = self.save();
+    }
+
+    /// Replace the stored entries
+    fn set_entries(&self, entries: Vec<PathBuf>) {
+        *self.entries.write().unwrap() = entries;
+        let _ = self.save();
+    }
+
+    /// Change directory, pushing the previous one and updating `PWD`/`OLDPWD`
+    pub fn change_dir(&self, target: &Path) -> Result<(), String> {
+        let previous = std::env::current_dir().ok();
+        enter(target)?;
+
+        let current = std::env::current_dir().ok();
+        if let Some(current) = &current {
+            self.entries.write().unwrap().retain(|d| d != current);
+        }
+        match previous {
+            Some(previous) if Some(&previous) != current.as_ref() => self.push(previous),
+            _ => {
+                let _ = self.save();
+            }
+        }
+        Ok(())
+    }
+
+    /// Rotate the whole stack (current directory included) so entry `n` is on top
+    fn rotate(&self, n: usize) -> Result<(), String> {
+        let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+        let mut full = vec![cwd];
+        full.extend(self.entries());
+        if n >= full.len() {
+            return Err(format!("+{}: directory stack index out of range", n));
+        }
+
+        full.rotate_left(n);
+        enter(&full[0])?;
+        self.set_entries(full.split_off(1));
+        Ok(())
+    }
+
+    /// Format the stack as `dirs` would (current directory first)
+    pub fn format(&self, verbose: bool, per_line: bool) -> String {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let full = std::iter::once(cwd).chain(self.entries());
+
+        if verbose {
+            full.enumerate()
+                .map(|(i, dir)| format!("{:2}  {}", i, display_path(&dir)))
+                .collect::<Vec<_>>()
+                .join("\n")
+        } else {
+            let dirs: Vec<String> = full.map(|dir| display_path(&dir)).collect();
+            dirs.join(if per_line { "\n" } else { " " })
+        }
+    }
+}
+
+impl Default for DirStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Global directory stack
+static DIR_STACK: Lazy<DirStack> = Lazy::new(DirStack::new);
+
+/// Get the global directory stack
+pub fn dir_stack() -> &'static DirStack {
+    &DIR_STACK
+}
+
+/// Set the process directory and export `PWD`/`OLDPWD`
+fn enter(target: &Path) -> Result<(), String> {
+    let previous = std::env::current_dir().ok();
+    std::env::set_current_dir(target).map_err(|e| format!("{}: {}", target.display(), e))?;
+
+    if let Some(old) = previous {
+        session_env().export("OLDPWD", Some(old.display().to_string()));
+    }
+    if let Ok(new) = std::env::current_dir() {
+        session_env().export("PWD", Some(new.display().to_string()));
+    }
+    Ok(())
+}
+
+/// Show a path with the home directory abbreviated to `~`
+pub fn display_path(path: &Path) -> String {
+    dirs::home_dir()
+        .and_then(|home| {
+            path.strip_prefix(&home).ok().map(|rel| {
+                if rel.as_os_str().is_empty() {
+                    "~".to_string()
+                } else {
+                    format!("~/{}", rel.display())
+                }
+            })
+        })
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Parse a `cd -N` argument (`-` alone means `-1`)
+pub fn parse_back_ref(arg: &str) -> Option<usize> {
+    let digits = arg.strip_prefix('-')?;
+    if digits.is_empty() {
+        return Some(1);
+    }
+    if digits.chars().all(|c| c.is_ascii_digit()) {
+        digits.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Parse a `pushd +N` / `popd +N` argument
+fn parse_index(arg: &str) -> Option<usize> {
+    let digits = arg.strip_prefix('+')?;
+    digits
+        .chars()
+        .all(|c| c.is_ascii_digit())
+        .then(|| digits.parse().ok())
+        .flatten()
+}
+
+/// Handle directory stack commands: `pushd`, `popd`, `dirs`
+pub fn handle_dirstack_command(
+    stack: &DirStack,
+    cmd: &str,
+    args: &[&str],
+) -> Result<Option<String>, String> {
+    match cmd {
+        "pushd" => {
+            match args {
+                // Swap the top two directories
+                [] => {
+                    let top = stack
+                        .get(1)
+                        .ok_or_else(|| "pushd: no other directory".to_string())?;
+                    stack
+                        .change_dir(&top)
+                        .map_err(|e| format!("pushd: {}", e))?;
+                }
+                [arg] => match parse_index(arg) {
+                    Some(n) => stack.rotate(n).map_err(|e| format!("pushd: {}", e))?,
+                    None => stack
+                        .change_dir(Path::new(arg))
+                        .map_err(|e| format!("pushd: {}", e))?,
+                },
+                _ => return Err("pushd: too many arguments".to_string()),
+            }
+            Ok(Some(stack.format(false, false)))
+        }
+        "popd" => {
+            let n = match args {
+                [] => 0,
+                [arg] => {
+                    parse_index(arg).ok_or_else(|| format!("popd: {}: invalid argument", arg))?
+                }
+                _ => return Err("popd: too many arguments".to_string()),
+            };
+
+            if n == 0 {
+                let top = stack
+                    .get(1)
+                    .ok_or_else(|| "popd: directory stack empty".to_string())?;
+                enter(&top).map_err(|e| format!("popd: {}", e))?;
+                stack.remove(1);
+            } else if stack.remove(n).is_none() {
+                return Err(format!("popd: +{}: directory stack index out of range", n));
+            }
+            Ok(Some(stack.format(false, false)))
+        }
+        "dirs" => {
+            let mut verbose = false;
+            let mut per_line = false;
+            for arg in args {
+                match *arg {
+                    "-v" => verbose = true,
+                    "-p" => per_line = true,
+                    "-c" => {
+                        stack.clear();
+                        return Ok(None);
+                    }
+                    other => return Err(format!("dirs: {}: invalid option", other)),
+                }
+            }
+            Ok(Some(stack.format(verbose, per_line)))
+        }
+        _ => Err(format!("{}: unknown command", cmd)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_stack() -> (tempfile::TempDir, DirStack) {
+        let dir = tempfile::tempdir().unwrap();
+        let stack = DirStack::with_path(dir.path().join("dirstack.yaml"));
+        (dir, stack)
+    }
+
+    #[test]
+    fn test_push_dedupes_and_persists() {
+        let (dir, stack) = temp_stack();
+        stack.push(PathBuf::from("/a"));
+        stack.push(PathBuf::from("/b"));
+        stack.push(PathBuf::from("/a"));
+        assert_eq!(
+            stack.entries(),
+            vec![PathBuf::from("/a"), PathBuf::from("/b")]
+        );
+
+        let reloaded = DirStack::with_path(dir.path().join("dirstack.yaml"));
+        assert_eq!(reloaded.get(2), Some(PathBuf::from("/b")));
+        assert_eq!(reloaded.get(0), None);
+    }
+
+    #[test]
+    fn test_push_is_bounded() {
+        let (_dir, stack) = temp_stack();
+        for i in 0..MAX_ENTRIES + 5 {
+            stack.push(PathBuf::from(format!("/d{}", i)));
+        }
+        assert_eq!(stack.entries().len(), MAX_ENTRIES);
+        assert_eq!(
+            stack.get(1),
+            Some(PathBuf::from(format!("/d{}", MAX_ENTRIES + 4)))
+        );
+    }
+
+    #[test]
+    fn test_popd_index_and_dirs_clear() {
+        let (_dir, stack) = temp_stack();
+        stack.push(PathBuf::from("/a"));
+        stack.push(PathBuf::from("/b"));
+
+        handle_dirstack_command(&stack, "popd", &["+2"]).unwrap();
+        assert_eq!(stack.entries(), vec![PathBuf::from("/b")]);
+        assert!(handle_dirstack_command(&stack, "popd", &["+5"]).is_err());
+
+        handle_dirstack_command(&stack, "dirs", &["-c"]).unwrap();
+        assert!(stack.entries().is_empty());
+        assert!(handle_dirstack_command(&stack, "popd", &[]).is_err());
+    }
+
+    #[test]
+    fn test_parse_back_ref() {
+        assert_eq!(parse_back_ref("-"), Some(1));
+        assert_eq!(parse_back_ref("-3"), Some(3));
+        assert_eq!(parse_back_ref("-x"), None);
+        assert_eq!(parse_back_ref("dir"), None);
+        assert_eq!(parse_index("+2"), Some(2));
+        assert_eq!(parse_index("2"), None);
+    }
+}
@@ -0,0 +1,282 @@
+//! Session Environment
+//!
+//! Variables owned by sc that persist between command lines:
+//! - Seeded from the process environment at startup (all exported)
+//! - `export`, `unset`, `set` and `env` builtins
+//! - `NAME=value` lines define shell (unexported) variables
+//! - Exported variables become the environment of every child process
+//!
+//! The executor, `EnvVarProvider` and the highlighter all read from the
+//! global [`session_env()`].
+
+#![allow(dead_code)]
+
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+/// A session variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Variable {
+    value: String,
+    exported: bool,
+}
+
+/// Session environment shared by the REPL, executor and completers
+#[derive(Debug, Default)]
+pub struct SessionEnv {
+    vars: RwLock<BTreeMap<String, Variable>>,
+}
+
+impl SessionEnv {
+    /// Create an empty environment
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an environment seeded from the process environment
+    pub fn from_process() -> Self {
+        let env = Self::new();
+        for (name, value) in std::env::vars() {
+            env.export(&name, Some(value));
+        }
+        env
+    }
+
+    /// Get a variable's value
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.vars
+            .read()
+            .unwrap()
+            .get(name)
+            .map(|var| var.value.clone())
+    }
+
+    /// Check whether a variable is defined
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.vars.read().unwrap().contains_key(name)
+    }
+
+    /// Check whether a variable is exported to child processes
+    pub fn is_exported(&self, name: &str) -> bool {
+        self.vars
+            .read()
+            .unwrap()
+            .get(name)
+            .is_some_and(|var| var.exported)
+    }
+
+    /// Set a variable, keeping its exported flag (new variables are not exported)
+    pub fn set(&self, name: &str, value: impl Into<String>) {
+        let mut vars = self.vars.write().unwrap();
+        let value = value.into();
+        match vars.get_mut(name) {
+            Some(var) => var.value = value,
+            None => {
+                vars.insert(
+                    name.to_string(),
+                    Variable {
+                        value,
+                        exported: false,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Export a variable, optionally assigning a new value
+    pub fn export(&self, name: &str, value: Option<String>) {
+        let mut vars = self.vars.write().unwrap();
+        let var = vars.entry(name.to_string()).or_insert_with(|| Variable {
+            value: String::new(),
+            exported: true,
+        });
+        var.exported = true;
+        if let Some(value) = value {
+            var.value = value;
+        }
+    }
+
+    /// Remove a variable; returns whether it existed
+    pub fn unset(&self, name: &str) -> bool {
+        self.vars.write().unwrap().remove(name).is_some()
+    }
+
+    /// All variables, sorted by name
+    pub fn vars(&self) -> Vec<(String, String)> {
+        self.vars
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, var)| (name.clone(), var.value.clone()))
+            .collect()
+    }
+
+    /// Exported variables, sorted by name (the environment for child processes)
+    pub fn exported(&self) -> Vec<(String, String)> {
+        self.vars
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, var)| var.exported)
+            .map(|(name, var)| (name.clone(), var.value.clone()))
+            .collect()
+    }
+}
+
+/// Global session environment
+static SESSION_ENV: Lazy<SessionEnv> = Lazy::new(SessionEnv::from_process);
+
+/// Get the global session environment
+pub fn session_env() -> &'static SessionEnv {
+    &SESSION_ENV
+}
+
+/// Check whether a string is a valid variable name
+pub fn is_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Split a `NAME=value` assignment
+pub fn split_assignment(word: &str) -> Option<(&str, &str)> {
+    let (name, value) = word.split_once('=')?;
+    is_var_name(name).then_some((name, value))
+}
+
+/// Quote a value for `export`/`set` listings
+fn quote(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('$', "\\$")
+    )
+}
+
+/// Handle environment commands: `export`, `unset`, `set`, `env`
+///
+/// Arguments arrive already expanded by the executor.
+pub fn handle_env_command(
+    env: &SessionEnv,
+    cmd: &str,
+    args: &[&str],
+) -> Result<Option<String>, String> {
+    match cmd {
+        "export" => {
+            let args: Vec<&str> = args.iter().copied().filter(|a| *a != "-p").collect();
+            if args.is_empty() {
+                let output: Vec<String> = env
+                    .exported()
+                    .iter()
+                    .map(|(name, value)| format!("export {}={}", name, quote(value)))
+                    .collect();
+                return Ok(Some(output.join("\n")).filter(|s| !s.is_empty()));
+            }
+
+            for arg in args {
+                match split_assignment(arg) {
+                    Some((name, value)) => env.export(name, Some(value.to_string())),
+                    None if is_var_name(arg) => env.export(arg, None),
+                    None => return Err(format!("export: '{}': not a valid identifier", arg)),
+                }
+            }
+            Ok(None)
+        }
+        "unset" => {
+            for name in args.iter().filter(|a| **a != "-v") {
+                if !is_var_name(name) {
+                    return Err(format!("unset: '{}': not a valid identifier", name));
+                }
+                env.unset(name);
+            }
+            Ok(None)
+        }
+        "set" => match args {
+            [] => {
+                let output: Vec<String> = env
+                    .vars()
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, quote(value)))
+                    .collect();
+                Ok(Some(output.join("\n")).filter(|s| !s.is_empty()))
+            }
+            [assignment] => match split_assignment(assignment) {
+                Some((name, value)) => {
+                    env.set(name, value);
+                    Ok(None)
+                }
+                None => Err("Usage: set [NAME=value | NAME value...]".to_string()),
+            },
+            [name, values @ ..] if is_var_name(name) => {
+                env.set(name, values.join(" "));
+                Ok(None)
+            }
+            _ => Err("Usage: set [NAME=value | NAME value...]".to_string()),
+        },
+        "env" => {
+            let output: Vec<String> = env
+                .exported()
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            Ok(Some(output.join("\n")).filter(|s| !s.is_empty()))
+        }
+        _ => Err(format!("{}: unknown command", cmd)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_export() {
+        let env = SessionEnv::new();
+
+        env.set("FOO", "bar");
+        assert_eq!(env.get("FOO"), Some("bar".to_string()));
+        assert!(!env.is_exported("FOO"));
+        assert!(env.exported().is_empty());
+
+        env.export("FOO", None);
+        assert!(env.is_exported("FOO"));
+        env.set("FOO", "baz");
+        assert_eq!(env.exported(), vec![("FOO".to_string(), "baz".to_string())]);
+
+        assert!(env.unset("FOO"));
+        assert!(!env.is_defined("FOO"));
+    }
+
+    #[test]
+    fn test_handle_env_command() {
+        let env = SessionEnv::new();
+
+        handle_env_command(&env, "export", &["A=1", "B=two words"]).unwrap();
+        handle_env_command(&env, "set", &["C", "3"]).unwrap();
+        assert_eq!(env.get("B"), Some("two words".to_string()));
+        assert!(env.is_defined("C") && !env.is_exported("C"));
+
+        let listing = handle_env_command(&env, "export", &[]).unwrap().unwrap();
+        assert_eq!(listing, "export A=\"1\"\nexport B=\"two words\"");
+
+        let listing = handle_env_command(&env, "env", &[]).unwrap().unwrap();
+        assert_eq!(listing, "A=1\nB=two words");
+
+        handle_env_command(&env, "unset", &["A", "C"]).unwrap();
+        assert!(!env.is_defined("A") && !env.is_defined("C"));
+
+        assert!(handle_env_command(&env, "export", &["1BAD=x"]).is_err());
+        assert!(handle_env_command(&env, "unset", &["a-b"]).is_err());
+    }
+
+    #[test]
+    fn test_split_assignment() {
+        assert_eq!(split_assignment("FOO=bar=baz"), Some(("FOO", "bar=baz")));
+        assert_eq!(split_assignment("_x1="), Some(("_x1", "")));
+        assert_eq!(split_assignment("--opt=value"), None);
+        assert_eq!(split_assignment("plain"), None);
+    }
+}
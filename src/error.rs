@@ -70,7 +70,7 @@ pub enum UpgradeError {
 
 /// Command execution errors
 #[allow(dead_code)]
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum CommandError {
     #[error("Command not found: {0}")]
     NotFound(String),
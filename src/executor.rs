@@ -0,0 +1,1792 @@
+//! Native Command Executor
+//!
+//! Runs command lines parsed by [`Pipeline`] directly instead of handing
+//! them to `sh -c`:
+//! - Pipes (`|`) between external commands and builtins
+//! - Chains (`&&`, `||`, `;`) with short-circuit evaluation
+//! - Redirections (`>`, `>>`, `<`, `2>`, `2>&1`)
+//! - Builtins (`cd`, `alias`, `bm`, ...) anywhere inside a chain
+//! - Per-segment exit status and duration for the timer and hinter
+//! - Background (`&`) and suspended jobs via the [`JobTable`]
+//! - `$VAR` expansion and child environments from the [`session_env()`]
+//! - Command substitution (`$(cmd)`, backticks), run by the executor itself
+//!   so builtins work on both sides: `export GOPATH=$(go env GOPATH)`
+//! - Timeouts: `exec.timeout_secs`, per-definition `default_timeout`, and
+//!   the `timeout DURATION cmd` prefix
+//!
+//! Syntax the native executor does not understand (subshells, arithmetic,
+//! control flow keywords, here-docs) falls back to `sh -c`, or to the shell
+//! configured as the [`Backend`].
+
+#![allow(dead_code)]
+
+use crate::backend::{Backend, Coprocess, Reply, ShellKind};
+use crate::dirstack::dir_stack;
+use crate::environment::{is_var_name, session_env, split_assignment};
+use crate::error::CommandError;
+use crate::jobs::{self, JobEntry, JobProcess, JobTable};
+use crate::output::Output;
+use crate::pipeline::{Pipeline, PipelineOperator};
+use crate::tokenizer::read_substitution;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, PipeReader, PipeWriter, Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Exit status used when a command cannot be found
+pub const STATUS_NOT_FOUND: i32 = 127;
+/// Exit status used when a command exists but cannot be executed
+pub const STATUS_NOT_EXECUTABLE: i32 = 126;
+/// Exit status used for syntax errors in the command line
+pub const STATUS_SYNTAX_ERROR: i32 = 2;
+
+/// A redirection attached to a simple command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Redirect {
+    /// `> file`
+    Stdout(String),
+    /// `>> file`
+    StdoutAppend(String),
+    /// `< file`
+    Stdin(String),
+    /// `2> file`
+    Stderr(String),
+    /// `2>&1`
+    StderrToStdout,
+}
+
+/// A single command with its raw (unexpanded) words and redirections
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimpleCommand {
+    /// Raw words, quotes and variables still in place
+    pub words: Vec<String>,
+    /// Redirections in the order they appeared
+    pub redirects: Vec<Redirect>,
+}
+
+impl Redirect {
+    /// The redirection as it would be typed
+    pub fn text(&self) -> String {
+        match self {
+            Self::Stdout(path) => format!("> {}", path),
+            Self::StdoutAppend(path) => format!(">> {}", path),
+            Self::Stdin(path) => format!("< {}", path),
+            Self::Stderr(path) => format!("2> {}", path),
+            Self::StderrToStdout => "2>&1".to_string(),
+        }
+    }
+}
+
+impl SimpleCommand {
+    /// Reconstruct the command text (used for timer records and messages)
+    pub fn text(&self) -> String {
+        self.words.join(" ")
+    }
+
+    /// Words and redirections, for handing the command to a shell
+    pub fn source(&self) -> String {
+        let redirects = self.redirects.iter().map(Redirect::text);
+        self.words
+            .iter()
+            .cloned()
+            .chain(redirects)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// A pipeline of simple commands connected by `|`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Job {
+    /// Commands in pipeline order
+    pub commands: Vec<SimpleCommand>,
+    /// Whether the job was started with a trailing `&`
+    pub background: bool,
+}
+
+impl Job {
+    /// Reconstruct the pipeline text (without a trailing `&`)
+    pub fn text(&self) -> String {
+        self.commands
+            .iter()
+            .map(|c| c.text())
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    /// Reconstruct the pipeline with its redirections, for a shell
+    pub fn source(&self) -> String {
+        self.commands
+            .iter()
+            .map(|c| c.source())
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+}
+
+/// How a job is connected to the job before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    /// First job, `;` or `&`
+    Always,
+    /// `&&`
+    IfSuccess,
+    /// `||`
+    IfFailure,
+}
+
+/// A full command line: jobs joined by `&&`, `||`, `;` and `&`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandList {
+    pub jobs: Vec<(Connector, Job)>,
+}
+
+impl CommandList {
+    /// Parse a command line
+    pub fn parse(line: &str) -> Result<Self, String> {
+        Self::from_pipeline(&Pipeline::parse(line))
+    }
+
+    /// Lower a parsed pipeline into jobs and simple commands
+    pub fn from_pipeline(pipeline: &Pipeline) -> Result<Self, String> {
+        let mut list = CommandList::default();
+        let mut job = Job::default();
+        let mut command = SimpleCommand::default();
+        let mut connector = Connector::Always;
+        let mut pending: Option<PipelineOperator> = None;
+
+        for segment in &pipeline.segments {
+            if let Some(op) = pending.take() {
+                // The first word of this segment is the redirect target
+                if segment.command.is_empty() {
+                    return Err(format!(
+                        "syntax error: expected file name after '{}'",
+                        op.as_str()
+                    ));
+                }
+                let target = segment.command.clone();
+                command.redirects.push(match op {
+                    PipelineOperator::RedirectOut => Redirect::Stdout(target),
+                    PipelineOperator::RedirectAppend => Redirect::StdoutAppend(target),
+                    PipelineOperator::RedirectIn => Redirect::Stdin(target),
+                    _ => Redirect::Stderr(target),
+                });
+                command.words.extend(segment.args.iter().cloned());
+            } else if !segment.command.is_empty() {
+                command.words.push(segment.command.clone());
+                command.words.extend(segment.args.iter().cloned());
+            }
+
+            match &segment.operator_after {
+                Some(
+                    op @ (PipelineOperator::RedirectOut
+                    | PipelineOperator::RedirectAppend
+                    | PipelineOperator::RedirectIn
+                    | PipelineOperator::RedirectErr),
+                ) => pending = Some(op.clone()),
+                Some(PipelineOperator::RedirectErrToOut) => {
+                    command.redirects.push(Redirect::StderrToStdout)
+                }
+                Some(PipelineOperator::Pipe) => {
+                    if command.words.is_empty() {
+                        return Err("syntax error near unexpected token '|'".to_string());
+                    }
+                    job.commands.push(std::mem::take(&mut command));
+                }
+                Some(
+                    op @ (PipelineOperator::And
+                    | PipelineOperator::Or
+                    | PipelineOperator::Sequence
+                    | PipelineOperator::Background),
+                ) => {
+                    if command.words.is_empty() {
+                        return Err(format!(
+                            "syntax error near unexpected token '{}'",
+                            op.as_str()
+                        ));
+                    }
+                    job.commands.push(std::mem::take(&mut command));
+                    job.background = *op == PipelineOperator::Background;
+                    list.jobs.push((connector, std::mem::take(&mut job)));
+                    connector = match op {
+                        PipelineOperator::And => Connector::IfSuccess,
+                        PipelineOperator::Or => Connector::IfFailure,
+                        _ => Connector::Always,
+                    };
+                }
+                None => {}
+            }
+        }
+
+        if let Some(op) = pending {
+            return Err(format!(
+                "syntax error: expected file name after '{}'",
+                op.as_str()
+            ));
+        }
+
+        if command.words.is_empty() {
+            // A trailing `;` or `&` ends the line cleanly, anything else is incomplete
+            if !job.commands.is_empty() || connector != Connector::Always {
+                return Err("syntax error: unexpected end of command".to_string());
+            }
+        } else {
+            job.commands.push(command);
+            list.jobs.push((connector, job));
+        }
+
+        Ok(list)
+    }
+}
+
+/// Shell builtins that run inside the REPL process
+pub trait Builtins {
+    /// Whether the expanded command `argv` is handled by this builtin set
+    fn is_builtin(&self, argv: &[String]) -> bool;
+
+    /// Run a builtin, writing its regular output to `out`, and return its exit status
+    fn run_builtin(&mut self, argv: &[String], out: &mut dyn Write) -> i32;
+}
+
+/// Exit status of one executed command
+#[derive(Debug, Clone)]
+pub struct SegmentStatus {
+    /// Command text as typed
+    pub command: String,
+    /// Exit status (128 + signal for signalled processes)
+    pub status: i32,
+    /// Time until the command finished
+    pub duration: Duration,
+}
+
+/// Result of executing a full command line
+#[derive(Debug, Clone, Default)]
+pub struct ExecOutcome {
+    /// Status of the last job that ran
+    pub status: i32,
+    /// Status of every command that ran, in order
+    pub segments: Vec<SegmentStatus>,
+    /// Why the last job was cut short (timeout or Ctrl-C), if it was
+    pub error: Option<CommandError>,
+}
+
+impl ExecOutcome {
+    /// Whether the command line succeeded
+    pub fn success(&self) -> bool {
+        self.status == 0
+    }
+}
+
+/// Execution timeouts for foreground jobs
+///
+/// A zero duration means "no timeout", so a definition can opt out of the
+/// global default (e.g. `default_timeout: 0` for an editor).
+#[derive(Debug, Clone, Default)]
+pub struct Timeouts {
+    /// Applies to every job without a more specific timeout
+    pub default: Option<Duration>,
+    /// Per-definition timeouts keyed by command path (`cargo`, `cargo test`)
+    pub commands: HashMap<String, Duration>,
+}
+
+impl Timeouts {
+    /// Timeout for a job: the first stage with a per-command timeout,
+    /// otherwise the default
+    pub fn for_job(&self, job: &Job) -> Option<Duration> {
+        let specific = job
+            .commands
+            .iter()
+            .find_map(|command| self.specific(&command.words));
+        specific
+            .or(self.default)
+            .filter(|timeout| !timeout.is_zero())
+    }
+
+    /// Timeout for a single command's words
+    pub fn for_words(&self, words: &[String]) -> Option<Duration> {
+        self.specific(words)
+            .or(self.default)
+            .filter(|timeout| !timeout.is_zero())
+    }
+
+    /// Per-command timeout of the longest matching command path
+    fn specific(&self, words: &[String]) -> Option<Duration> {
+        (1..=words.len())
+            .rev()
+            .find_map(|n| self.commands.get(&words[..n].join(" ")))
+            .copied()
+    }
+}
+
+/// Parse a `timeout` duration: seconds with an optional `s`, `m`, `h` or `d` suffix
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let (number, unit) = match text.char_indices().last()? {
+        (i, c) if c.is_ascii_alphabetic() => (&text[..i], c),
+        _ => (text, 's'),
+    };
+    let multiplier = match unit {
+        's' => 1.0,
+        'm' => 60.0,
+        'h' => 3600.0,
+        'd' => 86400.0,
+        _ => return None,
+    };
+    let value: f64 = number.parse().ok()?;
+    (value.is_finite() && value >= 0.0).then(|| Duration::from_secs_f64(value * multiplier))
+}
+
+/// Strip a leading `timeout DURATION` from a job
+///
+/// Anything the builtin does not understand (e.g. `timeout -s KILL 5 cmd`)
+/// is left for the external `timeout` command.
+fn strip_timeout_prefix(job: &Job) -> Option<(Job, Duration)> {
+    let first = job.commands.first()?;
+    match first.words.as_slice() {
+        [name, duration, _, ..] if name == "timeout" => {
+            let timeout = parse_duration(duration)?;
+            let mut job = job.clone();
+            job.commands[0].words.drain(..2);
+            Some((job, timeout))
+        }
+        _ => None,
+    }
+}
+
+/// Strip a leading `time` from a job, to be timed by the executor
+///
+/// `time stats` and friends stay with the builtin, and options (e.g.
+/// `time -v make`) are left for the external `time` command.
+fn strip_time_prefix(job: &Job, builtins: &dyn Builtins) -> Option<Job> {
+    let first = job.commands.first()?;
+    match first.words.as_slice() {
+        [name, next, ..]
+            if name == "time" && !next.starts_with('-') && !builtins.is_builtin(&first.words) =>
+        {
+            let mut job = job.clone();
+            job.commands[0].words.remove(0);
+            Some(job)
+        }
+        _ => None,
+    }
+}
+
+/// Where a spawned stage goes: its job's process group and the terminal
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    pgid: Option<u32>,
+    foreground: bool,
+    /// Give the job its own process group even without job control
+    own_group: bool,
+}
+
+/// Native command executor
+#[derive(Default)]
+pub struct Executor {
+    /// Background and suspended jobs
+    jobs: JobTable,
+    /// Status of the last command, used for `$?`
+    last_status: i32,
+    /// Timeouts for foreground jobs
+    timeouts: Timeouts,
+    /// Where lines the native executor does not run go
+    backend: Backend,
+    /// Keep one backend shell alive between lines
+    persistent: bool,
+    /// The running persistent shell
+    coprocess: Option<Coprocess>,
+    /// Where output goes while a command substitution runs
+    capture: Option<PipeWriter>,
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable job control (process groups, terminal hand-over, Ctrl-Z)
+    ///
+    /// Only takes effect when stdin is a terminal; returns whether it did.
+    pub fn enable_job_control(&mut self) -> bool {
+        self.jobs.enable_job_control()
+    }
+
+    pub fn jobs(&self) -> &JobTable {
+        &self.jobs
+    }
+
+    /// Set the global and per-command timeouts
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Set where command lines run, and whether a shell backend stays alive
+    /// between lines
+    pub fn set_backend(&mut self, backend: Backend, persistent: bool) {
+        self.backend = backend;
+        self.persistent = persistent;
+        if let Some(coprocess) = self.coprocess.take() {
+            coprocess.kill();
+        }
+    }
+
+    /// Print notices for jobs that finished or stopped since the last prompt
+    pub fn notify_jobs(&mut self) {
+        for notice in self.jobs.take_notices() {
+            Output::dim(&notice);
+        }
+    }
+
+    /// Execute a command line
+    pub fn execute(&mut self, line: &str, builtins: &mut dyn Builtins) -> ExecOutcome {
+        self.jobs.update();
+
+        let outcome = if needs_shell(line) {
+            debug!("Running through {:?}: {}", self.backend, line);
+            self.execute_with_shell(line)
+        } else {
+            match CommandList::parse(line) {
+                Ok(list) => self.execute_list(&list, builtins),
+                // A backend shell reports its own syntax errors
+                Err(_) if !self.backend.is_native() => self.execute_with_shell(line),
+                Err(e) => {
+                    Output::error(&e);
+                    ExecOutcome {
+                        status: STATUS_SYNTAX_ERROR,
+                        ..Default::default()
+                    }
+                }
+            }
+        };
+
+        self.last_status = outcome.status;
+        outcome
+    }
+
+    /// Run the body of a command substitution and return what it printed,
+    /// without trailing newlines
+    fn substitute(&mut self, body: &str, builtins: &mut dyn Builtins) -> String {
+        let (mut reader, writer) = match io::pipe() {
+            Ok(pipe) => pipe,
+            Err(e) => {
+                Output::error(&format!("pipe: {}", e));
+                return String::new();
+            }
+        };
+        // Read while the body runs so a long output cannot fill the pipe
+        let collector = std::thread::spawn(move || {
+            let mut output = Vec::new();
+            reader.read_to_end(&mut output).ok();
+            output
+        });
+
+        let outer = self.capture.replace(writer);
+        self.execute(body, builtins);
+        // Dropping our end lets the reader see EOF once the body is done
+        self.capture = outer;
+
+        let output = collector.join().unwrap_or_default();
+        String::from_utf8_lossy(&output)
+            .trim_end_matches('\n')
+            .to_string()
+    }
+
+    /// Exit status of the last command line (`$?`)
+    pub fn last_status(&self) -> i32 {
+        self.last_status
+    }
+
+    /// Override `$?`, e.g. when a command line was cancelled before running
+    pub fn set_last_status(&mut self, status: i32) {
+        self.last_status = status;
+    }
+
+    /// Whether a shell backend should run the job
+    ///
+    /// Jobs that use sc builtins, only assign variables or run in the
+    /// background still run natively, so `cd /usr && myfunc` changes sc's
+    /// directory and then calls the shell's function.
+    fn uses_backend(&self, job: &Job, builtins: &dyn Builtins) -> bool {
+        if self.backend.is_native() || job.background {
+            return false;
+        }
+
+        let native = job.commands.iter().any(|command| {
+            let words: Vec<String> = command
+                .words
+                .iter()
+                .skip_while(|word| split_assignment(word).is_some())
+                .cloned()
+                .collect();
+            words.first().is_none_or(|name| {
+                jobs::JOB_BUILTINS.contains(&name.as_str()) || builtins.is_builtin(&words)
+            })
+        });
+        !native
+    }
+
+    /// Run the line through the backend shell (`sh -c` for the native backend)
+    fn execute_with_shell(&mut self, line: &str) -> ExecOutcome {
+        let start = Instant::now();
+        let words: Vec<String> = line.split_whitespace().map(String::from).collect();
+        let timeout = self.timeouts.for_words(&words);
+
+        let (status, error) = match &self.backend {
+            // The persistent shell writes to the terminal, not a capture pipe
+            Backend::Shell { kind, program } if self.persistent && self.capture.is_none() => {
+                let (kind, program) = (*kind, program.clone());
+                self.execute_in_coprocess(line, kind, &program, timeout)
+            }
+            _ => self.execute_once(line, timeout),
+        };
+        ExecOutcome {
+            status,
+            segments: vec![SegmentStatus {
+                command: line.to_string(),
+                status,
+                duration: start.elapsed(),
+            }],
+            error,
+        }
+    }
+
+    /// Run the line in a fresh backend process
+    fn execute_once(
+        &mut self,
+        line: &str,
+        timeout: Option<Duration>,
+    ) -> (i32, Option<CommandError>) {
+        let dir_file = if self.backend.reports_directory() {
+            tempfile::NamedTempFile::new().ok()
+        } else {
+            None
+        };
+
+        let mut cmd = self.backend.command(
+            line,
+            dir_file.as_ref().map(|f| f.path()),
+            self.last_status,
+        );
+        cmd.env_clear().envs(session_env().exported());
+        if let Some(capture) = self.capture.as_ref().and_then(|c| c.try_clone().ok()) {
+            cmd.stdout(capture);
+        }
+        self.jobs.configure(&mut cmd, None, true, timeout.is_some());
+
+        let (status, error) = match cmd.spawn() {
+            Ok(child) => {
+                let mut entry = JobEntry::new(line, vec![JobProcess::new(child)]);
+                let error = self.wait_job(&mut entry, timeout);
+                let status = match (&error, entry.state) {
+                    (Some(CommandError::Timeout(_)), _) => jobs::STATUS_TIMED_OUT,
+                    (_, jobs::JobState::Done(status)) => status,
+                    _ => jobs::STATUS_STOPPED,
+                };
+                self.keep_if_stopped(entry);
+                (status, error)
+            }
+            Err(e) => {
+                Output::error(&format!("Error executing command: {}", e));
+                (STATUS_NOT_EXECUTABLE, None)
+            }
+        };
+
+        if let Some(dir) = dir_file.and_then(|f| std::fs::read_to_string(f.path()).ok()) {
+            follow_directory(Path::new(dir.trim_end_matches('\n')));
+        }
+        (status, error)
+    }
+
+    /// Run the line in the persistent backend shell, starting it if needed
+    fn execute_in_coprocess(
+        &mut self,
+        line: &str,
+        kind: ShellKind,
+        program: &str,
+        timeout: Option<Duration>,
+    ) -> (i32, Option<CommandError>) {
+        if !self.coprocess.as_mut().is_some_and(|c| c.is_alive()) {
+            match Coprocess::spawn(kind, program, self.jobs.job_control()) {
+                Ok(coprocess) => self.coprocess = Some(coprocess),
+                Err(e) => {
+                    Output::error(&format!("{}: {}", program, e));
+                    return (STATUS_NOT_EXECUTABLE, None);
+                }
+            }
+        }
+        let Some(coprocess) = self.coprocess.as_mut() else {
+            return (STATUS_NOT_EXECUTABLE, None);
+        };
+
+        let started = Instant::now();
+        if let Err(e) = coprocess.send(line, self.last_status) {
+            Output::error(&format!("{}: {}", program, e));
+            self.coprocess = None;
+            return (STATUS_NOT_EXECUTABLE, None);
+        }
+        if coprocess.own_group() {
+            self.jobs.lend_terminal(coprocess.pid());
+        }
+        let reply = coprocess.wait_reply(timeout.map(|t| started + t));
+        self.jobs.reclaim_terminal();
+
+        match reply {
+            Ok(Reply::Done { status, dir }) => {
+                follow_directory(&dir);
+                let error =
+                    (status == jobs::STATUS_INTERRUPTED).then_some(CommandError::Interrupted);
+                (status, error)
+            }
+            Ok(Reply::TimedOut) => {
+                // Its state cannot be saved, so the next line starts a new shell
+                if let Some(coprocess) = self.coprocess.take() {
+                    coprocess.kill();
+                }
+                let secs = timeout.map_or(0, |t| t.as_secs_f64().ceil() as u64);
+                (jobs::STATUS_TIMED_OUT, Some(CommandError::Timeout(secs)))
+            }
+            Ok(Reply::Exited(status)) => {
+                self.coprocess = None;
+                (status, None)
+            }
+            Err(e) => {
+                Output::error(&format!("{}: {}", program, e));
+                if let Some(coprocess) = self.coprocess.take() {
+                    coprocess.kill();
+                }
+                (STATUS_NOT_EXECUTABLE, None)
+            }
+        }
+    }
+
+    /// Wait for a foreground job and return why it was cut short, if it was
+    fn wait_job(
+        &mut self,
+        entry: &mut JobEntry,
+        timeout: Option<Duration>,
+    ) -> Option<CommandError> {
+        let timed_out = self.jobs.wait_foreground(entry, timeout);
+
+        match (timed_out, timeout) {
+            (true, Some(timeout)) => {
+                Some(CommandError::Timeout(timeout.as_secs_f64().ceil() as u64))
+            }
+            _ if entry.state == jobs::JobState::Done(jobs::STATUS_INTERRUPTED) => {
+                Some(CommandError::Interrupted)
+            }
+            _ => None,
+        }
+    }
+
+    /// Move a suspended foreground job into the job table
+    ///
+    /// Without job control (`sc -c`, scripts) the job is killed instead.
+    fn keep_if_stopped(&mut self, mut entry: JobEntry) {
+        if entry.state == jobs::JobState::Stopped && !self.jobs.job_control() {
+            self.jobs.kill_stopped(&mut entry);
+            Output::warn(&format!("Killed stopped job: {}", entry.command));
+        } else if entry.state == jobs::JobState::Stopped {
+            let command = entry.command.clone();
+            let id = self.jobs.add(entry);
+            println!();
+            Output::dim(&format!("[{}]+  Stopped    {}", id, command));
+        }
+    }
+
+    fn execute_list(&mut self, list: &CommandList, builtins: &mut dyn Builtins) -> ExecOutcome {
+        let mut outcome = ExecOutcome::default();
+
+        for (connector, job) in &list.jobs {
+            let run = match connector {
+                Connector::Always => true,
+                Connector::IfSuccess => outcome.status == 0,
+                Connector::IfFailure => outcome.status != 0,
+            };
+            if !run {
+                continue;
+            }
+
+            let (segments, error) = if self.uses_backend(job, builtins) {
+                let source = job.source();
+                debug!("Running through {:?}: {}", self.backend, source);
+                let outcome = self.execute_with_shell(&source);
+                (outcome.segments, outcome.error)
+            } else {
+                self.run_job(job, builtins)
+            };
+            if let Some(last) = segments.last() {
+                outcome.status = last.status;
+            }
+            self.last_status = outcome.status;
+            outcome.segments.extend(segments);
+            outcome.error = error;
+        }
+
+        outcome
+    }
+
+    /// Run one pipeline and return the status of each stage
+    fn run_job(
+        &mut self,
+        job: &Job,
+        builtins: &mut dyn Builtins,
+    ) -> (Vec<SegmentStatus>, Option<CommandError>) {
+        let start = Instant::now();
+
+        // `time cmd` reports how long the rest of the job took, like sh's
+        if let Some(timed) = strip_time_prefix(job, builtins).filter(|_| !job.background) {
+            let result = self.run_job(&timed, builtins);
+            let secs = start.elapsed().as_secs_f64();
+            eprintln!("\nreal\t{}m{:.3}s", (secs / 60.0).floor(), secs % 60.0);
+            return result;
+        }
+
+        // `timeout 30 cmd` overrides the configured timeouts
+        let stripped = strip_timeout_prefix(job);
+        let (job, timeout) = match &stripped {
+            Some((job, timeout)) => (job, Some(*timeout).filter(|t| !t.is_zero())),
+            None => (job, self.timeouts.for_job(job)),
+        };
+        let timeout = timeout.filter(|_| !job.background);
+
+        let count = job.commands.len();
+        let mut stages: Vec<Stage> = Vec::with_capacity(count);
+        let mut stdin_pipe: Option<PipeReader> = None;
+        let mut pgid: Option<u32> = None;
+        let foreground = !job.background;
+
+        for (i, command) in job.commands.iter().enumerate() {
+            let stdin = stdin_pipe.take();
+            let next_pipe = if i + 1 < count {
+                match io::pipe() {
+                    Ok((reader, writer)) => {
+                        stdin_pipe = Some(reader);
+                        Some(writer)
+                    }
+                    Err(e) => {
+                        Output::error(&format!("pipe: {}", e));
+                        stages.push(Stage::Done(1));
+                        break;
+                    }
+                }
+            } else {
+                None
+            };
+
+            let placement = Placement {
+                pgid,
+                foreground,
+                own_group: timeout.is_some(),
+            };
+            let stage = self.spawn_stage(command, stdin, next_pipe, builtins, placement);
+            if let (None, Stage::Running(child)) = (pgid, &stage) {
+                pgid = Some(child.id());
+            }
+            stages.push(stage);
+        }
+        drop(stdin_pipe);
+
+        // Builtins run after every external stage is spawned so pipes never stall
+        for stage in stages.iter_mut() {
+            if let Stage::Builtin { argv, out } = stage {
+                let status = match out.as_mut() {
+                    Some(writer) => builtins.run_builtin(argv, writer),
+                    None => builtins.run_builtin(argv, &mut io::stdout()),
+                };
+                // Dropping the writer closes the pipe for the next stage
+                *stage = Stage::Done(status);
+            }
+        }
+
+        // Hand the running processes to a job, remembering where each stage went
+        let mut processes = Vec::new();
+        let slots: Vec<Result<usize, i32>> = stages
+            .into_iter()
+            .map(|stage| match stage {
+                Stage::Running(child) => {
+                    processes.push(JobProcess::new(child));
+                    Ok(processes.len() - 1)
+                }
+                Stage::Done(status) => Err(status),
+                Stage::Builtin { .. } => Err(0),
+            })
+            .collect();
+
+        if processes.is_empty() {
+            let segments = slots
+                .into_iter()
+                .zip(&job.commands)
+                .map(|(slot, command)| SegmentStatus {
+                    command: command.text(),
+                    status: slot.err().unwrap_or(0),
+                    duration: start.elapsed(),
+                })
+                .collect();
+            return (segments, None);
+        }
+
+        let mut entry = JobEntry::new(job.text(), processes);
+
+        if job.background {
+            let pid = entry.pgid;
+            let id = self.jobs.add(entry);
+            Output::dim(&format!("[{}] {}", id, pid));
+            let segments = job
+                .commands
+                .iter()
+                .map(|command| SegmentStatus {
+                    command: command.text(),
+                    status: 0,
+                    duration: start.elapsed(),
+                })
+                .collect();
+            return (segments, None);
+        }
+
+        let error = self.wait_job(&mut entry, timeout);
+
+        let mut segments: Vec<SegmentStatus> = slots
+            .into_iter()
+            .zip(&job.commands)
+            .map(|(slot, command)| {
+                let (status, finished) = match slot {
+                    Ok(index) => {
+                        let process = &entry.processes[index];
+                        (
+                            process.status.unwrap_or(jobs::STATUS_STOPPED),
+                            process.finished,
+                        )
+                    }
+                    Err(status) => (status, None),
+                };
+                SegmentStatus {
+                    command: command.text(),
+                    status,
+                    duration: finished.unwrap_or_else(Instant::now) - start,
+                }
+            })
+            .collect();
+
+        // GNU `timeout` reports 124 for the whole job
+        if let (Some(CommandError::Timeout(_)), Some(last)) = (&error, segments.last_mut()) {
+            last.status = jobs::STATUS_TIMED_OUT;
+        }
+
+        self.keep_if_stopped(entry);
+        (segments, error)
+    }
+
+    /// Expand a command, wire up its redirections and start it
+    fn spawn_stage(
+        &mut self,
+        command: &SimpleCommand,
+        stdin: Option<PipeReader>,
+        stdout_pipe: Option<PipeWriter>,
+        builtins: &mut dyn Builtins,
+        placement: Placement,
+    ) -> Stage {
+        let last_status = self.last_status;
+        let lookup = &|name: &str| lookup_var(name, last_status);
+        // The last stage of a substitution writes to the capture pipe
+        let stdout_pipe = stdout_pipe.or_else(|| {
+            self.capture
+                .as_ref()
+                .and_then(|capture| capture.try_clone().ok())
+        });
+        let substitute = &mut |body: &str| self.substitute(body, builtins);
+
+        let mut assignments = Vec::new();
+        let mut argv = Vec::new();
+        for word in &command.words {
+            if argv.is_empty() {
+                if let Some((name, value)) = split_assignment(word) {
+                    let value = expand_word(value, lookup, substitute).join(" ");
+                    assignments.push((name.to_string(), value));
+                    continue;
+                }
+            }
+            argv.extend(expand_word(word, lookup, substitute));
+        }
+
+        let mut stdin = stdin.map(Target::Reader);
+        let mut stdout = stdout_pipe.map(Target::Writer);
+        let mut stderr: Option<Target> = None;
+
+        for redirect in &command.redirects {
+            let result = match redirect {
+                Redirect::Stdout(path) => {
+                    open_target(path, lookup, substitute, false).map(|f| stdout = Some(f))
+                }
+                Redirect::StdoutAppend(path) => {
+                    open_target(path, lookup, substitute, true).map(|f| stdout = Some(f))
+                }
+                Redirect::Stdin(path) => {
+                    let path = expand_target(path, lookup, substitute);
+                    File::open(&path)
+                        .map(|f| stdin = Some(Target::File(f)))
+                        .map_err(|e| format!("{}: {}", path, e))
+                }
+                Redirect::Stderr(path) => {
+                    open_target(path, lookup, substitute, false).map(|f| stderr = Some(f))
+                }
+                Redirect::StderrToStdout => match &stdout {
+                    Some(target) => target
+                        .try_clone()
+                        .map(|t| stderr = Some(t))
+                        .map_err(|e| e.to_string()),
+                    None => {
+                        stderr = Some(Target::Stdout);
+                        Ok(())
+                    }
+                },
+            };
+            if let Err(e) = result {
+                Output::error(&e);
+                return Stage::Done(1);
+            }
+        }
+
+        if argv.is_empty() {
+            // Bare assignments set variables for the rest of the session
+            for (name, value) in assignments {
+                session_env().set(&name, value);
+            }
+            return Stage::Done(0);
+        }
+
+        if jobs::JOB_BUILTINS.contains(&argv[0].as_str()) {
+            return Stage::Done(match stdout {
+                Some(Target::File(mut file)) => self.jobs.run_builtin(&argv, &mut file),
+                Some(Target::Writer(mut writer)) => self.jobs.run_builtin(&argv, &mut writer),
+                _ => self.jobs.run_builtin(&argv, &mut io::stdout()),
+            });
+        }
+
+        if builtins.is_builtin(&argv) {
+            let out: Option<Box<dyn Write>> = match stdout {
+                Some(Target::File(file)) => Some(Box::new(file)),
+                Some(Target::Writer(writer)) => Some(Box::new(writer)),
+                _ => None,
+            };
+            return Stage::Builtin { argv, out };
+        }
+
+        let mut cmd = Command::new(&argv[0]);
+        cmd.args(&argv[1..])
+            .env_clear()
+            .envs(session_env().exported())
+            .envs(assignments);
+        if let Some(target) = stdin {
+            cmd.stdin(target.into_stdio());
+        }
+        if let Some(target) = stdout {
+            cmd.stdout(target.into_stdio());
+        }
+        if let Some(target) = stderr {
+            cmd.stderr(target.into_stdio());
+        }
+        self.jobs.configure(
+            &mut cmd,
+            placement.pgid,
+            placement.foreground,
+            placement.own_group,
+        );
+
+        match cmd.spawn() {
+            Ok(child) => Stage::Running(child),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Output::error(&format!("{}: command not found", argv[0]));
+                Stage::Done(STATUS_NOT_FOUND)
+            }
+            Err(e) => {
+                Output::error(&format!("{}: {}", argv[0], e));
+                Stage::Done(STATUS_NOT_EXECUTABLE)
+            }
+        }
+    }
+}
+
+/// A pipeline stage between spawning and waiting
+enum Stage {
+    /// External process still running
+    Running(Child),
+    /// Builtin waiting to run, with its stdout target (`None` = terminal)
+    Builtin {
+        argv: Vec<String>,
+        out: Option<Box<dyn Write>>,
+    },
+    /// Finished (or failed to start) with a status
+    Done(i32),
+}
+
+/// Where a standard stream of a stage is connected
+enum Target {
+    /// The REPL's own stdout
+    Stdout,
+    File(File),
+    Reader(PipeReader),
+    Writer(PipeWriter),
+}
+
+impl Target {
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(match self {
+            Target::Stdout => Target::Stdout,
+            Target::File(file) => Target::File(file.try_clone()?),
+            Target::Reader(reader) => Target::Reader(reader.try_clone()?),
+            Target::Writer(writer) => Target::Writer(writer.try_clone()?),
+        })
+    }
+
+    fn into_stdio(self) -> Stdio {
+        match self {
+            Target::Stdout => Stdio::from(io::stdout()),
+            Target::File(file) => Stdio::from(file),
+            Target::Reader(reader) => Stdio::from(reader),
+            Target::Writer(writer) => Stdio::from(writer),
+        }
+    }
+}
+
+/// Convert an exit status to a shell-style code (128 + signal when killed)
+pub fn status_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    1
+}
+
+/// Resolve a variable for expansion
+fn lookup_var(name: &str, last_status: i32) -> Option<String> {
+    if name == "?" {
+        return Some(last_status.to_string());
+    }
+    session_env().get(name)
+}
+
+/// Expand a redirect target to a single path
+fn expand_target(
+    word: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    substitute: &mut dyn FnMut(&str) -> String,
+) -> String {
+    expand_word(word, lookup, substitute).join(" ")
+}
+
+/// Open a file for an output redirection
+fn open_target(
+    word: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    substitute: &mut dyn FnMut(&str) -> String,
+    append: bool,
+) -> Result<Target, String> {
+    let path = expand_target(word, lookup, substitute);
+    let file = if append {
+        OpenOptions::new().create(true).append(true).open(&path)
+    } else {
+        File::create(&path)
+    };
+    file.map(Target::File)
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Accumulates an expanded word and its glob pattern side by side
+#[derive(Default)]
+struct WordBuilder {
+    value: String,
+    pattern: String,
+    has_glob: bool,
+    quoted: bool,
+}
+
+impl WordBuilder {
+    fn push_literal(&mut self, c: char) {
+        self.value.push(c);
+        self.pattern
+            .push_str(&glob::Pattern::escape(&c.to_string()));
+    }
+
+    fn push_str_literal(&mut self, s: &str) {
+        self.value.push_str(s);
+        self.pattern.push_str(&glob::Pattern::escape(s));
+    }
+
+    fn push_glob(&mut self, c: char) {
+        self.value.push(c);
+        self.pattern.push(c);
+        self.has_glob = true;
+    }
+
+    /// The finished word, or the paths its glob matches
+    fn finish(self) -> Vec<String> {
+        if self.has_glob {
+            let options = glob::MatchOptions {
+                require_literal_leading_dot: true,
+                ..Default::default()
+            };
+            if let Ok(paths) = glob::glob_with(&self.pattern, options) {
+                let mut matches: Vec<String> = paths
+                    .filter_map(Result::ok)
+                    .map(|p| p.display().to_string())
+                    .collect();
+                if !matches.is_empty() {
+                    matches.sort();
+                    return matches;
+                }
+            }
+        }
+
+        if self.value.is_empty() && !self.quoted {
+            return Vec::new();
+        }
+
+        vec![self.value]
+    }
+}
+
+/// Expand one raw word: tilde, quotes, escapes, variables, command
+/// substitutions and globs
+///
+/// `substitute` runs the body of a `$(...)` or backtick substitution and
+/// returns its output. Returns no words for an unquoted variable that
+/// expands to nothing, and several words when a glob matches more than one
+/// path or an unquoted substitution prints several words.
+pub fn expand_word(
+    word: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    substitute: &mut dyn FnMut(&str) -> String,
+) -> Vec<String> {
+    let mut fields: Vec<WordBuilder> = Vec::new();
+    let mut builder = WordBuilder::default();
+    let mut chars = word.chars().peekable();
+    let mut in_single = false;
+    let mut in_double = false;
+
+    if word == "~" || word.starts_with("~/") {
+        if let Some(home) = dirs::home_dir() {
+            builder.push_str_literal(&home.display().to_string());
+            chars.next();
+        }
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' if !in_double => {
+                in_single = !in_single;
+                builder.quoted = true;
+            }
+            '"' if !in_single => {
+                in_double = !in_double;
+                builder.quoted = true;
+            }
+            '\\' if !in_single => match chars.next() {
+                Some(next) if !in_double || matches!(next, '$' | '`' | '"' | '\\') => {
+                    builder.push_literal(next)
+                }
+                Some(next) => {
+                    builder.push_literal('\\');
+                    builder.push_literal(next);
+                }
+                None => builder.push_literal('\\'),
+            },
+            '`' if !in_single => {
+                let (body, _) = read_substitution(&mut chars, true);
+                push_output(&mut fields, &mut builder, &substitute(&body), in_double);
+            }
+            '$' if !in_single && chars.peek() == Some(&'(') => {
+                chars.next();
+                let (body, _) = read_substitution(&mut chars, false);
+                push_output(&mut fields, &mut builder, &substitute(&body), in_double);
+            }
+            '$' if !in_single => {
+                let name = match chars.peek() {
+                    Some('{') => {
+                        chars.next();
+                        let mut name = String::new();
+                        for c in chars.by_ref() {
+                            if c == '}' {
+                                break;
+                            }
+                            name.push(c);
+                        }
+                        Some(name)
+                    }
+                    Some('?') => {
+                        chars.next();
+                        Some("?".to_string())
+                    }
+                    Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+                        let mut name = String::new();
+                        while let Some(&c) = chars.peek() {
+                            if !(c.is_ascii_alphanumeric() || c == '_') {
+                                break;
+                            }
+                            name.push(c);
+                            chars.next();
+                        }
+                        Some(name)
+                    }
+                    _ => None,
+                };
+                match name {
+                    Some(name) => builder.push_str_literal(&lookup(&name).unwrap_or_default()),
+                    None => builder.push_literal('$'),
+                }
+            }
+            '*' | '?' | '[' if !in_single && !in_double => builder.push_glob(c),
+            _ => builder.push_literal(c),
+        }
+    }
+
+    fields.push(builder);
+    fields.into_iter().flat_map(WordBuilder::finish).collect()
+}
+
+/// Add a command substitution's output to the word being built
+///
+/// Unquoted output is split into words at whitespace, as in sh; its glob
+/// characters stay literal.
+fn push_output(
+    fields: &mut Vec<WordBuilder>,
+    builder: &mut WordBuilder,
+    output: &str,
+    quoted: bool,
+) {
+    if quoted {
+        builder.push_str_literal(output);
+        return;
+    }
+    for (i, piece) in output.split(char::is_whitespace).enumerate() {
+        if i > 0 && (!builder.value.is_empty() || builder.quoted) {
+            fields.push(std::mem::take(builder));
+        }
+        builder.push_str_literal(piece);
+    }
+}
+
+/// Follow a `cd` made inside a backend shell, keeping `OLDPWD` and the
+/// directory stack up to date
+fn follow_directory(dir: &Path) {
+    if dir.as_os_str().is_empty() {
+        return;
+    }
+    let current = std::env::current_dir().ok();
+    if current.as_deref() == Some(dir) || current == dir.canonicalize().ok() {
+        return;
+    }
+    if let Err(e) = dir_stack().change_dir(dir) {
+        debug!("Could not follow the shell to {}: {}", dir.display(), e);
+    }
+}
+
+/// Keywords that only make sense to a real shell
+const SHELL_KEYWORDS: &[&str] = &[
+    "if", "then", "elif", "else", "fi", "for", "while", "until", "do", "done", "case", "esac",
+    "function", "select", "{", "}", "[[", "!",
+];
+
+/// Check whether a line uses syntax the native executor does not support
+pub fn needs_shell(line: &str) -> bool {
+    let chars: Vec<char> = line.chars().collect();
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let prev = if i > 0 { Some(chars[i - 1]) } else { None };
+
+        if in_single {
+            if c == '\'' {
+                in_single = false;
+            }
+            i += 1;
+            continue;
+        }
+
+        match c {
+            '\\' => {
+                i += 2;
+                continue;
+            }
+            '\'' if !in_double => in_single = true,
+            '"' => in_double = !in_double,
+            '$' if next == Some('(') && chars.get(i + 2) == Some(&'(') => return true,
+            // Command substitutions are run natively; skip over their body
+            '`' | '$' if c == '`' || next == Some('(') => {
+                let start = if c == '`' { i + 1 } else { i + 2 };
+                let mut rest = chars[start..].iter().copied();
+                let (_, closed) = read_substitution(&mut rest, c == '`');
+                if !closed {
+                    return true;
+                }
+                i = chars.len() - rest.count();
+                continue;
+            }
+            '$' if next == Some('{') => {
+                let rest: String = chars[i + 2..].iter().collect();
+                match rest.split_once('}') {
+                    Some((name, _)) if is_var_name(name) => {}
+                    _ => return true,
+                }
+            }
+            _ if in_double => {}
+            '(' | ')' => return true,
+            '<' if matches!(next, Some('<') | Some('(')) => return true,
+            '>' if next == Some('(') => return true,
+            '&' if next == Some('>') => return true,
+            '>' if next == Some('&') => {
+                let is_err_to_out = prev == Some('2') && chars.get(i + 2) == Some(&'1');
+                if !is_err_to_out {
+                    return true;
+                }
+            }
+            '0'..='9' if matches!(next, Some('>') | Some('<')) => {
+                let at_word_start = prev.is_none_or(|p| p.is_whitespace() || "|&;".contains(p));
+                if at_word_start && c != '2' {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    Pipeline::parse(line)
+        .segments
+        .iter()
+        .any(|segment| SHELL_KEYWORDS.contains(&segment.command.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestBuiltins {
+        calls: Vec<Vec<String>>,
+    }
+
+    impl Builtins for TestBuiltins {
+        fn is_builtin(&self, argv: &[String]) -> bool {
+            argv[0] == "greet"
+        }
+
+        fn run_builtin(&mut self, argv: &[String], out: &mut dyn Write) -> i32 {
+            self.calls.push(argv.to_vec());
+            writeln!(out, "hello {}", argv[1..].join(" ")).ok();
+            0
+        }
+    }
+
+    fn no_vars(_: &str) -> Option<String> {
+        None
+    }
+
+    fn no_subst(_: &str) -> String {
+        String::new()
+    }
+
+    #[test]
+    fn test_lower_chain() {
+        let list = CommandList::parse("make && make test || echo failed; ls &").unwrap();
+        let connectors: Vec<Connector> = list.jobs.iter().map(|(c, _)| *c).collect();
+        assert_eq!(
+            connectors,
+            vec![
+                Connector::Always,
+                Connector::IfSuccess,
+                Connector::IfFailure,
+                Connector::Always
+            ]
+        );
+        assert!(list.jobs[3].1.background);
+        assert_eq!(list.jobs[1].1.commands[0].words, vec!["make", "test"]);
+    }
+
+    #[test]
+    fn test_lower_redirects() {
+        let list = CommandList::parse("sort < in.txt > out.txt -r 2>&1 | head").unwrap();
+        let job = &list.jobs[0].1;
+        assert_eq!(job.commands.len(), 2);
+        assert_eq!(job.commands[0].words, vec!["sort", "-r"]);
+        assert_eq!(
+            job.commands[0].redirects,
+            vec![
+                Redirect::Stdin("in.txt".to_string()),
+                Redirect::Stdout("out.txt".to_string()),
+                Redirect::StderrToStdout
+            ]
+        );
+        assert_eq!(job.commands[1].words, vec!["head"]);
+    }
+
+    #[test]
+    fn test_job_source_keeps_redirects() {
+        let list = CommandList::parse("make 2>&1 > 'build log' | tee -a out < in").unwrap();
+        assert_eq!(
+            list.jobs[0].1.source(),
+            "make 2>&1 > 'build log' | tee -a out < in"
+        );
+    }
+
+    #[test]
+    fn test_lower_syntax_errors() {
+        assert!(CommandList::parse("ls >").is_err());
+        assert!(CommandList::parse("ls |").is_err());
+        assert!(CommandList::parse("ls &&").is_err());
+        assert!(CommandList::parse("ls;").is_ok());
+    }
+
+    #[test]
+    fn test_expand_word_quotes_and_escapes() {
+        assert_eq!(expand_word("'a b'", &no_vars, &mut no_subst), vec!["a b"]);
+        assert_eq!(
+            expand_word("\"a\\\"b\"", &no_vars, &mut no_subst),
+            vec!["a\"b"]
+        );
+        assert_eq!(expand_word("a\\ b", &no_vars, &mut no_subst), vec!["a b"]);
+        assert_eq!(expand_word("''", &no_vars, &mut no_subst), vec![""]);
+    }
+
+    #[test]
+    fn test_expand_word_variables() {
+        let lookup = |name: &str| match name {
+            "NAME" => Some("world".to_string()),
+            "?" => Some("3".to_string()),
+            _ => None,
+        };
+        assert_eq!(expand_word("$NAME", &lookup, &mut no_subst), vec!["world"]);
+        assert_eq!(
+            expand_word("${NAME}s", &lookup, &mut no_subst),
+            vec!["worlds"]
+        );
+        assert_eq!(
+            expand_word("\"$NAME!\"", &lookup, &mut no_subst),
+            vec!["world!"]
+        );
+        assert_eq!(
+            expand_word("'$NAME'", &lookup, &mut no_subst),
+            vec!["$NAME"]
+        );
+        assert_eq!(expand_word("$?", &lookup, &mut no_subst), vec!["3"]);
+        assert!(expand_word("$MISSING", &lookup, &mut no_subst).is_empty());
+        assert_eq!(
+            expand_word("\"$MISSING\"", &lookup, &mut no_subst),
+            vec![""]
+        );
+    }
+
+    #[test]
+    fn test_expand_word_substitution() {
+        let substitute = &mut |body: &str| match body {
+            "two" => "1  2".to_string(),
+            _ => String::new(),
+        };
+        assert_eq!(
+            expand_word("a$(two)b", &no_vars, substitute),
+            vec!["a1", "2b"]
+        );
+        assert_eq!(
+            expand_word("\"a`two`b\"", &no_vars, substitute),
+            vec!["a1  2b"]
+        );
+        assert!(expand_word("$(none)", &no_vars, substitute).is_empty());
+        assert_eq!(
+            expand_word("'$(two)'", &no_vars, substitute),
+            vec!["$(two)"]
+        );
+    }
+
+    #[test]
+    fn test_expand_word_tilde() {
+        let home = dirs::home_dir().unwrap().display().to_string();
+        assert_eq!(
+            expand_word("~", &no_vars, &mut no_subst),
+            vec![home.clone()]
+        );
+        assert_eq!(
+            expand_word("~/src", &no_vars, &mut no_subst),
+            vec![format!("{}/src", home)]
+        );
+        assert_eq!(expand_word("'~'", &no_vars, &mut no_subst), vec!["~"]);
+    }
+
+    #[test]
+    fn test_expand_word_glob() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.txt", "b.txt", ".hidden.txt"] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+        let base = dir.path().display().to_string();
+
+        let matches = expand_word(&format!("{}/*.txt", base), &no_vars, &mut no_subst);
+        assert_eq!(
+            matches,
+            vec![format!("{}/a.txt", base), format!("{}/b.txt", base)]
+        );
+
+        // Quoted and unmatched globs stay literal
+        let quoted = format!("'{}/*.txt'", base);
+        assert_eq!(
+            expand_word(&quoted, &no_vars, &mut no_subst),
+            vec![format!("{}/*.txt", base)]
+        );
+        let missing = format!("{}/*.rs", base);
+        assert_eq!(
+            expand_word(&missing, &no_vars, &mut no_subst),
+            vec![missing.clone()]
+        );
+    }
+
+    #[test]
+    fn test_needs_shell() {
+        assert!(!needs_shell("ls -la | grep foo > out.txt 2>&1"));
+        assert!(!needs_shell("echo '$(not a subshell)' \"${HOME}\""));
+        assert!(!needs_shell("export X=$(date | cut -c1-3) Y=`id -u`"));
+        assert!(!needs_shell("echo \"$(echo ')')\""));
+        assert!(needs_shell("echo $(date"));
+        assert!(needs_shell("echo $((1 + 2))"));
+        assert!(needs_shell("for f in *; do echo $f; done"));
+        assert!(needs_shell("(cd /tmp && ls)"));
+        assert!(needs_shell("cat <<EOF"));
+        assert!(needs_shell("ls 1>&2"));
+        assert!(needs_shell("ls &> out"));
+        assert!(needs_shell("echo ${HOME:-/root}"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_chains() {
+        let mut executor = Executor::new();
+        let mut builtins = TestBuiltins { calls: Vec::new() };
+
+        let outcome = executor.execute("false && greet skipped", &mut builtins);
+        assert_eq!(outcome.status, 1);
+        assert_eq!(outcome.segments.len(), 1);
+
+        let outcome = executor.execute("false || greet there; true", &mut builtins);
+        assert_eq!(outcome.status, 0);
+        assert_eq!(outcome.segments.len(), 3);
+        assert_eq!(outcome.segments[0].status, 1);
+        assert_eq!(builtins.calls, vec![vec!["greet", "there"]]);
+
+        let outcome = executor.execute("definitely-not-a-command-xyz", &mut builtins);
+        assert_eq!(outcome.status, STATUS_NOT_FOUND);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_session_variables() {
+        let mut executor = Executor::new();
+        let mut builtins = TestBuiltins { calls: Vec::new() };
+
+        executor.execute("SC_TEST_GREETING=there", &mut builtins);
+        executor.execute("greet $SC_TEST_GREETING", &mut builtins);
+        assert_eq!(builtins.calls, vec![vec!["greet", "there"]]);
+
+        // Shell variables stay private until exported
+        let check = "sh -c 'test \"$SC_TEST_GREETING\" = there'";
+        assert_eq!(executor.execute(check, &mut builtins).status, 1);
+        session_env().export("SC_TEST_GREETING", None);
+        assert_eq!(executor.execute(check, &mut builtins).status, 0);
+        session_env().unset("SC_TEST_GREETING");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_pipes_and_redirects() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.txt");
+        let out_str = out.display().to_string();
+        let mut executor = Executor::new();
+        let mut builtins = TestBuiltins { calls: Vec::new() };
+
+        let outcome = executor.execute(
+            &format!("printf 'b\\na\\n' | sort > {}", out_str),
+            &mut builtins,
+        );
+        assert!(outcome.success());
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "a\nb\n");
+
+        executor.execute(
+            &format!("greet you | tr a-z A-Z >> {}", out_str),
+            &mut builtins,
+        );
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "a\nb\nHELLO YOU\n");
+
+        let outcome = executor.execute(
+            &format!("wc -l < {} > {}.count", out_str, out_str),
+            &mut builtins,
+        );
+        assert!(outcome.success());
+        let count = std::fs::read_to_string(format!("{}.count", out_str)).unwrap();
+        assert_eq!(count.trim(), "3");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shell_backend() {
+        let mut executor = Executor::new();
+        let mut builtins = TestBuiltins { calls: Vec::new() };
+        executor.set_backend(Backend::parse("sh").unwrap(), true);
+
+        // Functions survive between lines in the persistent shell
+        let outcome = executor.execute("sc_test_fn() { return 4; }", &mut builtins);
+        assert_eq!(outcome.status, 0);
+        assert_eq!(executor.execute("sc_test_fn", &mut builtins).status, 4);
+        assert_eq!(executor.last_status(), 4);
+        assert_eq!(executor.execute("[ $? -eq 4 ]", &mut builtins).status, 0);
+
+        // Builtins still run in sc
+        executor.execute("greet backend", &mut builtins);
+        assert_eq!(builtins.calls, vec![vec!["greet", "backend"]]);
+
+        // A line mixing both runs each job in its place
+        let outcome = executor.execute("greet mixed && sc_test_fn", &mut builtins);
+        assert_eq!(outcome.status, 4);
+        assert_eq!(builtins.calls[1], vec!["greet", "mixed"]);
+
+        // Plain `NAME=value` variables reach the shell, without being exported
+        executor.execute("SC_TEST_PLAIN=x", &mut builtins);
+        let check = r#"sc_test_var() { [ "$SC_TEST_PLAIN" = x ] && [ -z "$(env | grep SC_TEST_PLAIN)" ]; }"#;
+        executor.execute(check, &mut builtins);
+        assert_eq!(executor.execute("sc_test_var", &mut builtins).status, 0);
+
+        // Without persistence each line gets a fresh shell
+        executor.set_backend(Backend::parse("sh").unwrap(), false);
+        assert_eq!(
+            executor.execute("sc_test_fn", &mut builtins).status,
+            STATUS_NOT_FOUND
+        );
+        let check = "if [ $? -eq 127 ]; then true; else false; fi";
+        assert_eq!(executor.execute(check, &mut builtins).status, 0);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("5"), Some(Duration::from_secs(5)));
+        assert_eq!(parse_duration("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("10x"), None);
+        assert_eq!(parse_duration("-1"), None);
+        assert_eq!(parse_duration("later"), None);
+    }
+
+    #[test]
+    fn test_timeouts_for_job() {
+        let timeouts = Timeouts {
+            default: Some(Duration::from_secs(30)),
+            commands: HashMap::from([
+                ("cargo".to_string(), Duration::from_secs(600)),
+                ("cargo fmt".to_string(), Duration::from_secs(5)),
+                ("vim".to_string(), Duration::ZERO),
+            ]),
+        };
+        let job_timeout =
+            |line: &str| timeouts.for_job(&CommandList::parse(line).unwrap().jobs[0].1);
+
+        assert_eq!(job_timeout("cargo build"), Some(Duration::from_secs(600)));
+        assert_eq!(job_timeout("cargo fmt --all"), Some(Duration::from_secs(5)));
+        assert_eq!(job_timeout("ls | grep x"), Some(Duration::from_secs(30)));
+        assert_eq!(job_timeout("vim notes.txt"), None);
+    }
+
+    #[test]
+    fn test_strip_timeout_prefix() {
+        let job = CommandList::parse("timeout 2m make test").unwrap().jobs[0].1.clone();
+        let (stripped, timeout) = strip_timeout_prefix(&job).unwrap();
+        assert_eq!(stripped.text(), "make test");
+        assert_eq!(timeout, Duration::from_secs(120));
+
+        // Options are left for the external command
+        let job = CommandList::parse("timeout -s KILL 5 make").unwrap().jobs[0].1.clone();
+        assert!(strip_timeout_prefix(&job).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_time_prefix() {
+        let mut executor = Executor::new();
+        let mut builtins = TestBuiltins { calls: Vec::new() };
+
+        let outcome = executor.execute("time sh -c 'exit 3'", &mut builtins);
+        assert_eq!(outcome.status, 3);
+        assert_eq!(outcome.segments[0].command, "sh -c 'exit 3'");
+
+        executor.execute("time greet you | cat", &mut builtins);
+        assert_eq!(builtins.calls, vec![vec!["greet", "you"]]);
+
+        // Options are left for the external command
+        let job = CommandList::parse("time -v make").unwrap().jobs[0].1.clone();
+        assert!(strip_time_prefix(&job, &builtins).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_times_out() {
+        let mut executor = Executor::new();
+        let mut builtins = TestBuiltins { calls: Vec::new() };
+
+        let start = std::time::Instant::now();
+        let outcome = executor.execute("timeout 0.2 sleep 5", &mut builtins);
+        assert!(start.elapsed() < Duration::from_secs(3));
+        assert_eq!(outcome.status, jobs::STATUS_TIMED_OUT);
+        assert!(matches!(outcome.error, Some(CommandError::Timeout(1))));
+        assert_eq!(executor.last_status(), jobs::STATUS_TIMED_OUT);
+
+        executor.set_timeouts(Timeouts {
+            default: Some(Duration::from_millis(200)),
+            commands: HashMap::new(),
+        });
+        let outcome = executor.execute("sleep 5", &mut builtins);
+        assert_eq!(outcome.status, jobs::STATUS_TIMED_OUT);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_command_substitution() {
+        let mut executor = Executor::new();
+        let mut builtins = TestBuiltins { calls: Vec::new() };
+
+        // Builtins take substituted words, and their output can be substituted
+        executor.execute("greet $(printf 'a  b') \"$(printf 'x  y')\"", &mut builtins);
+        executor.execute("greet $(greet you | tr h j)", &mut builtins);
+        assert_eq!(
+            builtins.calls,
+            vec![
+                vec!["greet", "a", "b", "x  y"],
+                vec!["greet", "you"],
+                vec!["greet", "jello", "you"],
+            ]
+        );
+
+        // Assignments stay in the session instead of a throwaway shell
+        executor.execute("SC_TEST_SUBST=$(echo hi)", &mut builtins);
+        assert_eq!(session_env().get("SC_TEST_SUBST").as_deref(), Some("hi"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stopped_job_killed_without_job_control() {
+        let mut executor = Executor::new();
+        let mut builtins = TestBuiltins { calls: Vec::new() };
+
+        // Nothing could `fg` it, so it is not left behind stopped
+        let outcome = executor.execute("sh -c 'kill -STOP $$'", &mut builtins);
+        assert_eq!(outcome.status, jobs::STATUS_STOPPED);
+        assert!(executor.jobs.is_empty());
+    }
+}
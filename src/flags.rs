@@ -0,0 +1,433 @@
+//! Flags typed on a command line and the constraints between them
+//!
+//! The completer uses this to hide flags that no longer fit (a second
+//! `--force`, or `--soft` once `--hard` is there), and the highlighter to
+//! mark flags that break `conflicts_with`, `requires` or `repeatable` before
+//! the line is run.
+
+use crate::command_def::{CommandSpec, FlagSpec};
+
+/// One flag found among the words of a command line
+#[derive(Debug, Clone)]
+pub struct FlagUse<'a> {
+    pub flag: &'a FlagSpec,
+    /// Index of the word it was found in
+    pub word: usize,
+    /// Given as `--no-<long>`
+    pub negated: bool,
+}
+
+/// A broken constraint, reported on the word of the offending flag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub word: usize,
+    pub message: String,
+}
+
+/// `--long`, or `-s` for flags without a long name
+pub fn label(flag: &FlagSpec) -> String {
+    match (&flag.long, flag.short) {
+        (Some(long), _) => format!("--{}", long),
+        (None, Some(short)) => format!("-{}", short),
+        (None, None) => "-".to_string(),
+    }
+}
+
+/// Where a flag's value was given
+#[derive(Debug, Clone)]
+pub struct FlagValue<'a> {
+    pub flag: &'a FlagSpec,
+    pub word: usize,
+    /// Byte offset of the value in the word (after `--output=` or `-o`)
+    pub offset: usize,
+}
+
+/// What the words after a command path are
+#[derive(Debug, Clone, Default)]
+pub struct Scan<'a> {
+    pub flags: Vec<FlagUse<'a>>,
+    pub values: Vec<FlagValue<'a>>,
+    /// Indices of the positional arguments
+    pub positionals: Vec<usize>,
+    /// Indices of words that look like flags the spec doesn't have
+    pub unknown: Vec<usize>,
+    /// Flag still waiting for its value in the next word
+    pub pending: Option<&'a FlagSpec>,
+}
+
+/// Sort `words`, the words after the command path of `spec`, into flags,
+/// flag values and positional arguments
+///
+/// Values of flags that take one are skipped, whether attached
+/// (`--output=json`, `-ojson`) or passed as the next word. Everything after
+/// `--` is positional, and so are negative numbers. Unknown flags are
+/// neither flags nor positionals.
+pub fn scan<'a>(spec: &'a CommandSpec, words: &[&str]) -> Scan<'a> {
+    let mut scan = Scan::default();
+    let mut rest_positional = false;
+    for (index, word) in words.iter().enumerate() {
+        if let Some(flag) = scan.pending.take() {
+            scan.values.push(FlagValue {
+                flag,
+                word: index,
+                offset: 0,
+            });
+            continue;
+        }
+        let number = word.len() > 1 && word.parse::<f64>().is_ok();
+        if rest_positional || *word == "-" || number || !word.starts_with('-') {
+            scan.positionals.push(index);
+            continue;
+        }
+        if *word == "--" {
+            rest_positional = true;
+            continue;
+        }
+
+        // `-name` is a long flag for tools like find that spell them that way
+        let long = word.strip_prefix("--").or_else(|| {
+            let name = &word[1..];
+            let is_long = |f: &FlagSpec| f.long.as_deref() == Some(name);
+            (name.len() > 1 && spec.flags.iter().any(is_long)).then_some(name)
+        });
+        if let Some(long) = long {
+            let (name, value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (long, None),
+            };
+            let found = spec
+                .flags
+                .iter()
+                .find(|f| f.long.as_deref() == Some(name))
+                .map(|f| (f, false))
+                .or_else(|| {
+                    let name = name.strip_prefix("no-")?;
+                    spec.flags
+                        .iter()
+                        .find(|f| f.negatable && f.long.as_deref() == Some(name))
+                        .map(|f| (f, true))
+                });
+            let Some((flag, negated)) = found else {
+                scan.unknown.push(index);
+                continue;
+            };
+            if flag.takes_value && !negated {
+                if let Some(value) = value {
+                    scan.values.push(FlagValue {
+                        flag,
+                        word: index,
+                        offset: word.len() - value.len(),
+                    });
+                } else if !flag.equals {
+                    scan.pending = Some(flag);
+                }
+            }
+            scan.flags.push(FlagUse {
+                flag,
+                word: index,
+                negated,
+            });
+        } else {
+            let chain = &word[1..];
+            for (offset, c) in chain.char_indices() {
+                let Some(flag) = spec.flags.iter().find(|f| f.short == Some(c)) else {
+                    if scan.unknown.last() != Some(&index) {
+                        scan.unknown.push(index);
+                    }
+                    continue;
+                };
+                scan.flags.push(FlagUse {
+                    flag,
+                    word: index,
+                    negated: false,
+                });
+                if flag.takes_value {
+                    // The rest of the word, if any, is the value
+                    let start = 1 + offset + c.len_utf8();
+                    if start < word.len() {
+                        scan.values.push(FlagValue {
+                            flag,
+                            word: index,
+                            offset: start,
+                        });
+                    } else if !flag.equals {
+                        scan.pending = Some(flag);
+                    }
+                    break;
+                }
+            }
+        }
+    }
+    scan
+}
+
+/// The flag whose value `word` is, and where in `word` the value starts
+///
+/// That is the word after a flag waiting for its value (`pending` from
+/// [`scan`] of the words before), or a value attached to the flag in the word
+/// itself: `--output=js` or `-ojs`.
+pub fn value_at<'a>(
+    spec: &'a CommandSpec,
+    pending: Option<&'a FlagSpec>,
+    word: &str,
+) -> Option<(&'a FlagSpec, usize)> {
+    if let Some(flag) = pending {
+        return Some((flag, 0));
+    }
+    if let Some(long) = word.strip_prefix("--") {
+        let (name, _) = long.split_once('=')?;
+        let flag = spec
+            .flags
+            .iter()
+            .find(|f| f.takes_value && f.long.as_deref() == Some(name))?;
+        return Some((flag, name.len() + 3));
+    }
+
+    let chain = word.strip_prefix('-')?;
+    for (offset, c) in chain.char_indices() {
+        let Some(flag) = spec.flags.iter().find(|f| f.short == Some(c)) else {
+            continue;
+        };
+        if flag.takes_value {
+            let start = 1 + offset + c.len_utf8();
+            return (start < word.len()).then_some((flag, start));
+        }
+    }
+    None
+}
+
+/// Flags of `spec` among `words`, the words after its command path
+pub fn parse<'a>(spec: &'a CommandSpec, words: &[&str]) -> Vec<FlagUse<'a>> {
+    scan(spec, words).flags
+}
+
+/// Whether `flag` can still be added to a line that already has `uses`
+pub fn is_available(flag: &FlagSpec, uses: &[FlagUse]) -> bool {
+    let repeated = !flag.repeatable && uses.iter().any(|u| std::ptr::eq(u.flag, flag));
+    let conflicting = uses
+        .iter()
+        .filter(|u| !u.negated)
+        .any(|u| conflicts(flag, u.flag) || conflicts(u.flag, flag));
+    !repeated && !conflicting
+}
+
+fn conflicts(flag: &FlagSpec, other: &FlagSpec) -> bool {
+    flag.conflicts_with.iter().any(|name| other.is_named(name))
+}
+
+/// Every constraint the flags in `uses` break
+pub fn violations(uses: &[FlagUse]) -> Vec<Violation> {
+    let mut found = Vec::new();
+    for (i, used) in uses.iter().enumerate() {
+        let flag = used.flag;
+        let again = uses[..i]
+            .iter()
+            .any(|u| std::ptr::eq(u.flag, flag) && u.negated == used.negated);
+        if !flag.repeatable && again {
+            found.push(Violation {
+                word: used.word,
+                message: format!("{} given more than once", label(flag)),
+            });
+        }
+        if used.negated {
+            continue;
+        }
+
+        let given = || uses.iter().filter(|u| !u.negated);
+        // A pair that declares the conflict both ways is reported once
+        let reported = |j: usize, other: &FlagUse| j < i && conflicts(other.flag, flag);
+        for (_, other) in uses
+            .iter()
+            .enumerate()
+            .filter(|(j, u)| !u.negated && conflicts(flag, u.flag) && !reported(*j, u))
+        {
+            let message = format!("{} conflicts with {}", label(flag), label(other.flag));
+            for word in [used.word, other.word] {
+                found.push(Violation {
+                    word,
+                    message: message.clone(),
+                });
+            }
+        }
+        for name in &flag.requires {
+            if !given().any(|u| u.flag.is_named(name)) {
+                let required = if name.chars().count() == 1 {
+                    format!("-{}", name)
+                } else {
+                    format!("--{}", name.trim_start_matches('-'))
+                };
+                found.push(Violation {
+                    word: used.word,
+                    message: format!("{} requires {}", label(flag), required),
+                });
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> CommandSpec {
+        serde_yaml::from_str(
+            r#"
+name: reset
+description: Reset
+flags:
+  - long: hard
+    description: Discard changes
+    conflicts_with: [soft]
+  - long: soft
+    description: Keep changes
+    conflicts_with: [hard]
+  - long: verbose
+    short: v
+    description: More output
+    repeatable: true
+  - long: verify
+    description: Run hooks
+    negatable: true
+  - long: output
+    short: o
+    description: Output format
+    takes_value: true
+  - long: sign
+    description: Sign
+    requires: [key]
+  - long: key
+    description: Signing key
+    takes_value: true
+    equals: true
+"#,
+        )
+        .unwrap()
+    }
+
+    fn names(uses: &[FlagUse]) -> Vec<String> {
+        uses.iter()
+            .map(|u| {
+                let name = label(u.flag);
+                if u.negated {
+                    format!("{} (no)", name)
+                } else {
+                    name
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_skips_values() {
+        let spec = spec();
+        let uses = parse(
+            &spec,
+            &[
+                "-o",
+                "--hard",
+                "-vv",
+                "--no-verify",
+                "file",
+                "--key=--soft",
+                "--",
+                "--sign",
+            ],
+        );
+        assert_eq!(
+            names(&uses),
+            [
+                "--output",
+                "--verbose",
+                "--verbose",
+                "--verify (no)",
+                "--key"
+            ]
+        );
+        assert_eq!(uses[3].word, 3);
+
+        let uses = parse(&spec, &["-vojson", "--soft"]);
+        assert_eq!(names(&uses), ["--verbose", "--output", "--soft"]);
+    }
+
+    #[test]
+    fn test_scan_positionals() {
+        let spec = spec();
+        let words = scan(&spec, &["a", "-o", "json", "-", "--hard", "b", "--", "-v"]);
+        assert_eq!(words.positionals, [0, 3, 5, 7]);
+        assert!(words.pending.is_none());
+
+        let words = scan(&spec, &["-x", "--output=json", "-5", "--nope", "-vz", "--"]);
+        assert_eq!(words.positionals, [2]);
+        assert_eq!(words.unknown, [0, 3, 4]);
+        assert_eq!(words.values.len(), 1);
+        assert_eq!(&"--output=json"[words.values[0].offset..], "json");
+
+        let words = scan(&spec, &["-output", "yaml", "-hard"]);
+        assert!(words.unknown.is_empty());
+        assert_eq!(words.values[0].word, 1);
+        assert_eq!(words.flags.len(), 2);
+
+        let words = scan(&spec, &["a", "--output"]);
+        assert_eq!(words.positionals, [0]);
+        assert_eq!(
+            words.pending.and_then(|f| f.long.as_deref()),
+            Some("output")
+        );
+    }
+
+    #[test]
+    fn test_value_at() {
+        let spec = spec();
+        let at = |pending, word| value_at(&spec, pending, word).map(|(f, i)| (label(f), i));
+
+        let output = spec.flags.iter().find(|f| f.is_named("output"));
+        assert_eq!(at(output, "js"), Some(("--output".to_string(), 0)));
+        assert_eq!(at(None, "--output=js"), Some(("--output".to_string(), 9)));
+        assert_eq!(at(None, "--key="), Some(("--key".to_string(), 6)));
+        assert_eq!(at(None, "-vojs"), Some(("--output".to_string(), 3)));
+        assert_eq!(at(None, "-o"), None);
+        assert_eq!(at(None, "--hard=x"), None);
+        assert_eq!(at(None, "js"), None);
+    }
+
+    #[test]
+    fn test_violations() {
+        let spec = spec();
+        let uses = parse(
+            &spec,
+            &[
+                "--hard", "--soft", "-v", "-v", "--sign", "--output", "x", "-o", "y",
+            ],
+        );
+        let found = violations(&uses);
+        let messages: Vec<(usize, &str)> =
+            found.iter().map(|v| (v.word, v.message.as_str())).collect();
+        assert_eq!(
+            messages,
+            [
+                (0, "--hard conflicts with --soft"),
+                (1, "--hard conflicts with --soft"),
+                (4, "--sign requires --key"),
+                (7, "--output given more than once"),
+            ]
+        );
+
+        // Turning a negatable flag back off is not a repeat
+        let uses = parse(&spec, &["--sign", "--key=abc", "--verify", "--no-verify"]);
+        assert!(violations(&uses).is_empty());
+    }
+
+    #[test]
+    fn test_is_available() {
+        let spec = spec();
+        let flag = |name: &str| spec.flags.iter().find(|f| f.is_named(name)).unwrap();
+        let uses = parse(&spec, &["--soft", "-v", "--output", "json"]);
+
+        assert!(!is_available(flag("hard"), &uses));
+        assert!(!is_available(flag("soft"), &uses));
+        assert!(!is_available(flag("output"), &uses));
+        assert!(is_available(flag("v"), &uses));
+        assert!(is_available(flag("sign"), &uses));
+    }
+}
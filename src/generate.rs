@@ -0,0 +1,617 @@
+//! Draft definitions from `--help` output and man pages (`sc defs generate`)
+//!
+//! Help text is split into sections by its headers (`Options:`, `Commands:`,
+//! `OPTIONS`, ...). Option lines in sections that list options (or at the top
+//! of header-less help) give flags, with wrapped descriptions joined; lines in
+//! a commands section give subcommands, and each subcommand's own `--help` (or
+//! `-h`) is parsed for its flags.
+//! Value-taking flags are recognised from `--opt=VALUE`, `--opt <VALUE>`,
+//! `-o FILE` and `--opt[=WHEN]`; value names such as `FILE` or `{a,b}` become
+//! path and choice types. The result is a starting point to edit by hand.
+
+use crate::command_def::{ArgumentType, CommandSpec, FlagSpec, I18nString, PathFilterConfig};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Help commands that take longer than this are abandoned
+const HELP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where the help text came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HelpSource {
+    /// `<cmd> --help`, falling back to the man page
+    Auto,
+    /// `man -P cat <cmd>` only
+    Man,
+}
+
+/// Generate a draft spec for `cmd` from its help output
+pub fn generate(cmd: &str, source: HelpSource) -> anyhow::Result<CommandSpec> {
+    let text = match source {
+        HelpSource::Auto => help_output(&[cmd]).or_else(|| man_page(cmd)),
+        HelpSource::Man => man_page(cmd),
+    };
+    let Some(text) = text else {
+        anyhow::bail!("No --help output or man page found for {}", cmd);
+    };
+
+    let help = parse_help(&text);
+    let mut spec = help.to_spec(cmd);
+    if source == HelpSource::Auto {
+        for sub in &mut spec.subcommands {
+            if let Some(text) = help_output(&[cmd, &sub.name]) {
+                let sub_help = parse_help(&text);
+                // Some tools print the top-level help again for `<sub> --help`
+                if sub_help.subcommands != help.subcommands {
+                    sub.flags = sub_help.flags;
+                }
+            }
+        }
+    }
+    Ok(spec)
+}
+
+/// Render a spec as definition YAML
+pub fn to_yaml(spec: &CommandSpec) -> anyhow::Result<String> {
+    Ok(serde_yaml::to_string(spec)?)
+}
+
+/// Run `<args> --help` (or `-h`, which git subcommands answer without a pager)
+fn help_output(args: &[&str]) -> Option<String> {
+    ["--help", "-h"].iter().find_map(|flag| {
+        let mut command = Command::new(args[0]);
+        command.args(&args[1..]).arg(flag);
+        run_captured(command).filter(|text| parse_help(text).has_content())
+    })
+}
+
+/// Read a man page as plain text
+fn man_page(cmd: &str) -> Option<String> {
+    let mut command = Command::new("man");
+    command.args(["-P", "cat", cmd]).env("MANWIDTH", "100");
+    run_captured(command).map(|text| strip_overstrike(&text))
+}
+
+/// Run a command with a timeout, returning stdout (or stderr if stdout is empty)
+fn run_captured(mut command: Command) -> Option<String> {
+    let mut child = command
+        .env("PAGER", "cat")
+        .env("MANPAGER", "cat")
+        .env("GIT_PAGER", "cat")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+
+    let mut stdout = child.stdout.take()?;
+    let mut stderr = child.stderr.take()?;
+    let out_reader = std::thread::spawn(move || {
+        let mut buf = String::new();
+        let _ = stdout.read_to_string(&mut buf);
+        buf
+    });
+    let err_reader = std::thread::spawn(move || {
+        let mut buf = String::new();
+        let _ = stderr.read_to_string(&mut buf);
+        buf
+    });
+
+    let deadline = Instant::now() + HELP_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+
+    let out = out_reader.join().unwrap_or_default();
+    let err = err_reader.join().unwrap_or_default();
+    let text = if out.trim().is_empty() { err } else { out };
+    (!text.trim().is_empty()).then_some(text)
+}
+
+/// Remove the backspace overstrike man uses for bold and underline
+fn strip_overstrike(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\u{8}' {
+            out.pop();
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Flags, subcommands and description found in a help text
+#[derive(Debug, Default)]
+pub struct ParsedHelp {
+    pub description: Option<String>,
+    pub flags: Vec<FlagSpec>,
+    pub subcommands: Vec<(String, String)>,
+}
+
+impl ParsedHelp {
+    fn has_content(&self) -> bool {
+        !self.flags.is_empty() || !self.subcommands.is_empty()
+    }
+
+    fn to_spec(&self, name: &str) -> CommandSpec {
+        let mut spec = CommandSpec::new(name, "");
+        spec.description = I18nString::english(self.description.as_deref().unwrap_or(name));
+        spec.flags = self.flags.clone();
+        spec.subcommands = self
+            .subcommands
+            .iter()
+            .map(|(sub, description)| {
+                let mut sub_spec = CommandSpec::new(sub, "");
+                sub_spec.description = I18nString::english(description);
+                sub_spec
+            })
+            .collect();
+        spec
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Top,
+    Usage,
+    Name,
+    Commands,
+    Options,
+    Other,
+}
+
+static HEADER: Lazy<Regex> = Lazy::new(|| Regex::new(r"^([A-Za-z][\w /-]*):\s*$").unwrap());
+static MAN_HEADER: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[A-Z][A-Z /-]+$").unwrap());
+static OPTION_NAME: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(-{1,2})(\[no-\])?([A-Za-z0-9?@][\w.-]*)(.*)$").unwrap());
+static SUBCOMMAND: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s+([a-z][\w:-]*)\*?(?:,\s*[\w-]+)*(?:\s{2,}(\S.*))?$").unwrap()
+});
+static POSSIBLE_VALUES: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\[possible values: ([^\]]+)\]").unwrap());
+static GAP: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s{2,}|\t").unwrap());
+
+/// Parse `--help` output or a man page
+pub fn parse_help(text: &str) -> ParsedHelp {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut help = ParsedHelp::default();
+    let mut section = Section::Top;
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim_end();
+        i += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+
+        if indent == 0 {
+            let lower = line.to_lowercase();
+            if lower.starts_with("usage:") {
+                section = Section::Usage;
+                continue;
+            }
+            if HEADER.is_match(line) || MAN_HEADER.is_match(line) {
+                section = if lower.trim() == "name" {
+                    Section::Name
+                } else if lower.contains("synopsis") {
+                    Section::Usage
+                } else if lower.contains("command") {
+                    Section::Commands
+                } else if ["option", "flag", "description"]
+                    .iter()
+                    .any(|word| lower.contains(word))
+                    || lists_options(&lines[i..])
+                {
+                    // GNU man pages list options under DESCRIPTION, and GNU
+                    // help under headings like "Startup:"
+                    Section::Options
+                } else {
+                    Section::Other
+                };
+                continue;
+            }
+            // Header-less help lists options at the top level, after the
+            // description and any notes
+            if matches!(section, Section::Top | Section::Usage) {
+                if help.description.is_none() {
+                    help.description = Some(line.trim().to_string());
+                }
+                section = Section::Top;
+            }
+            continue;
+        }
+
+        let trimmed = line.trim_start();
+        if section == Section::Name && help.description.is_none() {
+            help.description = trimmed
+                .split_once(" - ")
+                .map(|(_, d)| d.trim().to_string())
+                .or_else(|| Some(trimmed.to_string()));
+            continue;
+        }
+        // Usage lines list options too, but without descriptions
+        if section == Section::Usage && !trimmed.starts_with('-') {
+            continue;
+        }
+        // Indented subheadings ("Each range is one of:", tar's " Main
+        // operation mode:") decide whether options follow
+        if matches!(section, Section::Top | Section::Options | Section::Other)
+            && !trimmed.starts_with('-')
+            && trimmed.ends_with(':')
+        {
+            section = if lists_options(&lines[i..]) {
+                Section::Options
+            } else {
+                Section::Other
+            };
+            continue;
+        }
+
+        // A subcommand's description may sit on the following line
+        let next_description = || {
+            lines.get(i).and_then(|next| {
+                let next_indent = next.len() - next.trim_start().len();
+                (next_indent > indent && !next.trim_start().starts_with('-'))
+                    .then(|| next.trim().to_string())
+            })
+        };
+
+        if trimmed.starts_with('-') {
+            // Options may resume after a blank line ends a list of values
+            // (tar's "FORMAT is one of the following:")
+            if section == Section::Other && lines[i - 2].trim().is_empty() {
+                section = Section::Options;
+            }
+            if !matches!(section, Section::Top | Section::Usage | Section::Options) {
+                continue;
+            }
+            let mut parts = GAP.splitn(trimmed, 2);
+            let names = parts.next().unwrap_or_default();
+            // The description may start on, or wrap onto, deeper-indented lines
+            let mut description: Vec<&str> = parts.next().map(str::trim).into_iter().collect();
+            while let Some(next) = lines.get(i) {
+                let next_indent = next.len() - next.trim_start().len();
+                let next = next.trim();
+                if next.is_empty() || next_indent <= indent || next.starts_with('-') {
+                    break;
+                }
+                description.push(next);
+                i += 1;
+            }
+            let description = description.join(" ");
+            if let Some(flag) = parse_option(names, &description) {
+                let duplicate = help.flags.iter().any(|f| {
+                    (f.long.is_some() && f.long == flag.long)
+                        || (f.short.is_some() && f.short == flag.short)
+                });
+                if !duplicate {
+                    help.flags.push(flag);
+                }
+            }
+            continue;
+        }
+
+        if section == Section::Commands {
+            if let Some(caps) = SUBCOMMAND.captures(line) {
+                let name = caps[1].to_string();
+                let description = caps
+                    .get(2)
+                    .map(|d| d.as_str().trim().to_string())
+                    .or_else(next_description);
+                if let Some(description) = description {
+                    if !help.subcommands.iter().any(|(n, _)| *n == name) {
+                        help.subcommands.push((name, description));
+                    }
+                }
+            }
+        }
+    }
+
+    help
+}
+
+/// Whether the first entry of a section is an option
+fn lists_options(lines: &[&str]) -> bool {
+    lines
+        .iter()
+        .find(|line| !line.trim().is_empty())
+        .is_some_and(|line| {
+            line.starts_with(char::is_whitespace) && line.trim_start().starts_with('-')
+        })
+}
+
+/// Parse the names part of an option line (`-o, --output=FILE`)
+fn parse_option(names: &str, description: &str) -> Option<FlagSpec> {
+    let mut long = None;
+    let mut short = None;
+    let mut value = None;
+
+    for alternative in split_alternatives(names) {
+        let Some(caps) = OPTION_NAME.captures(alternative) else {
+            continue;
+        };
+        // `--verbose...` marks a repeatable flag
+        let name = caps[3].trim_end_matches('.');
+        let rest = caps[4].trim();
+        if !rest.is_empty() {
+            value = Some(rest.to_string());
+        }
+        match (&caps[1], name.chars().count()) {
+            ("--", _) => long = long.or(Some(name.to_string())),
+            ("-", 1) => short = short.or(name.chars().next()),
+            // Single-dash long options (`-name`) cannot be represented
+            _ => {}
+        }
+    }
+
+    if long.is_none() && short.is_none() {
+        return None;
+    }
+
+    let value_type = POSSIBLE_VALUES
+        .captures(description)
+        .map(|caps| ArgumentType::Choice {
+            values: caps[1].split(',').map(|v| v.trim().to_string()).collect(),
+        })
+        .or_else(|| value.as_deref().and_then(value_type));
+    Some(FlagSpec {
+        long,
+        short,
+        description: I18nString::english(description),
+        takes_value: value.is_some(),
+        value_type,
+        ..Default::default()
+    })
+}
+
+/// Split `-o, --output={a,b}` at the commas that start another option
+fn split_alternatives(names: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (i, c) in names.char_indices() {
+        if c == ',' && names[i + 1..].trim_start().starts_with('-') {
+            parts.push(names[start..i].trim());
+            start = i + 1;
+        }
+    }
+    parts.push(names[start..].trim());
+    parts
+}
+
+/// Guess a value type from its placeholder (`FILE`, `<dir>`, `{a,b}`)
+fn value_type(placeholder: &str) -> Option<ArgumentType> {
+    let name = placeholder
+        .trim_start_matches(['[', '='])
+        .trim_start_matches('=')
+        .trim_end_matches(']')
+        .trim_matches(['<', '>'])
+        .trim();
+
+    let choices = name
+        .strip_prefix('{')
+        .and_then(|n| n.strip_suffix('}'))
+        .map(|n| n.split(','))
+        .or_else(|| name.contains('|').then(|| name.split('|')));
+    if let Some(choices) = choices {
+        let values: Vec<String> = choices
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .collect();
+        // Upper-case alternatives are placeholders (`<KEY=VALUE|PATH>`), not literals
+        let literal = values
+            .iter()
+            .all(|v| v.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'));
+        if values.len() > 1 && literal {
+            return Some(ArgumentType::Choice { values });
+        }
+    }
+
+    let upper = name.to_uppercase();
+    if upper.contains("DIR") {
+        Some(ArgumentType::Path {
+            filter: PathFilterConfig {
+                dirs_only: true,
+                ..Default::default()
+            },
+        })
+    } else if upper.contains("FILE") || upper.contains("PATH") {
+        Some(ArgumentType::Path {
+            filter: PathFilterConfig::default(),
+        })
+    } else if ["NUM", "COUNT", "SECS", "SECONDS", "LINES", "JOBS"]
+        .iter()
+        .any(|n| upper == *n || upper.ends_with(&format!("_{}", n)))
+    {
+        Some(ArgumentType::Number {
+            min: None,
+            max: None,
+        })
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flag<'a>(help: &'a ParsedHelp, long: &str) -> &'a FlagSpec {
+        help.flags
+            .iter()
+            .find(|f| f.long.as_deref() == Some(long))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_gnu_help() {
+        let help = parse_help(
+            "Usage: tool [OPTION]... [FILE]...
+List information about the FILEs.
+
+Mandatory arguments to long options are mandatory for short options too.
+  -a, --all                  do not ignore entries starting with .
+      --color[=WHEN]         color the output; WHEN can be 'always' or 'never'
+  -w, --width=COLS           set output width to COLS
+  -o FILE, --output=FILE     write to FILE
+  -T, --tabsize=NUM          assume tab stops at each NUM
+      --format {json,text}   output format
+      --help     display this help and exit
+",
+        );
+        assert_eq!(
+            help.description.as_deref(),
+            Some("List information about the FILEs.")
+        );
+        assert_eq!(help.flags.len(), 7);
+
+        let all = flag(&help, "all");
+        assert_eq!(all.short, Some('a'));
+        assert!(!all.takes_value);
+        assert_eq!(all.description.get("en"), "do not ignore entries starting with .");
+
+        assert!(flag(&help, "color").takes_value);
+        assert!(flag(&help, "width").value_type.is_none());
+        assert!(matches!(
+            flag(&help, "output").value_type,
+            Some(ArgumentType::Path { .. })
+        ));
+        assert_eq!(flag(&help, "output").short, Some('o'));
+        assert!(matches!(
+            flag(&help, "tabsize").value_type,
+            Some(ArgumentType::Number { .. })
+        ));
+        match &flag(&help, "format").value_type {
+            Some(ArgumentType::Choice { values }) => assert_eq!(values, &["json", "text"]),
+            other => panic!("expected choice, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_option_sections() {
+        let help = parse_help(
+            "Usage: tool OPTION... [FILE]...
+Print selected parts of lines.
+
+  -f, --fields=LIST       select only these fields;  also print any line
+                            that contains no delimiter character
+  -s, --only-delimited    do not print lines not containing delimiters
+
+Each range is one of:
+
+  N     N'th byte, character or field, counted from 1
+  -M    from first to M'th (included) byte, character or field
+
+Startup:
+  -V, --version           display the version
+
+ FORMAT is one of the following:
+    gnu                   GNU format
+
+      --posix             same as --format=posix
+",
+        );
+        assert!(help.flags.iter().all(|f| f.short != Some('M')));
+        assert_eq!(
+            flag(&help, "fields").description.get("en"),
+            "select only these fields;  also print any line that contains no delimiter character"
+        );
+        assert_eq!(flag(&help, "version").short, Some('V'));
+        assert!(!flag(&help, "posix").takes_value);
+        assert_eq!(help.flags.len(), 4);
+    }
+
+    #[test]
+    fn test_parse_subcommands() {
+        let help = parse_help(
+            "A build tool
+
+Usage: tool [OPTIONS] [COMMAND]
+
+Options:
+  -V, --version          Print version info and exit
+  -v, --verbose...       Use verbose output
+      --color <WHEN>     Coloring [possible values: auto, always, never]
+      --config <KEY=VALUE|PATH>  Override a configuration value
+  -C <DIRECTORY>         Change to DIRECTORY before doing anything
+
+Commands:
+    build, b    Compile the current package
+    check       Analyze the current package
+    ...         See all commands with --list
+",
+        );
+        assert_eq!(help.description.as_deref(), Some("A build tool"));
+        assert_eq!(
+            help.subcommands,
+            vec![
+                ("build".to_string(), "Compile the current package".to_string()),
+                ("check".to_string(), "Analyze the current package".to_string()),
+            ]
+        );
+        match &flag(&help, "color").value_type {
+            Some(ArgumentType::Choice { values }) => {
+                assert_eq!(values, &["auto", "always", "never"])
+            }
+            other => panic!("expected choice, got {:?}", other),
+        }
+        assert!(!matches!(
+            flag(&help, "config").value_type,
+            Some(ArgumentType::Choice { .. })
+        ));
+        assert_eq!(flag(&help, "verbose").short, Some('v'));
+
+        let dir = help.flags.iter().find(|f| f.short == Some('C')).unwrap();
+        assert!(matches!(
+            &dir.value_type,
+            Some(ArgumentType::Path { filter }) if filter.dirs_only
+        ));
+    }
+
+    #[test]
+    fn test_parse_man_page() {
+        let page = strip_overstrike(
+            "NAME
+       tool - frobnicate files
+
+SYNOPSIS
+       tool [-v] [--depth=N] FILE
+
+OPTIONS
+       -v, --verbose
+              Explain what is being done
+
+       --depth=N
+              Stop after N levels
+",
+        );
+        let help = parse_help(&page);
+        assert_eq!(help.description.as_deref(), Some("frobnicate files"));
+        assert_eq!(help.flags.len(), 2);
+        assert_eq!(
+            flag(&help, "verbose").description.get("en"),
+            "Explain what is being done"
+        );
+        assert!(flag(&help, "depth").takes_value);
+    }
+
+    #[test]
+    fn test_strip_overstrike() {
+        assert_eq!(strip_overstrike("N\u{8}NA\u{8}AM\u{8}ME\u{8}E"), "NAME");
+    }
+}
@@ -4,6 +4,9 @@
 
 #![allow(dead_code)]
 
+use crate::completer::SmartCompleter;
+use crate::environment::session_env;
+use crate::history_expand;
 use nu_ansi_term::{Color, Style};
 use reedline::{Highlighter, StyledText};
 use std::collections::HashSet;
@@ -22,7 +25,7 @@ pub struct SyntaxTheme {
     pub flag_short: Style,
     /// Long flags (--flag)
     pub flag_long: Style,
-    /// Invalid flags
+    /// Flags that break a `conflicts_with`, `requires` or `repeatable` rule
     pub invalid_flag: Style,
     /// Single-quoted strings
     pub string_single: Style,
@@ -38,6 +41,10 @@ pub struct SyntaxTheme {
     pub operator: Style,
     /// Variables ($VAR)
     pub variable: Style,
+    /// Variables not defined in the session environment
+    pub undefined_variable: Style,
+    /// History references (`!!`, `!$`, `^old^new`)
+    pub history: Style,
     /// Comments (# ...)
     pub comment: Style,
     /// Default text
@@ -60,6 +67,8 @@ impl Default for SyntaxTheme {
             path_missing: Style::new().fg(Color::Red).dimmed(),
             operator: Style::new().bold().fg(Color::White),
             variable: Style::new().fg(Color::Green),
+            undefined_variable: Style::new().fg(Color::Red).dimmed(),
+            history: Style::new().bold().fg(Color::Magenta),
             comment: Style::new().fg(Color::DarkGray).dimmed(),
             default: Style::new(),
         }
@@ -83,6 +92,8 @@ impl SyntaxTheme {
             path_missing: Style::new().fg(Color::Rgb(191, 97, 106)).dimmed(),
             operator: Style::new().bold().fg(Color::Rgb(216, 222, 233)),
             variable: Style::new().fg(Color::Rgb(235, 203, 139)), // yellow
+            undefined_variable: Style::new().fg(Color::Rgb(191, 97, 106)).dimmed(),
+            history: Style::new().bold().fg(Color::Rgb(180, 142, 173)), // purple
             comment: Style::new().fg(Color::Rgb(76, 86, 106)).dimmed(),
             default: Style::new().fg(Color::Rgb(216, 222, 233)),
         }
@@ -104,6 +115,8 @@ impl SyntaxTheme {
             path_missing: Style::new().fg(Color::Rgb(255, 85, 85)).dimmed(),
             operator: Style::new().bold().fg(Color::Rgb(255, 121, 198)), // pink
             variable: Style::new().fg(Color::Rgb(80, 250, 123)), // green
+            undefined_variable: Style::new().fg(Color::Rgb(255, 85, 85)).dimmed(),
+            history: Style::new().bold().fg(Color::Rgb(255, 184, 108)), // orange
             comment: Style::new().fg(Color::Rgb(98, 114, 164)).dimmed(), // comment
             default: Style::new().fg(Color::Rgb(248, 248, 242)),
         }
@@ -124,6 +137,7 @@ enum TokenType {
     Path,
     Operator,
     Variable,
+    HistoryReference,
     Comment,
     Whitespace,
     Unknown,
@@ -144,6 +158,8 @@ pub struct SmartHighlighter {
     commands: HashSet<String>,
     /// Syntax theme
     theme: SyntaxTheme,
+    /// Command specs, for checking flag constraints
+    completer: Option<SmartCompleter>,
 }
 
 impl SmartHighlighter {
@@ -151,9 +167,16 @@ impl SmartHighlighter {
         Self {
             commands: commands.into_iter().collect(),
             theme: SyntaxTheme::default(),
+            completer: None,
         }
     }
 
+    /// Check flags against the specs the completer has loaded
+    pub fn with_completer(mut self, completer: SmartCompleter) -> Self {
+        self.completer = Some(completer);
+        self
+    }
+
     pub fn with_theme(mut self, theme: SyntaxTheme) -> Self {
         self.theme = theme;
         self
@@ -165,8 +188,27 @@ impl SmartHighlighter {
         let mut chars = line.char_indices().peekable();
         let mut is_first_token = true;
         let mut after_command = false;
+        let references = history_expand::reference_spans(line);
 
         while let Some((start, c)) = chars.next() {
+            // Handle history references; they stand in for whole commands or words
+            if let Some(span) = references.iter().find(|span| span.start == start) {
+                while chars.peek().is_some_and(|(i, _)| *i < span.end) {
+                    chars.next();
+                }
+                tokens.push(Token {
+                    text: line[span.clone()].to_string(),
+                    token_type: TokenType::HistoryReference,
+                    start,
+                    end: span.end,
+                });
+                if is_first_token {
+                    is_first_token = false;
+                    after_command = true;
+                }
+                continue;
+            }
+
             // Handle comments
             if c == '#' {
                 let text: String = std::iter::once(c)
@@ -217,7 +259,7 @@ impl SmartHighlighter {
             if c == '\'' {
                 let mut text = String::from(c);
                 let mut closed = false;
-                while let Some((_, next_c)) = chars.next() {
+                for (_, next_c) in chars.by_ref() {
                     text.push(next_c);
                     if next_c == '\'' {
                         closed = true;
@@ -241,7 +283,7 @@ impl SmartHighlighter {
             if c == '"' {
                 let mut text = String::from(c);
                 let mut closed = false;
-                while let Some((_, next_c)) = chars.next() {
+                for (_, next_c) in chars.by_ref() {
                     text.push(next_c);
                     if next_c == '"' {
                         closed = true;
@@ -267,7 +309,7 @@ impl SmartHighlighter {
                 // Check for ${...} syntax
                 if chars.peek().map(|(_, c)| *c) == Some('{') {
                     text.push(chars.next().unwrap().1);
-                    while let Some((_, next_c)) = chars.next() {
+                    for (_, next_c) in chars.by_ref() {
                         text.push(next_c);
                         if next_c == '}' {
                             break;
@@ -294,8 +336,9 @@ impl SmartHighlighter {
 
             // Handle regular tokens (commands, arguments, flags)
             let mut text = String::from(c);
-            while let Some(&(_, next_c)) = chars.peek() {
-                if next_c.is_whitespace()
+            while let Some(&(i, next_c)) = chars.peek() {
+                if references.iter().any(|span| span.start == i)
+                    || next_c.is_whitespace()
                     || next_c == '|'
                     || next_c == '&'
                     || next_c == ';'
@@ -402,13 +445,63 @@ impl SmartHighlighter {
                 }
             }
             TokenType::Operator => self.theme.operator,
-            TokenType::Variable => self.theme.variable,
+            TokenType::Variable => {
+                if Self::is_defined_variable(&token.text) {
+                    self.theme.variable
+                } else {
+                    self.theme.undefined_variable
+                }
+            }
+            TokenType::HistoryReference => self.theme.history,
             TokenType::Comment => self.theme.comment,
             TokenType::Whitespace => self.theme.default,
             TokenType::Argument | TokenType::Unknown => self.theme.default,
         }
     }
 
+    /// Indices of flag tokens that break a constraint of their command
+    fn invalid_flags(&self, tokens: &[Token]) -> HashSet<usize> {
+        let mut invalid = HashSet::new();
+        let Some(completer) = &self.completer else {
+            return invalid;
+        };
+
+        // Check each command of a pipeline or list on its own
+        let mut check = |words: &mut Vec<(usize, &str)>| {
+            let texts: Vec<&str> = words.iter().map(|(_, text)| *text).collect();
+            for violation in completer.flag_violations(&texts) {
+                invalid.insert(words[violation.word].0);
+            }
+            words.clear();
+        };
+        let mut words = Vec::new();
+        for (i, token) in tokens.iter().enumerate() {
+            match token.token_type {
+                TokenType::Operator => check(&mut words),
+                TokenType::Whitespace | TokenType::Comment => {}
+                _ => words.push((i, token.text.as_str())),
+            }
+        }
+        check(&mut words);
+        invalid
+    }
+
+    /// Check if a `$VAR` / `${VAR}` token refers to a defined session variable
+    fn is_defined_variable(text: &str) -> bool {
+        let name: String = text
+            .trim_start_matches('$')
+            .trim_start_matches('{')
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .collect();
+
+        // Bare `$` and positional parameters are not checked
+        if name.is_empty() || name.chars().all(|c| c.is_ascii_digit()) {
+            return true;
+        }
+        session_env().is_defined(&name)
+    }
+
     /// Check if a command is a system command
     fn is_system_command(&self, cmd: &str) -> bool {
         // Common system commands that might not be in our definitions
@@ -421,6 +514,7 @@ impl SmartHighlighter {
             "java", "javac", "ruby", "perl", "php", "make", "cmake", "gcc", "g++", "clang",
             "git", "docker", "kubectl", "terraform", "ansible", "vim", "nvim", "nano", "code",
             "exit", "config", "clear", "history", "alias", "export", "source", "env", "set",
+            "unset", "jobs", "fg", "bg", "wait", "pushd", "popd", "dirs", "timeout",
         ];
 
         system_commands.contains(&cmd)
@@ -430,10 +524,15 @@ impl SmartHighlighter {
 impl Highlighter for SmartHighlighter {
     fn highlight(&self, line: &str, _cursor: usize) -> StyledText {
         let tokens = self.tokenize(line);
+        let invalid = self.invalid_flags(&tokens);
         let mut styled = StyledText::new();
 
-        for token in tokens {
-            let style = self.style_for_token(&token);
+        for (i, token) in tokens.into_iter().enumerate() {
+            let style = if invalid.contains(&i) {
+                self.theme.invalid_flag
+            } else {
+                self.style_for_token(&token)
+            };
             styled.push((style, token.text));
         }
 
@@ -482,6 +581,42 @@ mod tests {
         assert_eq!(vars[1].text, "${PATH}");
     }
 
+    #[test]
+    fn test_tokenize_history_references() {
+        let highlighter = SmartHighlighter::new(vec![]);
+        let tokens = highlighter.tokenize("sudo !! && cat !$ foo!-2:1");
+
+        let refs: Vec<_> = tokens
+            .iter()
+            .filter(|t| t.token_type == TokenType::HistoryReference)
+            .map(|t| t.text.as_str())
+            .collect();
+        assert_eq!(refs, vec!["!!", "!$", "!-2:1"]);
+
+        let tokens = highlighter.tokenize("!git --all");
+        assert_eq!(tokens[0].token_type, TokenType::HistoryReference);
+        assert_eq!(tokens[2].token_type, TokenType::LongFlag);
+    }
+
+    #[test]
+    fn test_undefined_variable_style() {
+        let highlighter = SmartHighlighter::new(vec![]);
+        session_env().set("SC_TEST_DEFINED", "1");
+
+        let tokens = highlighter.tokenize("echo $SC_TEST_DEFINED ${SC_TEST_UNDEFINED}");
+        let vars: Vec<_> = tokens
+            .iter()
+            .filter(|t| t.token_type == TokenType::Variable)
+            .collect();
+        assert_eq!(highlighter.style_for_token(vars[0]), highlighter.theme.variable);
+        assert_eq!(
+            highlighter.style_for_token(vars[1]),
+            highlighter.theme.undefined_variable
+        );
+
+        session_env().unset("SC_TEST_DEFINED");
+    }
+
     #[test]
     fn test_tokenize_strings() {
         let highlighter = SmartHighlighter::new(vec![]);
@@ -530,6 +665,37 @@ mod tests {
         assert!(comments[0].text.starts_with('#'));
     }
 
+    #[test]
+    fn test_flag_constraints_are_marked() {
+        let reset: crate::command_def::CommandSpec = serde_yaml::from_str(
+            r#"
+name: reset
+description: Reset
+flags:
+  - long: soft
+    description: Keep changes
+  - long: hard
+    description: Discard changes
+    conflicts_with: [soft]
+"#,
+        )
+        .unwrap();
+        let completer = SmartCompleter::new(
+            crate::catalog::Catalog::new(vec![reset]),
+            std::sync::Arc::new(std::sync::RwLock::new("en".to_string())),
+        );
+        let highlighter =
+            SmartHighlighter::new(vec!["reset".to_string()]).with_completer(completer);
+
+        let tokens = highlighter.tokenize("reset --hard x | reset --soft --hard --soft");
+        let invalid: Vec<&str> = {
+            let mut marked: Vec<usize> = highlighter.invalid_flags(&tokens).into_iter().collect();
+            marked.sort();
+            marked.iter().map(|&i| tokens[i].text.as_str()).collect()
+        };
+        assert_eq!(invalid, ["--soft", "--hard", "--soft"]);
+    }
+
     #[test]
     fn test_theme_default() {
         let theme = SyntaxTheme::default();
@@ -29,6 +29,10 @@ fn get_first_token(string: &str) -> String {
 }
 
 /// Smart hinter that combines history, AI predictions, and context
+///
+/// Clones share the predictor and last-command state, so a clone kept by the
+/// REPL can record executed commands for the instance owned by the editor.
+#[derive(Clone)]
 pub struct SmartHinter {
     /// Style for the hint text
     style: Style,
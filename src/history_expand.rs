@@ -0,0 +1,487 @@
+//! History Expansion
+//!
+//! Bash-style references to earlier command lines, expanded before aliases:
+//! - `!!` (previous line), `!n` (line n), `!-n` (n lines back)
+//! - `!prefix` (last line starting with prefix), `!?text?` (last line containing text)
+//! - Word designators after `:` (`!!:2`, `!-2:1-3`, `!ls:$`), plus the
+//!   shorthands `!$`, `!^` and `!*`
+//! - `^old^new^` re-runs the previous line with the first `old` replaced
+//!
+//! Nothing is expanded inside single quotes, after a backslash, or when `!`
+//! is followed by whitespace, `=`, `(` or `"` (so `[ ! -f x ]` and `!=` are safe).
+
+use std::ops::Range;
+
+/// Which history line a reference points at
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Event {
+    /// `!!` and `!-n`: n lines back
+    Relative(usize),
+    /// `!n`: line n, counting from 1
+    Absolute(usize),
+    /// `!prefix`
+    Prefix(String),
+    /// `!?text?`
+    Contains(String),
+}
+
+/// One end of a word range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Index(usize),
+    /// `$`
+    Last,
+    /// End of `x-`: every word up to, but not including, the last one
+    BeforeLast,
+}
+
+/// Selected words of the event line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Words {
+    from: Bound,
+    to: Bound,
+    /// `*` expands to nothing when there are no arguments
+    allow_empty: bool,
+}
+
+/// A `!` reference found in a line
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Reference {
+    event: Event,
+    words: Option<Words>,
+    /// Byte range in the line, `!` included
+    span: Range<usize>,
+}
+
+/// Byte ranges of every history reference in `line` (for highlighting)
+pub fn reference_spans(line: &str) -> Vec<Range<usize>> {
+    let mut spans: Vec<Range<usize>> = quick_substitution_end(line)
+        .map(|end| 0..end)
+        .into_iter()
+        .collect();
+    spans.extend(references(line).into_iter().map(|r| r.span));
+    spans
+}
+
+/// Check whether `line` contains anything to expand
+pub fn has_references(line: &str) -> bool {
+    !reference_spans(line).is_empty()
+}
+
+/// Expand history references in `line`
+///
+/// `history` is ordered oldest first and must not contain `line` itself.
+/// Returns `Ok(None)` when there is nothing to expand.
+pub fn expand(line: &str, history: &[String]) -> Result<Option<String>, String> {
+    if quick_substitution_end(line).is_some() {
+        return quick_substitution(line, history).map(Some);
+    }
+
+    let refs = references(line);
+    if refs.is_empty() {
+        return Ok(None);
+    }
+
+    let mut expanded = String::new();
+    let mut pos = 0;
+    for reference in refs {
+        let text = &line[reference.span.clone()];
+        let event = resolve_event(&reference.event, history)
+            .ok_or_else(|| format!("{}: event not found", text))?;
+        expanded.push_str(&line[pos..reference.span.start]);
+        match reference.words {
+            Some(words) => expanded.push_str(
+                &select_words(event, words)
+                    .ok_or_else(|| format!("{}: bad word specifier", text))?,
+            ),
+            None => expanded.push_str(event),
+        }
+        pos = reference.span.end;
+    }
+    expanded.push_str(&line[pos..]);
+    Ok(Some(expanded))
+}
+
+/// Find the `!` references outside single quotes
+fn references(line: &str) -> Vec<Reference> {
+    let mut refs = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut chars = line.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if quote != Some('\'') => {
+                chars.next();
+            }
+            '\'' | '"' if quote.is_none() => quote = Some(c),
+            c if Some(c) == quote => quote = None,
+            '!' if quote != Some('\'') => {
+                if let Some(reference) = parse_reference(line, i) {
+                    // Skip past the reference
+                    while chars
+                        .clone()
+                        .next()
+                        .is_some_and(|(j, _)| j < reference.span.end)
+                    {
+                        chars.next();
+                    }
+                    refs.push(reference);
+                }
+            }
+            _ => {}
+        }
+    }
+    refs
+}
+
+/// Parse the reference starting at the `!` at byte `start`
+fn parse_reference(line: &str, start: usize) -> Option<Reference> {
+    let rest = &line[start + 1..];
+    let next = rest.chars().next()?;
+
+    // (event, bytes consumed after `!`, whether `:` may be omitted before words)
+    let (event, len, colon_optional) = match next {
+        c if c.is_whitespace() || matches!(c, '=' | '(' | '"') => return None,
+        '!' => (Event::Relative(1), 1, true),
+        '$' | '^' | '*' | ':' => (Event::Relative(1), 0, true),
+        '-' => {
+            let digits = leading_digits(&rest[1..]);
+            let n = digits.parse().ok().filter(|n| *n > 0)?;
+            (Event::Relative(n), 1 + digits.len(), true)
+        }
+        c if c.is_ascii_digit() => {
+            let digits = leading_digits(rest);
+            (Event::Absolute(digits.parse().ok()?), digits.len(), true)
+        }
+        '?' => {
+            let body = &rest[1..];
+            let (text, len) = match body.find('?') {
+                Some(end) => (&body[..end], 1 + end + 1),
+                None => (body, 1 + body.len()),
+            };
+            if text.is_empty() {
+                return None;
+            }
+            (Event::Contains(text.to_string()), len, false)
+        }
+        _ => {
+            let prefix: String = rest
+                .chars()
+                .take_while(|c| {
+                    !c.is_whitespace()
+                        && !matches!(
+                            c,
+                            ':' | ';' | '|' | '&' | '<' | '>' | '(' | ')' | '\'' | '"'
+                        )
+                })
+                .collect();
+            if prefix.is_empty() {
+                return None;
+            }
+            let len = prefix.len();
+            (Event::Prefix(prefix), len, false)
+        }
+    };
+
+    let after = &rest[len..];
+    let (words, words_len) = match after.strip_prefix(':') {
+        Some(spec) => match parse_words(spec) {
+            Some((words, spec_len)) => (Some(words), 1 + spec_len),
+            None => (None, 0),
+        },
+        None if colon_optional => match after.chars().next() {
+            Some('^') => (Some(single(Bound::Index(1))), 1),
+            Some('$') => (Some(single(Bound::Last)), 1),
+            Some('*') => (Some(all_arguments()), 1),
+            _ => (None, 0),
+        },
+        None => (None, 0),
+    };
+
+    // A bare `!:` with nothing valid after it is just text
+    if len == 0 && words.is_none() {
+        return None;
+    }
+
+    Some(Reference {
+        event,
+        words,
+        span: start..start + 1 + len + words_len,
+    })
+}
+
+/// Parse a word designator after `:`, returning it and its length
+fn parse_words(spec: &str) -> Option<(Words, usize)> {
+    if spec.starts_with('*') {
+        return Some((all_arguments(), 1));
+    }
+    if let Some(rest) = spec.strip_prefix('-') {
+        let (to, len) = parse_bound(rest)?;
+        return Some((
+            Words {
+                from: Bound::Index(0),
+                to,
+                allow_empty: false,
+            },
+            1 + len,
+        ));
+    }
+
+    let (from, len) = parse_bound(spec)?;
+    let rest = &spec[len..];
+    if rest.starts_with('*') {
+        return Some((
+            Words {
+                from,
+                to: Bound::Last,
+                allow_empty: true,
+            },
+            len + 1,
+        ));
+    }
+    if let Some(after_dash) = rest.strip_prefix('-') {
+        return Some(match parse_bound(after_dash) {
+            Some((to, to_len)) => (
+                Words {
+                    from,
+                    to,
+                    allow_empty: false,
+                },
+                len + 1 + to_len,
+            ),
+            None => (
+                Words {
+                    from,
+                    to: Bound::BeforeLast,
+                    allow_empty: false,
+                },
+                len + 1,
+            ),
+        });
+    }
+    Some((single(from), len))
+}
+
+/// Parse `n`, `^` or `$`
+fn parse_bound(spec: &str) -> Option<(Bound, usize)> {
+    match spec.chars().next()? {
+        '^' => Some((Bound::Index(1), 1)),
+        '$' => Some((Bound::Last, 1)),
+        c if c.is_ascii_digit() => {
+            let digits = leading_digits(spec);
+            Some((Bound::Index(digits.parse().ok()?), digits.len()))
+        }
+        _ => None,
+    }
+}
+
+fn single(bound: Bound) -> Words {
+    Words {
+        from: bound,
+        to: bound,
+        allow_empty: false,
+    }
+}
+
+/// `*`: every word but the command name
+fn all_arguments() -> Words {
+    Words {
+        from: Bound::Index(1),
+        to: Bound::Last,
+        allow_empty: true,
+    }
+}
+
+fn leading_digits(text: &str) -> &str {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    &text[..end]
+}
+
+/// Look up the history line an event refers to
+fn resolve_event<'a>(event: &Event, history: &'a [String]) -> Option<&'a String> {
+    match event {
+        Event::Relative(n) => history.len().checked_sub(*n).map(|i| &history[i]),
+        Event::Absolute(n) => n.checked_sub(1).and_then(|i| history.get(i)),
+        Event::Prefix(prefix) => history
+            .iter()
+            .rev()
+            .find(|l| l.starts_with(prefix.as_str())),
+        Event::Contains(text) => history.iter().rev().find(|l| l.contains(text.as_str())),
+    }
+}
+
+/// Pick the designated words out of a history line
+fn select_words(line: &str, words: Words) -> Option<String> {
+    let all = split_words(line);
+    let last = all.len().checked_sub(1)?;
+    let index = |bound: Bound| match bound {
+        Bound::Index(n) => Some(n),
+        Bound::Last => Some(last),
+        Bound::BeforeLast => last.checked_sub(1),
+    };
+
+    let from = index(words.from)?;
+    let to = index(words.to)?;
+    if words.allow_empty && from > last {
+        return Some(String::new());
+    }
+    if from > to || to > last {
+        return None;
+    }
+    Some(all[from..=to].join(" "))
+}
+
+/// Split a command line into words, keeping quotes and treating operators as words
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quote != Some('\'') => {
+                word.push(c);
+                if let Some(next) = chars.next() {
+                    word.push(next);
+                }
+            }
+            '\'' | '"' if quote.is_none() => {
+                quote = Some(c);
+                word.push(c);
+            }
+            c if Some(c) == quote => {
+                quote = None;
+                word.push(c);
+            }
+            _ if quote.is_some() => word.push(c),
+            c if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            '|' | '&' | ';' | '<' | '>' => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                let mut op = c.to_string();
+                if matches!(c, '|' | '&' | '>') && chars.peek() == Some(&c) {
+                    op.push(c);
+                    chars.next();
+                }
+                words.push(op);
+            }
+            _ => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// End of a leading `^old^new^` (the whole line when the last `^` is omitted)
+fn quick_substitution_end(line: &str) -> Option<usize> {
+    let body = line.strip_prefix('^')?;
+    let old_end = body.find('^')?;
+    if old_end == 0 {
+        return None;
+    }
+    let new = &body[old_end + 1..];
+    Some(match new.find('^') {
+        Some(end) => 1 + old_end + 1 + end + 1,
+        None => line.len(),
+    })
+}
+
+/// Expand `^old^new^rest` into the previous line with `old` replaced
+fn quick_substitution(line: &str, history: &[String]) -> Result<String, String> {
+    let end = quick_substitution_end(line).unwrap_or(line.len());
+    let spec = &line[..end];
+    let body = spec[1..].trim_end_matches('^');
+    let (old, new) = body.split_once('^').unwrap_or((body, ""));
+
+    let previous = history
+        .last()
+        .ok_or_else(|| format!("{}: event not found", spec))?;
+    if !previous.contains(old) {
+        return Err(format!("{}: substitution failed", spec));
+    }
+    Ok(format!(
+        "{}{}",
+        previous.replacen(old, new, 1),
+        &line[end..]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<String> {
+        vec![
+            "git status".to_string(),
+            "ls -la /tmp".to_string(),
+            "cp a.txt 'b c.txt' | tee out.log".to_string(),
+        ]
+    }
+
+    fn expanded(line: &str) -> String {
+        expand(line, &history()).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_event_designators() {
+        assert_eq!(expanded("sudo !!"), "sudo cp a.txt 'b c.txt' | tee out.log");
+        assert_eq!(expanded("!1"), "git status");
+        assert_eq!(expanded("!-2"), "ls -la /tmp");
+        assert_eq!(expanded("!git --short"), "git status --short");
+        assert_eq!(expanded("!?-la?"), "ls -la /tmp");
+        assert_eq!(
+            expand("!9", &history()),
+            Err("!9: event not found".to_string())
+        );
+    }
+
+    #[test]
+    fn test_word_designators() {
+        assert_eq!(expanded("cat !$"), "cat out.log");
+        assert_eq!(expanded("echo !^"), "echo a.txt");
+        assert_eq!(expanded("echo !!:2"), "echo 'b c.txt'");
+        assert_eq!(expanded("echo !*"), "echo a.txt 'b c.txt' | tee out.log");
+        assert_eq!(expanded("echo !ls:1-2"), "echo -la /tmp");
+        assert_eq!(expanded("echo !ls:0-"), "echo ls -la");
+        assert_eq!(expanded("echo !-2:1*"), "echo -la /tmp");
+        assert_eq!(
+            expand("echo !ls:7", &history()),
+            Err("!ls:7: bad word specifier".to_string())
+        );
+    }
+
+    #[test]
+    fn test_quick_substitution() {
+        assert_eq!(
+            expanded("^tee^cat^ -n"),
+            "cp a.txt 'b c.txt' | cat out.log -n"
+        );
+        assert_eq!(expanded("^a.txt^x.txt"), "cp x.txt 'b c.txt' | tee out.log");
+        assert!(expand("^nope^x", &history()).is_err());
+    }
+
+    #[test]
+    fn test_literal_bangs() {
+        for line in [
+            "[ ! -f x ]",
+            "a != b",
+            "echo 'hi!!'",
+            "echo \\!!",
+            "echo hi!",
+        ] {
+            assert_eq!(expand(line, &history()), Ok(None), "{}", line);
+            assert!(!has_references(line));
+        }
+        assert_eq!(reference_spans("echo !$ x"), vec![5..7]);
+    }
+}
@@ -0,0 +1,1095 @@
+//! Import completion specs from other ecosystems (`sc defs import`)
+//!
+//! Supported formats, read from local files only:
+//! - Fig (withfig/autocomplete) specs exported as JSON
+//! - carapace-spec YAML
+//! - fish `complete` files
+//!
+//! Subcommands, options, value-taking flags, choice lists and path templates
+//! carry over. Generators are mapped to a built-in provider when their script
+//! is recognisable. Everything else is listed in [`Import::notes`].
+
+use crate::command_def::{
+    ArgumentSpec, ArgumentType, CommandSpec, FlagSpec, I18nString, PathFilterConfig,
+};
+use serde_json::Value as Json;
+use serde_yaml::Value as Yaml;
+
+/// Source format of an imported spec
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImportFormat {
+    Fig,
+    Carapace,
+    Fish,
+}
+
+/// A converted spec and what could not be represented
+#[derive(Debug)]
+pub struct Import {
+    pub spec: CommandSpec,
+    pub notes: Vec<String>,
+}
+
+/// Convert a spec file's content
+pub fn import(format: ImportFormat, source: &str) -> anyhow::Result<Import> {
+    let mut notes = Vec::new();
+    let spec = match format {
+        ImportFormat::Fig => {
+            let value: Json = serde_json::from_str(source)?;
+            fig_spec(&value, &[], "", &mut notes)?
+        }
+        ImportFormat::Carapace => {
+            let value: Yaml = serde_yaml::from_str(source)?;
+            carapace_spec(&value, &[], "", &mut notes)?
+        }
+        ImportFormat::Fish => fish_spec(source, &mut notes)?,
+    };
+    Ok(Import { spec, notes })
+}
+
+/// Built-in provider for a generator script or completion macro
+fn provider_for(script: &str) -> Option<&'static str> {
+    let s = script.to_lowercase();
+    let has = |words: &[&str]| words.iter().all(|w| s.contains(w));
+    if has(&["git", "branch"]) {
+        Some("git_branch")
+    } else if has(&["git", "tag"]) {
+        Some("git_tag")
+    } else if has(&["git", "remote"]) {
+        Some("git_remote")
+    } else if has(&["git", "stash"]) {
+        Some("git_stash")
+    } else if has(&["docker", "container"]) || has(&["docker", "ps"]) {
+        Some("docker_container")
+    } else if has(&["docker", "image"]) {
+        Some("docker_image")
+    } else if has(&["docker", "volume"]) {
+        Some("docker_volume")
+    } else if has(&["kube", "namespace"]) || has(&["kubectl", " ns"]) {
+        Some("k8s_namespace")
+    } else if has(&["kube", "context"]) {
+        Some("k8s_context")
+    } else if has(&["kube", "pod"]) {
+        Some("k8s_pods")
+    } else if has(&["ssh", "host"]) {
+        Some("ssh_host")
+    } else if has(&["make", "target"]) {
+        Some("make_target")
+    } else if has(&["env"]) && (s.contains("var") || s.contains("env ")) {
+        Some("env_var")
+    } else if has(&["process"]) || has(&["ps ", "pid"]) {
+        Some("process")
+    } else {
+        None
+    }
+}
+
+fn path_type(dirs_only: bool, extensions: Option<Vec<String>>) -> ArgumentType {
+    ArgumentType::Path {
+        filter: PathFilterConfig {
+            dirs_only,
+            extensions,
+            ..Default::default()
+        },
+    }
+}
+
+/// Split `-f` / `--force` names into a flag's short and long name
+fn flag_names(
+    names: &[String],
+    path: &str,
+    notes: &mut Vec<String>,
+) -> (Option<String>, Option<char>) {
+    let mut long = None;
+    let mut short = None;
+    for name in names {
+        if let Some(l) = name.strip_prefix("--") {
+            if long.is_none() {
+                long = Some(l.to_string());
+                continue;
+            }
+        } else if let Some(s) = name.strip_prefix('-') {
+            let mut chars = s.chars();
+            if let (Some(c), None, None) = (chars.next(), chars.next(), short) {
+                short = Some(c);
+                continue;
+            }
+        }
+        notes.push(format!("{}: extra option name `{}` dropped", path, name));
+    }
+    (long, short)
+}
+
+fn flag_label(flag: &FlagSpec) -> String {
+    match (&flag.long, flag.short) {
+        (Some(long), _) => format!("--{}", long),
+        (None, Some(short)) => format!("-{}", short),
+        (None, None) => "-".to_string(),
+    }
+}
+
+// ---- Fig ----------------------------------------------------------------
+
+/// Keys that only affect Fig's UI
+const FIG_COSMETIC: &[&str] = &["icon", "priority", "displayName", "insertValue", "hidden"];
+
+fn fig_names(value: Option<&Json>) -> Vec<String> {
+    match value {
+        Some(Json::String(s)) => vec![s.clone()],
+        Some(Json::Array(items)) => items
+            .iter()
+            .filter_map(|v| v.as_str().map(String::from))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn fig_list(value: Option<&Json>) -> Vec<&Json> {
+    match value {
+        Some(Json::Array(items)) => items.iter().collect(),
+        Some(v @ Json::Object(_)) => vec![v],
+        _ => Vec::new(),
+    }
+}
+
+fn fig_description(value: &Json) -> String {
+    value
+        .get("description")
+        .and_then(|d| d.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn fig_unknown_keys(value: &Json, known: &[&str], path: &str, notes: &mut Vec<String>) {
+    if let Some(map) = value.as_object() {
+        for key in map.keys() {
+            if !known.contains(&key.as_str()) && !FIG_COSMETIC.contains(&key.as_str()) {
+                notes.push(format!("{}: `{}` is not supported", path, key));
+            }
+        }
+    }
+}
+
+fn fig_spec(
+    value: &Json,
+    inherited: &[FlagSpec],
+    parent: &str,
+    notes: &mut Vec<String>,
+) -> anyhow::Result<CommandSpec> {
+    let names = fig_names(value.get("name"));
+    let Some(name) = names.first() else {
+        anyhow::bail!(
+            "{}: spec without a name",
+            if parent.is_empty() { "<root>" } else { parent }
+        );
+    };
+    let path = if parent.is_empty() {
+        name.clone()
+    } else {
+        format!("{} {}", parent, name)
+    };
+    if names.len() > 1 {
+        notes.push(format!(
+            "{}: aliases {} dropped",
+            path,
+            names[1..].join(", ")
+        ));
+    }
+    fig_unknown_keys(
+        value,
+        &["name", "description", "subcommands", "options", "args"],
+        &path,
+        notes,
+    );
+
+    let mut spec = CommandSpec::new(name, "");
+    spec.description = I18nString::english(&fig_description(value));
+    spec.flags = inherited.to_vec();
+
+    let mut persistent = inherited.to_vec();
+    for option in fig_list(value.get("options")) {
+        let option_names = fig_names(option.get("name"));
+        let (long, short) = flag_names(&option_names, &path, notes);
+        if long.is_none() && short.is_none() {
+            continue;
+        }
+        let mut flag = FlagSpec {
+            long,
+            short,
+            description: I18nString::english(&fig_description(option)),
+            takes_value: false,
+            value_type: None,
+            ..Default::default()
+        };
+        let label = format!("{} {}", path, flag_label(&flag));
+        fig_unknown_keys(
+            option,
+            &["name", "description", "args", "isPersistent"],
+            &label,
+            notes,
+        );
+
+        let args = fig_list(option.get("args"));
+        if let Some(arg) = args.first() {
+            flag.takes_value = true;
+            flag.value_type = fig_arg_type(arg, &label, notes);
+        }
+        if args.len() > 1 {
+            notes.push(format!(
+                "{}: only the first of {} values is kept",
+                label,
+                args.len()
+            ));
+        }
+
+        if option.get("isPersistent").and_then(|v| v.as_bool()) == Some(true) {
+            persistent.push(flag.clone());
+        }
+        match spec
+            .flags
+            .iter_mut()
+            .find(|f| f.long == flag.long && f.short == flag.short)
+        {
+            Some(existing) => *existing = flag,
+            None => spec.flags.push(flag),
+        }
+    }
+
+    for (position, arg) in fig_list(value.get("args")).into_iter().enumerate() {
+        let arg_name = arg.get("name").and_then(|n| n.as_str()).map(String::from);
+        let label = format!("{} <{}>", path, arg_name.as_deref().unwrap_or("arg"));
+        fig_unknown_keys(
+            arg,
+            &[
+                "name",
+                "description",
+                "isOptional",
+                "isVariadic",
+                "suggestions",
+                "template",
+                "generators",
+            ],
+            &label,
+            notes,
+        );
+        spec.arguments.push(ArgumentSpec {
+            position: Some(position),
+            name: arg_name,
+            description: arg
+                .get("description")
+                .and_then(|d| d.as_str())
+                .map(I18nString::english),
+            arg_type: fig_arg_type(arg, &label, notes).unwrap_or_default(),
+            required: arg.get("isOptional").and_then(|v| v.as_bool()) != Some(true),
+            variadic: arg.get("isVariadic").and_then(|v| v.as_bool()) == Some(true),
+            provider: None,
+        });
+    }
+
+    for sub in fig_list(value.get("subcommands")) {
+        spec.subcommands
+            .push(fig_spec(sub, &persistent, &path, notes)?);
+    }
+    Ok(spec)
+}
+
+/// Value type of a Fig arg from its suggestions, template or generators
+fn fig_arg_type(arg: &Json, label: &str, notes: &mut Vec<String>) -> Option<ArgumentType> {
+    let suggestions: Vec<String> = fig_list(arg.get("suggestions"))
+        .into_iter()
+        .filter_map(|s| match s {
+            Json::String(s) => Some(s.clone()),
+            other => fig_names(other.get("name")).into_iter().next(),
+        })
+        .collect();
+    if !suggestions.is_empty() {
+        return Some(ArgumentType::Choice {
+            values: suggestions,
+        });
+    }
+
+    let template = fig_names(arg.get("template"));
+    if template.iter().any(|t| t == "filepaths") {
+        return Some(path_type(false, None));
+    }
+    if template.iter().any(|t| t == "folders") {
+        return Some(path_type(true, None));
+    }
+
+    for generator in fig_list(arg.get("generators")) {
+        let template = fig_names(generator.get("template"));
+        if template.iter().any(|t| t == "filepaths") {
+            return Some(path_type(false, None));
+        }
+        if template.iter().any(|t| t == "folders") {
+            return Some(path_type(true, None));
+        }
+        let script = match generator.get("script") {
+            Some(Json::String(s)) => s.clone(),
+            Some(Json::Array(parts)) => parts
+                .iter()
+                .filter_map(|p| p.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+            _ => String::new(),
+        };
+        match provider_for(&script) {
+            Some(provider) => {
+                return Some(ArgumentType::Provider {
+                    name: provider.to_string(),
+                })
+            }
+            None => notes.push(format!(
+                "{}: generator {} has no matching provider",
+                label,
+                if script.is_empty() {
+                    "(function)".to_string()
+                } else {
+                    format!("`{}`", script)
+                }
+            )),
+        }
+    }
+    None
+}
+
+// ---- carapace ------------------------------------------------------------
+
+fn yaml_str<'a>(value: &'a Yaml, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(|v| v.as_str())
+}
+
+/// carapace value lists: `["a\tdesc", "$files"]`
+fn yaml_strings(value: Option<&Yaml>) -> Vec<String> {
+    match value {
+        Some(Yaml::Sequence(items)) => items
+            .iter()
+            .filter_map(|v| v.as_str().map(String::from))
+            .collect(),
+        Some(Yaml::String(s)) => vec![s.clone()],
+        _ => Vec::new(),
+    }
+}
+
+/// Parse a carapace flag key: `-o, --output=` / `--verbose*` / `--opt?`
+fn carapace_flag(
+    key: &str,
+    description: &str,
+    path: &str,
+    notes: &mut Vec<String>,
+) -> Option<FlagSpec> {
+    let mut takes_value = false;
+    let mut names = Vec::new();
+    for part in key.split(',').map(str::trim) {
+        let mut name = part;
+        loop {
+            if let Some(n) = name.strip_suffix('=') {
+                takes_value = true;
+                name = n;
+            } else if let Some(n) = name.strip_suffix('?') {
+                takes_value = true;
+                name = n;
+            } else if let Some(n) = name.strip_suffix('*') {
+                notes.push(format!("{} {}: repeatable marker dropped", path, n));
+                name = n;
+            } else if let Some(n) = name.strip_suffix('&') {
+                notes.push(format!("{} {}: hidden marker dropped", path, n));
+                name = n;
+            } else if let Some(n) = name.strip_suffix('!') {
+                notes.push(format!("{} {}: required marker dropped", path, n));
+                name = n;
+            } else {
+                break;
+            }
+        }
+        // `-name` (single dash, several letters) is a non-POSIX long flag
+        if name.starts_with('-') && !name.starts_with("--") && name.chars().count() > 2 {
+            notes.push(format!(
+                "{}: single-dash long flag `{}` dropped",
+                path, name
+            ));
+            continue;
+        }
+        names.push(name.to_string());
+    }
+    let (long, short) = flag_names(&names, path, notes);
+    if long.is_none() && short.is_none() {
+        return None;
+    }
+    Some(FlagSpec {
+        long,
+        short,
+        description: I18nString::english(description),
+        takes_value,
+        value_type: None,
+        ..Default::default()
+    })
+}
+
+fn carapace_flags(value: Option<&Yaml>, path: &str, notes: &mut Vec<String>) -> Vec<FlagSpec> {
+    let Some(Yaml::Mapping(map)) = value else {
+        return Vec::new();
+    };
+    map.iter()
+        .filter_map(|(key, description)| {
+            let key = key.as_str()?;
+            carapace_flag(key, description.as_str().unwrap_or_default(), path, notes)
+        })
+        .collect()
+}
+
+/// Value type from carapace completion values and macros
+fn carapace_type(values: &[String], label: &str, notes: &mut Vec<String>) -> Option<ArgumentType> {
+    let mut literals = Vec::new();
+    for value in values {
+        let value = value.split('\t').next().unwrap_or_default();
+        let Some(macro_name) = value.strip_prefix('$') else {
+            literals.push(value.to_string());
+            continue;
+        };
+        let (macro_name, arg) = match macro_name.split_once('(') {
+            Some((m, a)) => (m, Some(a.trim_end_matches(')'))),
+            None => (macro_name, None),
+        };
+        match macro_name {
+            "files" => {
+                let extensions = arg.map(|a| {
+                    a.trim_matches(['[', ']'])
+                        .split(',')
+                        .map(|e| e.trim().trim_start_matches('.').to_string())
+                        .filter(|e| !e.is_empty())
+                        .collect()
+                });
+                return Some(path_type(false, extensions));
+            }
+            "directories" => return Some(path_type(true, None)),
+            other => match provider_for(other) {
+                Some(provider) => {
+                    return Some(ArgumentType::Provider {
+                        name: provider.to_string(),
+                    })
+                }
+                None => notes.push(format!(
+                    "{}: macro `${}` has no matching provider",
+                    label, other
+                )),
+            },
+        }
+    }
+    (!literals.is_empty()).then_some(ArgumentType::Choice { values: literals })
+}
+
+fn carapace_spec(
+    value: &Yaml,
+    inherited: &[FlagSpec],
+    parent: &str,
+    notes: &mut Vec<String>,
+) -> anyhow::Result<CommandSpec> {
+    let Some(name) = yaml_str(value, "name") else {
+        anyhow::bail!(
+            "{}: command without a name",
+            if parent.is_empty() { "<root>" } else { parent }
+        );
+    };
+    // `name: add [file]...` carries usage after the name
+    let name = name.split_whitespace().next().unwrap_or(name);
+    let path = if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{} {}", parent, name)
+    };
+
+    if let Some(Yaml::Mapping(map)) = Some(value) {
+        for key in map.keys().filter_map(|k| k.as_str()) {
+            if ![
+                "name",
+                "description",
+                "aliases",
+                "group",
+                "flags",
+                "persistentflags",
+                "completion",
+                "commands",
+            ]
+            .contains(&key)
+            {
+                notes.push(format!("{}: `{}` is not supported", path, key));
+            }
+        }
+    }
+    let aliases = yaml_strings(value.get("aliases"));
+    if !aliases.is_empty() {
+        notes.push(format!("{}: aliases {} dropped", path, aliases.join(", ")));
+    }
+
+    let mut spec = CommandSpec::new(name, "");
+    spec.description = I18nString::english(yaml_str(value, "description").unwrap_or_default());
+    let persistent: Vec<FlagSpec> = inherited
+        .iter()
+        .cloned()
+        .chain(carapace_flags(value.get("persistentflags"), &path, notes))
+        .collect();
+    spec.flags = persistent.clone();
+    spec.flags
+        .extend(carapace_flags(value.get("flags"), &path, notes));
+
+    if let Some(completion) = value.get("completion") {
+        if let Some(Yaml::Mapping(flags)) = completion.get("flag") {
+            for (key, values) in flags {
+                let Some(key) = key.as_str() else { continue };
+                let label = format!("{} --{}", path, key);
+                let value_type = carapace_type(&yaml_strings(Some(values)), &label, notes);
+                let flag = spec.flags.iter_mut().find(|f| {
+                    f.long.as_deref() == Some(key)
+                        || f.short.map(|c| c.to_string()).as_deref() == Some(key)
+                });
+                match flag {
+                    Some(flag) => flag.value_type = value_type,
+                    None => notes.push(format!("{}: completion for unknown flag", label)),
+                }
+            }
+        }
+        if let Some(Yaml::Sequence(positional)) = completion.get("positional") {
+            for (position, values) in positional.iter().enumerate() {
+                let label = format!("{} <#{}>", path, position);
+                spec.arguments.push(ArgumentSpec {
+                    position: Some(position),
+                    name: None,
+                    description: None,
+                    arg_type: carapace_type(&yaml_strings(Some(values)), &label, notes)
+                        .unwrap_or_default(),
+                    required: false,
+                    variadic: false,
+                    provider: None,
+                });
+            }
+        }
+        if let Some(values) = completion.get("positionalany") {
+            let position = spec.arguments.len();
+            let label = format!("{} <#{}...>", path, position);
+            spec.arguments.push(ArgumentSpec {
+                position: Some(position),
+                name: None,
+                description: None,
+                arg_type: carapace_type(&yaml_strings(Some(values)), &label, notes)
+                    .unwrap_or_default(),
+                required: false,
+                variadic: true,
+                provider: None,
+            });
+        }
+        for key in ["dash", "dashany"] {
+            if completion.get(key).is_some() {
+                notes.push(format!("{}: `completion.{}` is not supported", path, key));
+            }
+        }
+    }
+
+    if let Some(Yaml::Sequence(commands)) = value.get("commands") {
+        for sub in commands {
+            spec.subcommands
+                .push(carapace_spec(sub, &persistent, &path, notes)?);
+        }
+    }
+    Ok(spec)
+}
+
+// ---- fish ----------------------------------------------------------------
+
+/// Split a fish command line into words, honouring quotes and backslashes
+fn fish_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                in_word = true;
+                while let Some(q) = chars.next() {
+                    if q == c {
+                        break;
+                    }
+                    // Inside quotes only the quote and backslash can be escaped
+                    if q == '\\' {
+                        match chars.next() {
+                            Some(e) if e == c || e == '\\' => current.push(e),
+                            Some(e) => {
+                                current.push(q);
+                                current.push(e);
+                            }
+                            None => current.push(q),
+                        }
+                        continue;
+                    }
+                    current.push(q);
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            '#' if !in_word => break,
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    words
+}
+
+/// One parsed `complete` line
+#[derive(Debug, Default)]
+struct FishComplete {
+    command: Option<String>,
+    condition: Option<String>,
+    short: Option<char>,
+    long: Option<String>,
+    old: Option<String>,
+    arguments: Option<String>,
+    description: String,
+    requires_value: bool,
+    force_files: bool,
+}
+
+fn fish_complete(words: &[String]) -> FishComplete {
+    let mut c = FishComplete::default();
+    let mut i = 1;
+    while i < words.len() {
+        let word = words[i].as_str();
+        let (flag, inline) = match word.split_once('=') {
+            Some((f, v)) if f.starts_with("--") => (f, Some(v.to_string())),
+            _ => (word, None),
+        };
+        let takes_arg = matches!(
+            flag,
+            "-c" | "--command"
+                | "-n"
+                | "--condition"
+                | "-s"
+                | "--short-option"
+                | "-l"
+                | "--long-option"
+                | "-o"
+                | "--old-option"
+                | "-a"
+                | "--arguments"
+                | "-d"
+                | "--description"
+                | "-w"
+                | "--wraps"
+                | "-p"
+                | "--path"
+        );
+        let arg = if takes_arg {
+            inline.or_else(|| {
+                i += 1;
+                words.get(i).cloned()
+            })
+        } else {
+            None
+        };
+        match flag {
+            "-c" | "--command" | "-p" | "--path" => c.command = arg,
+            "-n" | "--condition" => c.condition = arg,
+            "-s" | "--short-option" => c.short = arg.and_then(|a| a.chars().next()),
+            "-l" | "--long-option" => c.long = arg,
+            "-o" | "--old-option" => c.old = arg,
+            "-a" | "--arguments" => c.arguments = arg,
+            "-d" | "--description" => c.description = arg.unwrap_or_default(),
+            "-r" | "--require-parameter" | "-x" | "--exclusive" => c.requires_value = true,
+            "-F" | "--force-files" => c.force_files = true,
+            _ => {}
+        }
+        i += 1;
+    }
+    c
+}
+
+/// Value type for fish `-a` arguments
+fn fish_type(
+    arguments: &str,
+    force_files: bool,
+    label: &str,
+    notes: &mut Vec<String>,
+) -> Option<ArgumentType> {
+    let arguments = arguments.trim();
+    if let Some(call) = arguments
+        .strip_prefix('(')
+        .and_then(|a| a.strip_suffix(')'))
+    {
+        if call.contains("__fish_complete_directories") {
+            return Some(path_type(true, None));
+        }
+        if call.contains("__fish_complete_path") || call.contains("__fish_complete_suffix") {
+            return Some(path_type(false, None));
+        }
+        return match provider_for(call) {
+            Some(provider) => Some(ArgumentType::Provider {
+                name: provider.to_string(),
+            }),
+            None => {
+                notes.push(format!("{}: `({})` has no matching provider", label, call));
+                None
+            }
+        };
+    }
+    if arguments.is_empty() {
+        return force_files.then(|| path_type(false, None));
+    }
+    Some(ArgumentType::Choice {
+        values: arguments.split_whitespace().map(String::from).collect(),
+    })
+}
+
+/// Subcommand a fish condition refers to, if it is one we understand
+enum FishTarget {
+    Root,
+    /// `__fish_use_subcommand`: the line lists subcommands
+    SubcommandList,
+    Subcommand(String),
+    Unknown,
+}
+
+fn fish_target(condition: Option<&str>) -> FishTarget {
+    let Some(condition) = condition else {
+        return FishTarget::Root;
+    };
+    let words = fish_words(condition);
+    let first = words.first().map(String::as_str);
+    if first == Some("__fish_use_subcommand")
+        || (first == Some("not")
+            && words.get(1).map(String::as_str) == Some("__fish_seen_subcommand_from"))
+    {
+        return FishTarget::SubcommandList;
+    }
+    if first == Some("__fish_seen_subcommand_from") && words.len() == 2 {
+        return FishTarget::Subcommand(words[1].clone());
+    }
+    FishTarget::Unknown
+}
+
+fn fish_spec(source: &str, notes: &mut Vec<String>) -> anyhow::Result<CommandSpec> {
+    // Join continuation lines first
+    let joined = source.replace("\\\n", " ");
+    let mut spec: Option<CommandSpec> = None;
+
+    for (number, line) in joined.lines().enumerate() {
+        let words = fish_words(line);
+        if words.first().map(String::as_str) != Some("complete") {
+            if !words.is_empty() {
+                notes.push(format!(
+                    "line {}: not a `complete` command, skipped",
+                    number + 1
+                ));
+            }
+            continue;
+        }
+        let c = fish_complete(&words);
+        let Some(command) = c.command.clone() else {
+            notes.push(format!("line {}: no -c command, skipped", number + 1));
+            continue;
+        };
+        let root = spec.get_or_insert_with(|| {
+            let mut root = CommandSpec::new(&command, "");
+            root.description = I18nString::english(&command);
+            root
+        });
+        if root.name != command {
+            notes.push(format!(
+                "line {}: completion for `{}` skipped (importing `{}`)",
+                number + 1,
+                command,
+                root.name
+            ));
+            continue;
+        }
+
+        if let Some(old) = &c.old {
+            notes.push(format!(
+                "line {}: old-style option -{} dropped",
+                number + 1,
+                old
+            ));
+        }
+
+        let target = fish_target(c.condition.as_deref());
+        let has_flag = c.short.is_some() || c.long.is_some();
+
+        let (spec, path) = match target {
+            FishTarget::SubcommandList if !has_flag => {
+                for name in c
+                    .arguments
+                    .as_deref()
+                    .unwrap_or_default()
+                    .split_whitespace()
+                {
+                    if !root.subcommands.iter().any(|s| s.name == name) {
+                        let mut sub = CommandSpec::new(name, "");
+                        sub.description = I18nString::english(&c.description);
+                        root.subcommands.push(sub);
+                    }
+                }
+                continue;
+            }
+            FishTarget::Root | FishTarget::SubcommandList => (&mut *root, command.clone()),
+            FishTarget::Subcommand(name) => {
+                let path = format!("{} {}", command, name);
+                let index = match root.subcommands.iter().position(|s| s.name == name) {
+                    Some(index) => index,
+                    None => {
+                        let mut sub = CommandSpec::new(&name, "");
+                        sub.description = I18nString::english(&name);
+                        root.subcommands.push(sub);
+                        root.subcommands.len() - 1
+                    }
+                };
+                (&mut root.subcommands[index], path)
+            }
+            FishTarget::Unknown => {
+                notes.push(format!(
+                    "line {}: condition `{}` not supported, added to `{}`",
+                    number + 1,
+                    c.condition.as_deref().unwrap_or_default(),
+                    command
+                ));
+                (&mut *root, command.clone())
+            }
+        };
+
+        if has_flag {
+            let mut flag = FlagSpec {
+                long: c.long.clone(),
+                short: c.short,
+                description: I18nString::english(&c.description),
+                takes_value: c.requires_value,
+                value_type: None,
+                ..Default::default()
+            };
+            let label = format!("{} {}", path, flag_label(&flag));
+            if let Some(arguments) = &c.arguments {
+                flag.takes_value = true;
+                flag.value_type = fish_type(arguments, c.force_files, &label, notes);
+            } else if c.force_files && c.requires_value {
+                flag.value_type = Some(path_type(false, None));
+            }
+            match spec
+                .flags
+                .iter_mut()
+                .find(|f| f.long == flag.long && f.short == flag.short)
+            {
+                Some(existing) => *existing = flag,
+                None => spec.flags.push(flag),
+            }
+        } else if let Some(arguments) = &c.arguments {
+            let label = format!("{} <#0>", path);
+            if let Some(arg_type) = fish_type(arguments, c.force_files, &label, notes) {
+                match spec.arguments.first_mut() {
+                    Some(ArgumentSpec {
+                        arg_type: ArgumentType::Choice { values },
+                        ..
+                    }) if matches!(&arg_type, ArgumentType::Choice { .. }) => {
+                        if let ArgumentType::Choice { values: more } = arg_type {
+                            values.extend(more);
+                        }
+                    }
+                    Some(_) => notes.push(format!("{}: extra argument completion dropped", label)),
+                    None => spec.arguments.push(ArgumentSpec {
+                        position: Some(0),
+                        name: None,
+                        description: None,
+                        arg_type,
+                        required: false,
+                        variadic: true,
+                        provider: None,
+                    }),
+                }
+            }
+        }
+    }
+
+    spec.ok_or_else(|| anyhow::anyhow!("No `complete -c` lines found"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flag<'a>(spec: &'a CommandSpec, long: &str) -> &'a FlagSpec {
+        spec.flags
+            .iter()
+            .find(|f| f.long.as_deref() == Some(long))
+            .unwrap_or_else(|| panic!("no --{} in {}", long, spec.name))
+    }
+
+    fn sub<'a>(spec: &'a CommandSpec, name: &str) -> &'a CommandSpec {
+        spec.subcommands.iter().find(|s| s.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_import_fig() {
+        let import = import(
+            ImportFormat::Fig,
+            r#"{
+  "name": "deploy",
+  "description": "Deploy tool",
+  "options": [
+    { "name": ["-v", "--verbose"], "description": "Verbose", "isPersistent": true },
+    { "name": "--env", "description": "Target", "args": { "name": "env", "suggestions": ["staging", { "name": "prod" }] } },
+    { "name": "--force", "description": "Force", "isRepeatable": true }
+  ],
+  "subcommands": [
+    {
+      "name": ["rollback", "rb"],
+      "description": "Undo a deploy",
+      "args": [
+        { "name": "branch", "generators": { "script": "git branch --no-color" } },
+        { "name": "files", "template": "filepaths", "isVariadic": true, "isOptional": true }
+      ]
+    }
+  ]
+}"#,
+        )
+        .unwrap();
+        let spec = import.spec;
+        assert_eq!(spec.description.get("en"), "Deploy tool");
+        assert_eq!(flag(&spec, "verbose").short, Some('v'));
+        match &flag(&spec, "env").value_type {
+            Some(ArgumentType::Choice { values }) => assert_eq!(values, &["staging", "prod"]),
+            other => panic!("expected choice, got {:?}", other),
+        }
+
+        let rollback = sub(&spec, "rollback");
+        // Persistent options are copied into subcommands
+        assert!(flag(rollback, "verbose").short == Some('v'));
+        assert!(matches!(
+            &rollback.arguments[0].arg_type,
+            ArgumentType::Provider { name } if name == "git_branch"
+        ));
+        assert!(rollback.arguments[0].required);
+        assert!(matches!(
+            rollback.arguments[1].arg_type,
+            ArgumentType::Path { .. }
+        ));
+        assert!(rollback.arguments[1].variadic);
+        assert!(!rollback.arguments[1].required);
+
+        assert!(import.notes.iter().any(|n| n.contains("isRepeatable")));
+        assert!(import.notes.iter().any(|n| n.contains("aliases rb")));
+    }
+
+    #[test]
+    fn test_import_carapace() {
+        let import = import(
+            ImportFormat::Carapace,
+            r#"
+name: deploy
+description: Deploy tool
+persistentflags:
+  -v, --verbose*: Verbose output
+flags:
+  -o, --output=: Output file
+  --format=: Output format
+commands:
+  - name: rollback [release]
+    description: Undo a deploy
+    flags:
+      --to=: Release to roll back to
+    completion:
+      flag:
+        to: ["$_tools.git.Branches"]
+      positional:
+        - ["one\tFirst", "two"]
+      positionalany: ["$directories"]
+completion:
+  flag:
+    output: ["$files([.yaml, .yml])"]
+    format: ["json", "yaml"]
+"#,
+        )
+        .unwrap();
+        let spec = import.spec;
+        let output = flag(&spec, "output");
+        assert_eq!(output.short, Some('o'));
+        assert!(output.takes_value);
+        match &output.value_type {
+            Some(ArgumentType::Path { filter }) => {
+                assert_eq!(filter.extensions, Some(vec!["yaml".into(), "yml".into()]))
+            }
+            other => panic!("expected path, got {:?}", other),
+        }
+        assert!(matches!(
+            &flag(&spec, "format").value_type,
+            Some(ArgumentType::Choice { values }) if values == &["json", "yaml"]
+        ));
+
+        let rollback = sub(&spec, "rollback");
+        assert!(flag(rollback, "verbose").short == Some('v'));
+        assert!(matches!(
+            &flag(rollback, "to").value_type,
+            Some(ArgumentType::Provider { name }) if name == "git_branch"
+        ));
+        assert!(matches!(
+            &rollback.arguments[0].arg_type,
+            ArgumentType::Choice { values } if values == &["one", "two"]
+        ));
+        assert!(rollback.arguments[1].variadic);
+        assert!(import.notes.iter().any(|n| n.contains("repeatable")));
+    }
+
+    #[test]
+    fn test_import_fish() {
+        let import = import(
+            ImportFormat::Fish,
+            r#"
+# deploy completions
+complete -c deploy -s v -l verbose -d 'Verbose output'
+complete -c deploy -f -n '__fish_use_subcommand' -a 'rollback status' -d 'Subcommand'
+complete -c deploy -n '__fish_seen_subcommand_from rollback' -l to -x -a '(__fish_git_branches)' -d 'Release'
+complete -c deploy -n '__fish_seen_subcommand_from status' -l format -x -a "json text" -d "Format"
+complete -c deploy -n '__fish_seen_subcommand_from status' -l out -r -F -d "Output \"file\""
+complete -c deploy -n '__fish_contains_opt verbose' -l quiet -d 'Quiet'
+"#,
+        )
+        .unwrap();
+        let spec = import.spec;
+        assert_eq!(spec.name, "deploy");
+        assert_eq!(flag(&spec, "verbose").short, Some('v'));
+        assert_eq!(spec.subcommands.len(), 2);
+
+        let rollback = sub(&spec, "rollback");
+        assert!(matches!(
+            &flag(rollback, "to").value_type,
+            Some(ArgumentType::Provider { name }) if name == "git_branch"
+        ));
+        let status = sub(&spec, "status");
+        assert!(matches!(
+            &flag(status, "format").value_type,
+            Some(ArgumentType::Choice { values }) if values == &["json", "text"]
+        ));
+        assert_eq!(flag(status, "out").description.get("en"), "Output \"file\"");
+        assert!(matches!(
+            flag(status, "out").value_type,
+            Some(ArgumentType::Path { .. })
+        ));
+
+        // Unsupported conditions still keep the flag, on the root
+        assert!(flag(&spec, "quiet").long.is_some());
+        assert!(import
+            .notes
+            .iter()
+            .any(|n| n.contains("__fish_contains_opt")));
+    }
+}
@@ -0,0 +1,857 @@
+//! Job Control
+//!
+//! Keeps track of jobs started by the executor so they can be managed
+//! without leaving the REPL:
+//! - A process group per job, with the terminal handed to the foreground job
+//! - Ctrl-Z (SIGTSTP) suspends the foreground job into the job table
+//! - `jobs`, `fg %n`, `bg %n` and `wait` builtins
+//! - Notices for jobs that finished, printed just before the next prompt
+//! - Foreground timeouts: SIGTERM to the job's process group, then SIGKILL
+//!
+//! Process groups, terminal hand-over and suspension are Unix only; on other
+//! platforms background jobs are still tracked and can be waited for.
+
+#![allow(dead_code)]
+
+use std::io::{IsTerminal, Write};
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+/// Builtins implemented by the job table
+pub const JOB_BUILTINS: &[&str] = &["jobs", "fg", "bg", "wait"];
+
+/// Exit status reported for a command that was suspended
+#[cfg(unix)]
+pub const STATUS_STOPPED: i32 = 128 + libc::SIGTSTP;
+#[cfg(not(unix))]
+pub const STATUS_STOPPED: i32 = 148;
+
+/// Exit status reported when `wait` is interrupted by Ctrl-C
+pub const STATUS_INTERRUPTED: i32 = 130;
+
+/// Exit status reported for a job killed by its timeout (as GNU `timeout`)
+pub const STATUS_TIMED_OUT: i32 = 124;
+
+/// Time between SIGTERM and SIGKILL for a job that timed out
+pub const KILL_GRACE: Duration = Duration::from_secs(3);
+
+/// Polling interval while a foreground job has a timeout
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// State of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    Done(i32),
+}
+
+impl JobState {
+    /// Label used by `jobs` and notices
+    pub fn label(&self) -> String {
+        match self {
+            JobState::Running => "Running".to_string(),
+            JobState::Stopped => "Stopped".to_string(),
+            JobState::Done(0) => "Done".to_string(),
+            JobState::Done(code) => format!("Exit {}", code),
+        }
+    }
+}
+
+/// One process of a job
+#[derive(Debug)]
+pub struct JobProcess {
+    pub child: Child,
+    /// Exit status once the process has finished
+    pub status: Option<i32>,
+    /// When the process finished
+    pub finished: Option<Instant>,
+}
+
+impl JobProcess {
+    pub fn new(child: Child) -> Self {
+        Self {
+            child,
+            status: None,
+            finished: None,
+        }
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    fn finish(&mut self, status: i32) {
+        self.status = Some(status);
+        self.finished = Some(Instant::now());
+    }
+}
+
+/// A job: the processes of one pipeline
+pub struct JobEntry {
+    /// Job number shown as `%n` (0 until added to the table)
+    pub id: usize,
+    /// Process group id (the pid of the first process)
+    pub pgid: u32,
+    /// Command text as typed
+    pub command: String,
+    pub processes: Vec<JobProcess>,
+    pub state: JobState,
+    /// Whether the state changed since the user was last told
+    changed: bool,
+    /// Terminal modes saved when the job was suspended
+    tmodes: Option<sys::TermModes>,
+}
+
+impl JobEntry {
+    pub fn new(command: impl Into<String>, processes: Vec<JobProcess>) -> Self {
+        let pgid = processes.first().map(|p| p.pid()).unwrap_or(0);
+        Self {
+            id: 0,
+            pgid,
+            command: command.into(),
+            processes,
+            state: JobState::Running,
+            changed: false,
+            tmodes: None,
+        }
+    }
+
+    /// Record a state change for one of the job's processes
+    fn record(&mut self, pid: u32, event: sys::WaitEvent) {
+        match event {
+            sys::WaitEvent::Exited(status) => {
+                if let Some(process) = self.processes.iter_mut().find(|p| p.pid() == pid) {
+                    process.finish(status);
+                }
+            }
+            sys::WaitEvent::Stopped => {
+                if self.state != JobState::Stopped {
+                    self.state = JobState::Stopped;
+                    self.changed = true;
+                }
+            }
+            sys::WaitEvent::Continued => {
+                if self.state == JobState::Stopped {
+                    self.state = JobState::Running;
+                }
+            }
+        }
+
+        if self.processes.iter().all(|p| p.status.is_some()) {
+            let status = self.processes.last().and_then(|p| p.status).unwrap_or(0);
+            self.state = JobState::Done(status);
+            self.changed = true;
+        }
+    }
+
+    fn owns(&self, pid: u32) -> bool {
+        self.processes.iter().any(|p| p.pid() == pid)
+    }
+
+    /// Format a `jobs` line, e.g. `[1]+  Running    cargo build &`
+    fn format_line(&self, marker: char, long: bool) -> String {
+        let pid = if long {
+            format!("{} ", self.pgid)
+        } else {
+            String::new()
+        };
+        let suffix = if self.state == JobState::Running {
+            " &"
+        } else {
+            ""
+        };
+        format!(
+            "[{}]{}  {}{:<10} {}{}",
+            self.id,
+            marker,
+            pid,
+            self.state.label(),
+            self.command,
+            suffix
+        )
+    }
+}
+
+/// Table of background and suspended jobs
+pub struct JobTable {
+    entries: Vec<JobEntry>,
+    /// Whether process groups and the terminal are managed
+    job_control: bool,
+    shell_pgid: u32,
+    shell_tmodes: Option<sys::TermModes>,
+}
+
+impl Default for JobTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JobTable {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            job_control: false,
+            shell_pgid: 0,
+            shell_tmodes: None,
+        }
+    }
+
+    /// Enable job control if stdin is a terminal
+    ///
+    /// Makes sc ignore the terminal stop signals so it can hand the terminal
+    /// to jobs and take it back. Returns whether job control is active.
+    pub fn enable_job_control(&mut self) -> bool {
+        if let Some(pgid) = sys::enable() {
+            self.job_control = true;
+            self.shell_pgid = pgid;
+            self.shell_tmodes = sys::get_modes();
+        }
+        self.job_control
+    }
+
+    pub fn job_control(&self) -> bool {
+        self.job_control
+    }
+
+    /// Put a command into the job's process group and, for foreground jobs,
+    /// let it take the terminal before it runs
+    ///
+    /// Without job control, `own_group` still gives the job its own process
+    /// group so a timeout can signal all of it, unless stdin is a terminal:
+    /// that group would be in the terminal's background and stop as soon as
+    /// it read from it. There the job stays in sc's group and a timeout
+    /// signals its processes one by one.
+    pub fn configure(
+        &self,
+        cmd: &mut Command,
+        pgid: Option<u32>,
+        foreground: bool,
+        own_group: bool,
+    ) {
+        if self.job_control {
+            sys::configure(cmd, pgid, foreground);
+        } else if own_group && !std::io::stdin().is_terminal() {
+            sys::set_group(cmd, pgid);
+        }
+    }
+
+    /// Hand the terminal to a process group that is not a job (the
+    /// persistent backend shell) while it runs a line
+    pub fn lend_terminal(&self, pgid: u32) {
+        if self.job_control {
+            sys::give_terminal(pgid);
+        }
+    }
+
+    /// Take the terminal back after [`JobTable::lend_terminal`]
+    pub fn reclaim_terminal(&self) {
+        if self.job_control {
+            sys::give_terminal(self.shell_pgid);
+            if let Some(modes) = &self.shell_tmodes {
+                sys::set_modes(modes);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn has_stopped(&self) -> bool {
+        self.entries.iter().any(|e| e.state == JobState::Stopped)
+    }
+
+    pub fn get(&self, id: usize) -> Option<&JobEntry> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    /// Add a job and return its number
+    pub fn add(&mut self, mut entry: JobEntry) -> usize {
+        entry.id = self.entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+        entry.changed = false;
+        let id = entry.id;
+        self.entries.push(entry);
+        id
+    }
+
+    /// Resolve a job spec (`%n`, `n`, `%+`, `%%`, `%-`, `%prefix`) to a job number
+    ///
+    /// Without a spec the current (most recent) job is used.
+    pub fn resolve(&self, spec: Option<&str>) -> Result<usize, String> {
+        let current = || self.entries.last().map(|e| e.id);
+        let id = match spec {
+            None | Some("%") | Some("%%") | Some("%+") => current(),
+            Some("%-") => self
+                .entries
+                .len()
+                .checked_sub(2)
+                .map(|i| self.entries[i].id),
+            Some(spec) => {
+                let spec = spec.strip_prefix('%').unwrap_or(spec);
+                match spec.parse::<usize>() {
+                    Ok(n) => self.get(n).map(|e| e.id),
+                    Err(_) => self
+                        .entries
+                        .iter()
+                        .rev()
+                        .find(|e| e.command.starts_with(spec))
+                        .map(|e| e.id),
+                }
+            }
+        };
+
+        id.ok_or_else(|| match spec {
+            Some(spec) => format!("{}: no such job", spec),
+            None => "no current job".to_string(),
+        })
+    }
+
+    /// Poll jobs without blocking and record finished or stopped processes
+    pub fn update(&mut self) {
+        for entry in &mut self.entries {
+            for (pid, event) in sys::poll(entry) {
+                entry.record(pid, event);
+            }
+        }
+    }
+
+    /// Notices for jobs that changed state; finished jobs leave the table
+    pub fn take_notices(&mut self) -> Vec<String> {
+        self.update();
+
+        let count = self.entries.len();
+        let mut notices = Vec::new();
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            if entry.changed {
+                entry.changed = false;
+                notices.push(entry.format_line(Self::marker(i, count), false));
+            }
+        }
+        self.entries
+            .retain(|e| !matches!(e.state, JobState::Done(_)));
+        notices
+    }
+
+    /// Run a foreground job until it finishes or is suspended
+    ///
+    /// With a `timeout`, the job's process group gets SIGTERM once it
+    /// elapses and SIGKILL [`KILL_GRACE`] later. Returns whether the job
+    /// timed out.
+    pub fn wait_foreground(&mut self, entry: &mut JobEntry, timeout: Option<Duration>) -> bool {
+        if self.job_control {
+            if let Some(modes) = &entry.tmodes {
+                sys::set_modes(modes);
+            }
+            sys::give_terminal(entry.pgid);
+        }
+
+        let mut deadline = timeout.map(|t| Instant::now() + t);
+        let mut timed_out = false;
+        while !matches!(entry.state, JobState::Done(_) | JobState::Stopped) {
+            let Some(at) = deadline else {
+                match sys::wait(entry, self.job_control) {
+                    Some((pid, event)) => entry.record(pid, event),
+                    None if sys::take_interrupt() => continue,
+                    None => break,
+                }
+                continue;
+            };
+
+            for (pid, event) in sys::poll(entry) {
+                entry.record(pid, event);
+            }
+            if Instant::now() < at {
+                std::thread::sleep(POLL_INTERVAL);
+            } else if !timed_out {
+                sys::terminate(entry, false);
+                timed_out = true;
+                deadline = Some(at + KILL_GRACE);
+            } else {
+                sys::terminate(entry, true);
+                deadline = None;
+            }
+        }
+        entry.changed = false;
+
+        if self.job_control {
+            if entry.state == JobState::Stopped {
+                entry.tmodes = sys::get_modes();
+            }
+            sys::give_terminal(self.shell_pgid);
+            if let Some(modes) = &self.shell_tmodes {
+                sys::set_modes(modes);
+            }
+        }
+        timed_out
+    }
+
+    /// Kill a suspended job and reap it
+    ///
+    /// Without job control nothing could resume it, so it must not outlive
+    /// the command that started it.
+    pub fn kill_stopped(&mut self, entry: &mut JobEntry) {
+        sys::terminate(entry, true);
+        while !matches!(entry.state, JobState::Done(_)) {
+            match sys::wait(entry, false) {
+                Some((pid, event)) => entry.record(pid, event),
+                None => break,
+            }
+        }
+    }
+
+    /// Run one of the job builtins
+    pub fn run_builtin(&mut self, argv: &[String], out: &mut dyn Write) -> i32 {
+        let args: Vec<&str> = argv[1..].iter().map(String::as_str).collect();
+        let result = match argv[0].as_str() {
+            "jobs" => self.builtin_jobs(&args, out),
+            "fg" => self.builtin_fg(args.first().copied(), out),
+            "bg" => self.builtin_bg(args.first().copied(), out),
+            "wait" => self.builtin_wait(&args),
+            _ => Err(format!("{}: not a job builtin", argv[0])),
+        };
+
+        result.unwrap_or_else(|e| {
+            crate::output::Output::error(&format!("{}: {}", argv[0], e));
+            1
+        })
+    }
+
+    /// `jobs [-l]`
+    fn builtin_jobs(&mut self, args: &[&str], out: &mut dyn Write) -> Result<i32, String> {
+        let long = args.contains(&"-l");
+        self.update();
+
+        let count = self.entries.len();
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            entry.changed = false;
+            writeln!(out, "{}", entry.format_line(Self::marker(i, count), long)).ok();
+        }
+        self.entries
+            .retain(|e| !matches!(e.state, JobState::Done(_)));
+        Ok(0)
+    }
+
+    /// `fg [%n]` - continue a job in the foreground
+    fn builtin_fg(&mut self, spec: Option<&str>, out: &mut dyn Write) -> Result<i32, String> {
+        let id = self.resolve(spec)?;
+        let index = self.entries.iter().position(|e| e.id == id).unwrap();
+        let mut entry = self.entries.remove(index);
+
+        writeln!(out, "{}", entry.command).ok();
+        out.flush().ok();
+        if entry.state == JobState::Stopped {
+            sys::continue_job(&entry);
+            entry.state = JobState::Running;
+        }
+
+        self.wait_foreground(&mut entry, None);
+
+        match entry.state {
+            JobState::Done(status) => Ok(status),
+            _ => {
+                let status = STATUS_STOPPED;
+                let line = entry.format_line('+', false);
+                self.entries.insert(index, entry);
+                writeln!(out).ok();
+                writeln!(out, "{}", line).ok();
+                Ok(status)
+            }
+        }
+    }
+
+    /// `bg [%n]` - continue a stopped job in the background
+    fn builtin_bg(&mut self, spec: Option<&str>, out: &mut dyn Write) -> Result<i32, String> {
+        if !self.job_control {
+            return Err("no job control".to_string());
+        }
+
+        let id = self.resolve(spec)?;
+        let entry = self.entries.iter_mut().find(|e| e.id == id).unwrap();
+        if entry.state != JobState::Stopped {
+            return Err(format!("job {} already in background", id));
+        }
+
+        sys::continue_job(entry);
+        entry.state = JobState::Running;
+        writeln!(out, "[{}]+ {} &", entry.id, entry.command).ok();
+        Ok(0)
+    }
+
+    /// `wait [%n ...]` - wait for running jobs to finish
+    fn builtin_wait(&mut self, args: &[&str]) -> Result<i32, String> {
+        let ids = if args.is_empty() {
+            self.entries
+                .iter()
+                .filter(|e| e.state == JobState::Running)
+                .map(|e| e.id)
+                .collect()
+        } else {
+            args.iter()
+                .map(|spec| self.resolve(Some(spec)))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let mut status = 0;
+        for id in ids {
+            let Some(entry) = self.entries.iter_mut().find(|e| e.id == id) else {
+                continue;
+            };
+            while entry.state == JobState::Running {
+                match sys::wait(entry, false) {
+                    Some((pid, event)) => entry.record(pid, event),
+                    None if sys::take_interrupt() => return Ok(STATUS_INTERRUPTED),
+                    None => break,
+                }
+            }
+            status = match entry.state {
+                JobState::Done(code) => code,
+                _ => STATUS_STOPPED,
+            };
+        }
+
+        // Waited-for jobs are reported by `wait` itself
+        for entry in &mut self.entries {
+            entry.changed = false;
+        }
+        self.entries
+            .retain(|e| !matches!(e.state, JobState::Done(_)));
+        Ok(status)
+    }
+
+    /// `+` marks the current job, `-` the previous one
+    fn marker(index: usize, count: usize) -> char {
+        if index + 1 == count {
+            '+'
+        } else if index + 2 == count {
+            '-'
+        } else {
+            ' '
+        }
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use super::JobEntry;
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    pub type TermModes = libc::termios;
+
+    /// A state change reported by `waitpid`
+    #[derive(Debug, Clone, Copy)]
+    pub enum WaitEvent {
+        Exited(i32),
+        Stopped,
+        Continued,
+    }
+
+    static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn on_sigint(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+
+    /// Set up the shell for job control; returns its process group
+    pub fn enable() -> Option<u32> {
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) != 1 {
+                return None;
+            }
+
+            libc::signal(libc::SIGTSTP, libc::SIG_IGN);
+            libc::signal(libc::SIGTTIN, libc::SIG_IGN);
+            libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+
+            // No SA_RESTART, so a blocking `wait` returns on Ctrl-C
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_sigint as extern "C" fn(libc::c_int) as usize;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+
+            // Lead our own process group and own the terminal
+            let pid = libc::getpid();
+            if libc::getpgrp() != pid {
+                libc::setpgid(0, 0);
+            }
+            let pgid = libc::getpgrp();
+            libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+            Some(pgid as u32)
+        }
+    }
+
+    pub fn take_interrupt() -> bool {
+        INTERRUPTED.swap(false, Ordering::SeqCst)
+    }
+
+    pub fn configure(cmd: &mut Command, pgid: Option<u32>, foreground: bool) {
+        cmd.process_group(pgid.unwrap_or(0) as i32);
+        unsafe {
+            cmd.pre_exec(move || {
+                if foreground {
+                    libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+                }
+                for signal in [
+                    libc::SIGINT,
+                    libc::SIGQUIT,
+                    libc::SIGTSTP,
+                    libc::SIGTTIN,
+                    libc::SIGTTOU,
+                ] {
+                    libc::signal(signal, libc::SIG_DFL);
+                }
+                Ok(())
+            });
+        }
+    }
+
+    pub fn set_group(cmd: &mut Command, pgid: Option<u32>) {
+        cmd.process_group(pgid.unwrap_or(0) as i32);
+    }
+
+    /// Signal the job: SIGTERM, or SIGKILL when `kill`
+    ///
+    /// A job in its own process group gets the signal as a group; one left
+    /// in sc's group (no job control on a terminal) per process.
+    pub fn terminate(entry: &JobEntry, kill: bool) {
+        let signal = if kill { libc::SIGKILL } else { libc::SIGTERM };
+        let pgid = entry.pgid as libc::pid_t;
+        let running = entry.processes.iter().filter(|p| p.status.is_none());
+        let grouped = running
+            .clone()
+            .any(|p| unsafe { libc::getpgid(p.pid() as libc::pid_t) } == pgid);
+        unsafe {
+            if grouped {
+                libc::killpg(pgid, signal);
+                // A stopped job only acts on SIGTERM once continued
+                libc::killpg(pgid, libc::SIGCONT);
+            } else {
+                for process in running {
+                    libc::kill(process.pid() as libc::pid_t, signal);
+                    libc::kill(process.pid() as libc::pid_t, libc::SIGCONT);
+                }
+            }
+        }
+    }
+
+    pub fn give_terminal(pgid: u32) {
+        unsafe {
+            libc::tcsetpgrp(libc::STDIN_FILENO, pgid as libc::pid_t);
+        }
+    }
+
+    pub fn get_modes() -> Option<TermModes> {
+        unsafe {
+            let mut modes: TermModes = std::mem::zeroed();
+            (libc::tcgetattr(libc::STDIN_FILENO, &mut modes) == 0).then_some(modes)
+        }
+    }
+
+    pub fn set_modes(modes: &TermModes) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, modes);
+        }
+    }
+
+    pub fn continue_job(entry: &JobEntry) {
+        unsafe {
+            libc::killpg(entry.pgid as libc::pid_t, libc::SIGCONT);
+        }
+    }
+
+    fn decode(status: libc::c_int) -> Option<WaitEvent> {
+        if libc::WIFEXITED(status) {
+            Some(WaitEvent::Exited(libc::WEXITSTATUS(status)))
+        } else if libc::WIFSIGNALED(status) {
+            Some(WaitEvent::Exited(128 + libc::WTERMSIG(status)))
+        } else if libc::WIFSTOPPED(status) {
+            Some(WaitEvent::Stopped)
+        } else if libc::WIFCONTINUED(status) {
+            Some(WaitEvent::Continued)
+        } else {
+            None
+        }
+    }
+
+    /// Block until one of the job's processes changes state
+    ///
+    /// Returns `None` when interrupted or when there is nothing left to wait for.
+    pub fn wait(entry: &mut JobEntry, by_group: bool) -> Option<(u32, WaitEvent)> {
+        loop {
+            let target = if by_group {
+                -(entry.pgid as libc::pid_t)
+            } else {
+                entry.processes.iter().find(|p| p.status.is_none())?.pid() as libc::pid_t
+            };
+
+            let mut status = 0;
+            let pid = unsafe { libc::waitpid(target, &mut status, libc::WUNTRACED) };
+            if pid > 0 {
+                if let Some(event) = decode(status) {
+                    return Some((pid as u32, event));
+                }
+                continue;
+            }
+
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::EINTR) {
+                INTERRUPTED.store(true, Ordering::SeqCst);
+                return None;
+            }
+
+            // Reaped elsewhere: nothing more to learn about these processes
+            let pid = entry.processes.iter().find(|p| p.status.is_none())?.pid();
+            return Some((pid, WaitEvent::Exited(0)));
+        }
+    }
+
+    /// Collect state changes without blocking
+    pub fn poll(entry: &mut JobEntry) -> Vec<(u32, WaitEvent)> {
+        let mut events = Vec::new();
+        for process in entry.processes.iter().filter(|p| p.status.is_none()) {
+            let mut status = 0;
+            let pid = unsafe {
+                libc::waitpid(
+                    process.pid() as libc::pid_t,
+                    &mut status,
+                    libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED,
+                )
+            };
+            if pid > 0 {
+                if let Some(event) = decode(status) {
+                    events.push((pid as u32, event));
+                }
+            }
+        }
+        events
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    use super::JobEntry;
+    use crate::executor::status_code;
+    use std::process::Command;
+
+    pub type TermModes = ();
+
+    #[derive(Debug, Clone, Copy)]
+    pub enum WaitEvent {
+        Exited(i32),
+        Stopped,
+        Continued,
+    }
+
+    pub fn enable() -> Option<u32> {
+        None
+    }
+
+    pub fn take_interrupt() -> bool {
+        false
+    }
+
+    pub fn configure(_cmd: &mut Command, _pgid: Option<u32>, _foreground: bool) {}
+
+    pub fn set_group(_cmd: &mut Command, _pgid: Option<u32>) {}
+
+    /// No process groups here: kill each process of the job
+    pub fn terminate(entry: &mut JobEntry, _kill: bool) {
+        for process in entry.processes.iter_mut().filter(|p| p.status.is_none()) {
+            let _ = process.child.kill();
+        }
+    }
+
+    pub fn give_terminal(_pgid: u32) {}
+
+    pub fn get_modes() -> Option<TermModes> {
+        None
+    }
+
+    pub fn set_modes(_modes: &TermModes) {}
+
+    pub fn continue_job(_entry: &JobEntry) {}
+
+    pub fn wait(entry: &mut JobEntry, _by_group: bool) -> Option<(u32, WaitEvent)> {
+        let process = entry.processes.iter_mut().find(|p| p.status.is_none())?;
+        let status = process.child.wait().map(status_code).unwrap_or(1);
+        Some((process.pid(), WaitEvent::Exited(status)))
+    }
+
+    pub fn poll(entry: &mut JobEntry) -> Vec<(u32, WaitEvent)> {
+        entry
+            .processes
+            .iter_mut()
+            .filter(|p| p.status.is_none())
+            .filter_map(|p| match p.child.try_wait() {
+                Ok(Some(status)) => Some((p.pid(), WaitEvent::Exited(status_code(status)))),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawn(program: &str, args: &[&str]) -> JobEntry {
+        let child = Command::new(program).args(args).spawn().unwrap();
+        JobEntry::new(
+            format!("{} {}", program, args.join(" ")),
+            vec![JobProcess::new(child)],
+        )
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_job_specs() {
+        let mut table = JobTable::new();
+        let first = table.add(spawn("sleep", &["0"]));
+        let second = table.add(spawn("true", &[]));
+
+        assert_eq!(table.resolve(None), Ok(second));
+        assert_eq!(table.resolve(Some("%+")), Ok(second));
+        assert_eq!(table.resolve(Some("%-")), Ok(first));
+        assert_eq!(table.resolve(Some("%1")), Ok(first));
+        assert_eq!(table.resolve(Some("2")), Ok(second));
+        assert_eq!(table.resolve(Some("%sle")), Ok(first));
+        assert!(table.resolve(Some("%9")).is_err());
+
+        let mut out = Vec::new();
+        assert_eq!(table.run_builtin(&["wait".to_string()], &mut out), 0);
+        assert!(table.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_finished_job_notice() {
+        let mut table = JobTable::new();
+        let id = table.add(spawn("sh", &["-c", "exit 3"]));
+
+        let mut entry = table.entries.pop().unwrap();
+        table.wait_foreground(&mut entry, None);
+        assert_eq!(entry.state, JobState::Done(3));
+
+        entry.changed = true;
+        table.entries.push(entry);
+        let notices = table.take_notices();
+        assert_eq!(notices.len(), 1);
+        assert!(notices[0].starts_with(&format!("[{}]+", id)));
+        assert!(notices[0].contains("Exit 3"));
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn test_state_labels() {
+        assert_eq!(JobState::Running.label(), "Running");
+        assert_eq!(JobState::Done(0).label(), "Done");
+        assert_eq!(JobState::Done(2).label(), "Exit 2");
+    }
+}
@@ -0,0 +1,626 @@
+//! Definition linting for `sc defs lint`
+//!
+//! Checks definition files for problems the YAML parser does not catch:
+//! - a flag name defined twice, or a `short` shared by two flags
+//! - `pattern` regexes that do not compile
+//! - provider names that no registered provider answers to
+//! - `use`, `extends`, `conflicts_with` and `requires` naming nothing in the file
+//! - `since`/`until` that are not versions, or that no `version_command` can check
+//! - `wraps` below the top level, where it is never used (warning)
+//! - descriptions without a `zh` translation (warning)
+//!
+//! Parsed specs carry no positions, so the line of each piece is found by
+//! searching the source for the key that introduced it, in document order.
+
+use crate::command_def::{ArgumentType, CommandSpec, FlagSpec, I18nString};
+use crate::loader;
+use crate::versions::Version;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// A problem found in a definition file
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    /// 1-based line number
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Lint every yaml file in the given files and directories
+pub fn lint_paths(paths: &[PathBuf], providers: &[&str]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for file in yaml_files(paths) {
+        match fs::read_to_string(&file) {
+            Ok(source) => diagnostics.extend(lint_source(&file, &source, providers)),
+            Err(e) => diagnostics.push(Diagnostic {
+                file,
+                line: 1,
+                severity: Severity::Error,
+                message: format!("cannot read file: {}", e),
+            }),
+        }
+    }
+    diagnostics
+}
+
+/// Expand directories into their yaml files, sorted by name
+pub fn yaml_files(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            files.extend(loader::yaml_files(path));
+        } else {
+            files.push(path.clone());
+        }
+    }
+    files
+}
+
+/// Lint the source of one definition file
+pub fn lint_source(file: &Path, source: &str, providers: &[&str]) -> Vec<Diagnostic> {
+    let spec = match serde_yaml::from_str::<CommandSpec>(source) {
+        Ok(spec) => spec,
+        Err(e) => {
+            return vec![Diagnostic {
+                file: file.to_path_buf(),
+                line: e.location().map(|l| l.line()).unwrap_or(1),
+                severity: Severity::Error,
+                message: e.to_string(),
+            }]
+        }
+    };
+
+    let mut linter = Linter {
+        file,
+        lines: source.lines().collect(),
+        providers,
+        diagnostics: Vec::new(),
+    };
+    let line = linter.find(0, "name", &spec.name).unwrap_or(0);
+    linter.spec(&spec, &spec.name, line, &[]);
+    linter.diagnostics.sort_by_key(|d| d.line);
+    linter.diagnostics
+}
+
+struct Linter<'a> {
+    file: &'a Path,
+    lines: Vec<&'a str>,
+    providers: &'a [&'a str],
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    /// Index of the first line at or after `from` that reads `key: value`
+    fn find(&self, from: usize, key: &str, value: &str) -> Option<usize> {
+        self.lines
+            .iter()
+            .enumerate()
+            .skip(from)
+            .find(|(_, line)| {
+                let entry = line.trim_start().trim_start_matches("- ").trim_start();
+                entry.split_once(':').is_some_and(|(k, v)| {
+                    k.trim() == key && v.trim().trim_matches(|c| c == '"' || c == '\'') == value
+                })
+            })
+            .map(|(i, _)| i)
+    }
+
+    fn report(&mut self, line: usize, severity: Severity, message: String) {
+        self.diagnostics.push(Diagnostic {
+            file: self.file.to_path_buf(),
+            line: line + 1,
+            severity,
+            message,
+        });
+    }
+
+    fn spec(&mut self, spec: &CommandSpec, path: &str, line: usize, parents: &[&CommandSpec]) {
+        self.translation(&spec.description, line, path);
+        self.references(spec, path, line, parents);
+        let root = parents.first().copied().unwrap_or(spec);
+        if parents.is_empty() {
+            if let Some(pattern) = &spec.version_regex {
+                self.arg_type(
+                    &ArgumentType::Pattern {
+                        regex: pattern.clone(),
+                    },
+                    line,
+                    &format!("{} version_regex", path),
+                );
+            }
+        }
+        self.versions(root, &spec.since, &spec.until, line, path);
+        if !parents.is_empty() && (spec.wraps || spec.wraps_after.is_some()) {
+            self.report(
+                line,
+                Severity::Warning,
+                format!("{}: wraps only applies to top level commands", path),
+            );
+        }
+
+        let mut cursor = line;
+        let mut longs: HashMap<&str, usize> = HashMap::new();
+        let mut shorts: HashMap<char, String> = HashMap::new();
+        for flag in &spec.flags {
+            let found = match (&flag.long, flag.short) {
+                (Some(long), _) => self.find(cursor + 1, "long", long),
+                (None, Some(short)) => self.find(cursor + 1, "short", &short.to_string()),
+                (None, None) => None,
+            };
+            let flag_line = found.unwrap_or(cursor);
+            cursor = flag_line;
+            let name = format!("{} {}", path, flag_name(flag));
+
+            if let Some(long) = &flag.long {
+                if let Some(first) = longs.insert(long, flag_line) {
+                    self.report(
+                        flag_line,
+                        Severity::Error,
+                        format!("{}: defined twice (first on line {})", name, first + 1),
+                    );
+                }
+            }
+            if let Some(short) = flag.short {
+                if let Some(other) = shorts.insert(short, flag_name(flag)) {
+                    self.report(
+                        flag_line,
+                        Severity::Error,
+                        format!("{}: -{} is also used by {}", name, short, other),
+                    );
+                }
+            }
+            if flag.long.is_none() && flag.short.is_none() {
+                self.report(
+                    flag_line,
+                    Severity::Error,
+                    format!("{}: flag has neither long nor short name", path),
+                );
+            }
+
+            self.translation(&flag.description, flag_line, &name);
+            if let Some(value_type) = &flag.value_type {
+                self.arg_type(value_type, flag_line, &name);
+            }
+            self.flag_references(flag, spec, parents, flag_line, &name);
+            self.versions(root, &flag.since, &flag.until, flag_line, &name);
+        }
+
+        let mut cursor = line;
+        for arg in &spec.arguments {
+            let arg_line = arg
+                .name
+                .as_deref()
+                .and_then(|name| self.find(cursor + 1, "name", name))
+                .unwrap_or(cursor);
+            cursor = arg_line;
+            let name = format!(
+                "{} <{}>",
+                path,
+                arg.name
+                    .clone()
+                    .or_else(|| arg.position.map(|p| format!("#{}", p)))
+                    .unwrap_or_else(|| "arg".to_string())
+            );
+
+            if let Some(description) = &arg.description {
+                self.translation(description, arg_line, &name);
+            }
+            if let Some(provider) = &arg.provider {
+                self.provider(provider, arg_line, &name);
+            }
+            self.arg_type(&arg.arg_type, arg_line, &name);
+        }
+
+        let mut cursor = line;
+        for example in &spec.examples {
+            let example_line = self.find(cursor + 1, "cmd", &example.cmd).unwrap_or(cursor);
+            cursor = example_line;
+            self.translation(
+                &example.scenario,
+                example_line,
+                &format!("{} example `{}`", path, example.cmd),
+            );
+        }
+
+        let mut cursor = line;
+        for sub in &spec.subcommands {
+            let sub_line = self.find(cursor + 1, "name", &sub.name).unwrap_or(cursor);
+            cursor = sub_line;
+            let scope: Vec<&CommandSpec> = parents.iter().copied().chain([spec]).collect();
+            self.spec(sub, &format!("{} {}", path, sub.name), sub_line, &scope);
+        }
+    }
+
+    /// `conflicts_with` and `requires` names that match no flag of the spec
+    ///
+    /// Skipped when some of the spec's flags come from another file.
+    fn flag_references(
+        &mut self,
+        flag: &FlagSpec,
+        spec: &CommandSpec,
+        parents: &[&CommandSpec],
+        line: usize,
+        name: &str,
+    ) {
+        if spec.extends.is_some() || spec.uses.iter().any(|set| set.contains('.')) {
+            return;
+        }
+        let from_sets = spec.uses.iter().filter_map(|set| {
+            std::iter::once(spec)
+                .chain(parents.iter().copied())
+                .find_map(|scope| scope.flag_sets.get(set))
+        });
+        let known: Vec<&FlagSpec> = spec.flags.iter().chain(from_sets.flatten()).collect();
+        for (key, names) in [
+            ("conflicts_with", &flag.conflicts_with),
+            ("requires", &flag.requires),
+        ] {
+            for other in names {
+                if !known.iter().any(|f| f.is_named(other)) {
+                    self.report(
+                        line,
+                        Severity::Error,
+                        format!("{}: {} names unknown flag `{}`", name, key, other),
+                    );
+                }
+            }
+        }
+    }
+
+    /// `use` and `extends` targets that can be checked within this file
+    ///
+    /// Qualified set names and paths under other commands live in other
+    /// files and are only checked when definitions are loaded.
+    fn references(
+        &mut self,
+        spec: &CommandSpec,
+        path: &str,
+        line: usize,
+        parents: &[&CommandSpec],
+    ) {
+        for name in spec.uses.iter().filter(|name| !name.contains('.')) {
+            let known = std::iter::once(spec)
+                .chain(parents.iter().copied())
+                .any(|scope| {
+                    scope.flag_sets.contains_key(name) || scope.argument_sets.contains_key(name)
+                });
+            if !known {
+                self.report(
+                    line,
+                    Severity::Error,
+                    format!("{}: unknown set `{}`", path, name),
+                );
+            }
+        }
+
+        let Some(target) = &spec.extends else {
+            return;
+        };
+        let root = parents.first().copied().unwrap_or(spec);
+        let mut parts = target.split_whitespace();
+        if parts.next() != Some(root.name.as_str()) {
+            return;
+        }
+        let mut found = Some(root);
+        for part in parts {
+            found = found.and_then(|s| s.subcommands.iter().find(|sub| sub.name == part));
+        }
+        if found.is_none() {
+            self.report(
+                line,
+                Severity::Error,
+                format!("{}: `extends: {}` not found", path, target),
+            );
+        }
+    }
+
+    /// `since` and `until` bounds that are not versions or cannot be checked
+    fn versions(
+        &mut self,
+        root: &CommandSpec,
+        since: &Option<String>,
+        until: &Option<String>,
+        line: usize,
+        name: &str,
+    ) {
+        for (key, bound) in [("since", since), ("until", until)] {
+            let Some(bound) = bound else {
+                continue;
+            };
+            if Version::parse(bound).is_none() {
+                self.report(
+                    line,
+                    Severity::Error,
+                    format!("{}: {} `{}` is not a version", name, key, bound),
+                );
+            } else if root.version_command.is_none() {
+                self.report(
+                    line,
+                    Severity::Warning,
+                    format!("{}: {} is ignored without a version_command", name, key),
+                );
+            }
+        }
+        let parsed = |bound: &Option<String>| bound.as_deref().and_then(Version::parse);
+        if let (Some(since), Some(until)) = (parsed(since), parsed(until)) {
+            if since >= until {
+                self.report(
+                    line,
+                    Severity::Error,
+                    format!("{}: since is not before until", name),
+                );
+            }
+        }
+    }
+
+    fn arg_type(&mut self, arg_type: &ArgumentType, line: usize, name: &str) {
+        match arg_type {
+            ArgumentType::Pattern { regex } => {
+                if let Err(e) = Regex::new(regex) {
+                    let reason = e.to_string();
+                    let reason = reason.lines().last().unwrap_or_default().to_string();
+                    self.report(
+                        line,
+                        Severity::Error,
+                        format!("{}: pattern `{}` does not compile: {}", name, regex, reason),
+                    );
+                }
+            }
+            ArgumentType::Provider { name: provider } => self.provider(provider, line, name),
+            ArgumentType::Choice { values } if values.is_empty() => {
+                self.report(line, Severity::Error, format!("{}: choice has no values", name));
+            }
+            _ => {}
+        }
+    }
+
+    fn provider(&mut self, provider: &str, line: usize, name: &str) {
+        if !self.providers.contains(&provider) {
+            self.report(
+                line,
+                Severity::Error,
+                format!("{}: unknown provider `{}`", name, provider),
+            );
+        }
+    }
+
+    fn translation(&mut self, text: &I18nString, line: usize, name: &str) {
+        let translated = match text {
+            I18nString::Simple(_) => false,
+            I18nString::Map(map) => map.contains_key("zh"),
+        };
+        if !translated {
+            self.report(
+                line,
+                Severity::Warning,
+                format!("{}: missing zh translation", name),
+            );
+        }
+    }
+}
+
+fn flag_name(flag: &FlagSpec) -> String {
+    match (&flag.long, flag.short) {
+        (Some(long), _) => format!("--{}", long),
+        (None, Some(short)) => format!("-{}", short),
+        (None, None) => "-".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROVIDERS: &[&str] = &["git_branch", "path"];
+
+    fn lint(source: &str) -> Vec<(usize, Severity, String)> {
+        lint_source(Path::new("test.yaml"), source, PROVIDERS)
+            .into_iter()
+            .map(|d| (d.line, d.severity, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_flag_conflicts() {
+        let diagnostics = lint(
+            r#"name: tool
+description:
+  en: Tool
+  zh: 工具
+flags:
+  - long: verbose
+    short: v
+    description: { en: Verbose, zh: 详细 }
+  - long: version
+    short: v
+    description: { en: Version, zh: 版本 }
+  - long: verbose
+    description: { en: Again, zh: 再次 }
+    conflicts_with: [version, quiet]
+"#,
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                (
+                    9,
+                    Severity::Error,
+                    "tool --version: -v is also used by --verbose".to_string()
+                ),
+                (
+                    12,
+                    Severity::Error,
+                    "tool --verbose: defined twice (first on line 6)".to_string()
+                ),
+                (
+                    12,
+                    Severity::Error,
+                    "tool --verbose: conflicts_with names unknown flag `quiet`".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_patterns_and_providers() {
+        let diagnostics = lint(
+            r#"name: tool
+description: { en: Tool, zh: 工具 }
+subcommands:
+  - name: run
+    description: { en: Run, zh: 运行 }
+    arguments:
+      - name: branch
+        provider: git_branches
+      - name: id
+        type:
+          type: pattern
+          regex: "[a-z"
+"#,
+        );
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].0, 7);
+        assert!(diagnostics[0].2.contains("unknown provider `git_branches`"));
+        assert_eq!(diagnostics[1].0, 9);
+        assert!(diagnostics[1].2.starts_with("tool run <id>: pattern `[a-z` does not compile"));
+    }
+
+    #[test]
+    fn test_unknown_references() {
+        let diagnostics = lint(
+            r#"name: tool
+description: { en: Tool, zh: 工具 }
+flag_sets:
+  common:
+    - long: verbose
+      description: { en: Verbose, zh: 详细 }
+subcommands:
+  - name: run
+    description: { en: Run, zh: 运行 }
+    use: [common, other.common, missing]
+  - name: go
+    description: { en: Go, zh: 去 }
+    extends: tool walk
+"#,
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                (
+                    8,
+                    Severity::Error,
+                    "tool run: unknown set `missing`".to_string()
+                ),
+                (
+                    11,
+                    Severity::Error,
+                    "tool go: `extends: tool walk` not found".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_version_bounds() {
+        let diagnostics = lint(
+            r#"name: tool
+description: { en: Tool, zh: 工具 }
+version_regex: 'v(\d+'
+subcommands:
+  - name: new
+    description: { en: New, zh: 新 }
+    since: "2.23"
+    until: "2.0"
+  - name: old
+    description: { en: Old, zh: 旧 }
+    until: latest
+"#,
+        );
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.2.as_str()).collect();
+        assert!(messages[0].starts_with("tool version_regex: pattern `v(\\d+` does not"));
+        assert_eq!(
+            messages[1..],
+            [
+                "tool new: since is ignored without a version_command",
+                "tool new: until is ignored without a version_command",
+                "tool new: since is not before until",
+                "tool old: until `latest` is not a version",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_wraps() {
+        let diagnostics = lint(
+            r#"name: tool
+description: { en: Tool, zh: 工具 }
+wraps: true
+subcommands:
+  - name: exec
+    description: { en: Exec, zh: 执行 }
+    wraps_after: 1
+"#,
+        );
+        assert_eq!(
+            diagnostics,
+            [(
+                5,
+                Severity::Warning,
+                "tool exec: wraps only applies to top level commands".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_missing_translation_is_a_warning() {
+        let diagnostics = lint("name: tool\ndescription: Tool\n");
+        assert_eq!(
+            diagnostics,
+            vec![(
+                1,
+                Severity::Warning,
+                "tool: missing zh translation".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_error_has_line() {
+        let diagnostics = lint("name: tool\ndescription: Tool\nflags: 3\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].0, 3);
+        assert_eq!(diagnostics[0].1, Severity::Error);
+    }
+
+    #[test]
+    fn test_bundled_definitions_have_no_errors() {
+        let registry = crate::providers::registry();
+        let providers = registry.provider_names();
+        let errors: Vec<String> = lint_paths(&[PathBuf::from("definitions")], &providers)
+            .into_iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| format!("{}:{}: {}", d.file.display(), d.line, d.message))
+            .collect();
+        assert!(errors.is_empty(), "{}", errors.join("\n"));
+    }
+}
@@ -1,82 +1,980 @@
-use crate::command_def::CommandSpec;
+//! Layered loading of command definitions
+//!
+//! Definitions are read from every layer that exists, lowest priority first:
+//! - System: `/usr/local/share/smart-command/definitions`, `/usr/share/smart-command/definitions`
+//! - User: `~/.config/smart-command/definitions` and the platform config dir
+//! - Bundled: `definitions/` next to the executable
+//! - Local: `./definitions`
+//! - Custom: `--definitions` / `definitions_dir` from config
+//! - Project: `.smart-command/definitions` between the cwd and its git root
+//!
+//! Specs with the same command name are merged rather than replaced: a later
+//! layer overrides scalar fields and adds or overrides subcommands (by name),
+//! flags (by long/short), examples (by cmd) and arguments (by name/position).
+//! The file that supplied each piece is recorded so `sc defs which` can show it.
+//!
+//! After merging, `use:` and `extends:` are expanded so the rest of sc only
+//! ever sees plain specs. `use: [kube]` copies the flags and arguments of the
+//! `kube` set defined on the spec or one of its parents; `use: [kubectl.kube]`
+//! names a set at the top of another command. `extends: kubectl get` starts
+//! from a copy of that spec and layers the spec's own content on top.
+//!
+//! The project layer depends on the current directory, so the REPL merges it
+//! separately (see [`project_layer`] and [`overlay_layer`]) and drops it again
+//! when the user leaves the project.
+
+use crate::catalog::{self, Catalog};
+use crate::command_def::{ArgumentSpec, CommandSpec, Example, FlagSpec};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Where a definitions layer comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerKind {
+    System,
+    User,
+    Bundled,
+    Local,
+    Custom,
+    Project,
+}
+
+impl LayerKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LayerKind::System => "system",
+            LayerKind::User => "user",
+            LayerKind::Bundled => "bundled",
+            LayerKind::Local => "local",
+            LayerKind::Custom => "custom",
+            LayerKind::Project => "project",
+        }
+    }
+
+    /// Whether the layer comes with the directory sc runs in, like a cloned
+    /// repository, rather than being installed by the user
+    pub fn comes_with_checkout(&self) -> bool {
+        matches!(self, LayerKind::Local | LayerKind::Project)
+    }
+}
+
+/// A definitions directory and its place in the layer order
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub kind: LayerKind,
+    pub dir: PathBuf,
+}
+
+/// The file a definition piece was read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    pub kind: LayerKind,
+    pub file: PathBuf,
+}
 
-/// Find the definitions directory from multiple candidate paths
-fn find_definitions_dir() -> Option<PathBuf> {
+/// Existing definition layers, lowest priority first, without duplicates
+///
+/// Directories are made absolute, so a list resolved once keeps naming the
+/// same layers after a `cd`.
+pub fn definition_layers<P: AsRef<Path>>(custom_dir: P) -> Vec<Layer> {
     let candidates = [
-        // 1. Current working directory
-        std::env::current_dir().ok().map(|p| p.join("definitions")),
-        // 2. Executable directory (for installed binaries)
-        std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.join("definitions"))),
-        // 3. User config directory
-        dirs::config_dir().map(|p| p.join("smart-command").join("definitions")),
-        // 4. Home directory config
-        dirs::home_dir().map(|p| p.join(".config").join("smart-command").join("definitions")),
-        // 5. System-wide directory (Unix)
-        Some(PathBuf::from("/usr/share/smart-command/definitions")),
-        // 6. Local system directory (Unix)
-        Some(PathBuf::from("/usr/local/share/smart-command/definitions")),
+        (
+            LayerKind::System,
+            Some(PathBuf::from("/usr/local/share/smart-command/definitions")),
+        ),
+        (
+            LayerKind::System,
+            Some(PathBuf::from("/usr/share/smart-command/definitions")),
+        ),
+        (
+            LayerKind::User,
+            dirs::home_dir().map(|p| p.join(".config").join("smart-command").join("definitions")),
+        ),
+        (
+            LayerKind::User,
+            dirs::config_dir().map(|p| p.join("smart-command").join("definitions")),
+        ),
+        (
+            LayerKind::Bundled,
+            std::env::current_exe()
+                .ok()
+                .and_then(|p| p.parent().map(|p| p.join("definitions"))),
+        ),
+        (
+            LayerKind::Local,
+            std::env::current_dir().ok().map(|p| p.join("definitions")),
+        ),
+        (LayerKind::Custom, Some(custom_dir.as_ref().to_path_buf())),
     ];
 
-    candidates
-        .into_iter()
-        .flatten()
-        .find(|p| p.exists() && p.is_dir())
-}
-
-/// Load commands from a specific directory
-fn load_from_dir<P: AsRef<Path>>(dir: P, commands: &mut Vec<CommandSpec>) -> usize {
-    let mut loaded = 0;
-
-    if let Ok(entries) = fs::read_dir(&dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("yaml") {
-                if let Ok(content) = fs::read_to_string(&path) {
-                    match serde_yaml::from_str::<CommandSpec>(&content) {
-                        Ok(cmd) => {
-                            commands.push(cmd);
-                            loaded += 1;
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to parse {:?}: {}", path, e);
-                        }
-                    }
-                }
+    let mut layers: Vec<Layer> = Vec::new();
+    let mut seen: Vec<PathBuf> = Vec::new();
+    for (kind, dir) in candidates {
+        let Some(dir) = dir else { continue };
+        let dir = std::path::absolute(&dir).unwrap_or(dir);
+        if !dir.is_dir() {
+            continue;
+        }
+        let canonical = dir.canonicalize().unwrap_or_else(|_| dir.clone());
+        if let Some(pos) = seen.iter().position(|p| p == &canonical) {
+            // The same directory reached twice keeps the higher priority slot
+            seen.remove(pos);
+            layers.remove(pos);
+        }
+        seen.push(canonical);
+        layers.push(Layer { kind, dir });
+    }
+    layers
+}
+
+/// Directory holding a project's own definitions, relative to a project dir
+pub const PROJECT_DEFINITIONS_DIR: &str = ".smart-command/definitions";
+
+/// Find the nearest project definitions directory from `cwd` up to its git root
+///
+/// The search stops at the first directory containing `.git`; outside a git
+/// repository only `cwd` itself is checked.
+pub fn find_project_definitions(cwd: &Path) -> Option<PathBuf> {
+    let root = cwd
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(cwd);
+    for dir in cwd.ancestors() {
+        let candidate = dir.join(PROJECT_DEFINITIONS_DIR);
+        if candidate.is_dir() {
+            return Some(candidate);
+        }
+        if dir == root {
+            break;
+        }
+    }
+    None
+}
+
+/// The project layer for `cwd`, if the project has definitions
+pub fn project_layer(cwd: &Path) -> Option<Layer> {
+    find_project_definitions(cwd).map(|dir| Layer {
+        kind: LayerKind::Project,
+        dir,
+    })
+}
+
+/// The yaml files of a definitions directory, sorted by file name
+pub fn yaml_files(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("yaml"))
+            .collect(),
+        Err(_) => return Vec::new(),
+    };
+    paths.sort();
+    paths
+}
+
+/// Read the specs of a layer
+///
+/// A checkout's definitions don't get to run a `version_command` on every
+/// completion, so it is dropped.
+fn load_layer(layer: &Layer) -> Vec<(CommandSpec, PathBuf)> {
+    let mut specs = load_from_dir(&layer.dir);
+    if layer.kind.comes_with_checkout() {
+        for (spec, path) in &mut specs {
+            if spec.version_command.take().is_some() {
+                debug!(
+                    "Ignoring version_command in {} ({} definitions)",
+                    path.display(),
+                    layer.kind.label()
+                );
+            }
+        }
+    }
+    specs
+}
+
+/// Read every yaml file in a directory, sorted by file name
+fn load_from_dir(dir: &Path) -> Vec<(CommandSpec, PathBuf)> {
+    let mut specs = Vec::new();
+    for path in yaml_files(dir) {
+        if let Ok(content) = fs::read_to_string(&path) {
+            match serde_yaml::from_str::<CommandSpec>(&content) {
+                Ok(cmd) => specs.push((cmd, path)),
+                Err(e) => eprintln!("Failed to parse {:?}: {}", path, e),
+            }
+        }
+    }
+    specs
+}
+
+/// Merged command definitions with the origin of each piece
+#[derive(Debug, Default)]
+pub struct Definitions {
+    specs: BTreeMap<String, CommandSpec>,
+    origins: HashMap<String, Origin>,
+    files: HashMap<String, Vec<Origin>>,
+}
+
+impl Definitions {
+    /// Load and merge all layers in order
+    pub fn load(layers: &[Layer]) -> Self {
+        let mut defs = Self::default();
+        for layer in layers {
+            for (spec, file) in load_layer(layer) {
+                defs.add(
+                    spec,
+                    Origin {
+                        kind: layer.kind,
+                        file,
+                    },
+                );
+            }
+        }
+        for error in resolve(defs.specs.values_mut()) {
+            eprintln!("Warning: {}", error);
+        }
+        defs.attribute_expanded();
+        defs
+    }
+
+    /// Credit pieces pulled in by `use` or `extends` to the spec that asked
+    /// for them
+    fn attribute_expanded(&mut self) {
+        fn walk(
+            spec: &CommandSpec,
+            key: &str,
+            inherited: Option<&Origin>,
+            origins: &mut HashMap<String, Origin>,
+        ) {
+            let Some(origin) = origins.get(key).or(inherited).cloned() else {
+                return;
+            };
+            let mut fill = |piece: String| {
+                origins.entry(piece).or_insert_with(|| origin.clone());
+            };
+            fill(key.to_string());
+            for flag in &spec.flags {
+                fill(format!("{} {}", key, flag_key(flag)));
+            }
+            for arg in &spec.arguments {
+                fill(format!("{} {}", key, argument_key(arg)));
+            }
+            for example in &spec.examples {
+                fill(example_key(key, example));
+            }
+            for sub in &spec.subcommands {
+                walk(
+                    sub,
+                    &format!("{} {}", key, sub.name),
+                    Some(&origin),
+                    origins,
+                );
+            }
+        }
+
+        for (name, spec) in &self.specs {
+            walk(spec, name, None, &mut self.origins);
+        }
+    }
+
+    /// Merge a spec on top of what has been loaded so far
+    pub fn add(&mut self, spec: CommandSpec, origin: Origin) {
+        let name = spec.name.clone();
+        let files = self.files.entry(name.clone()).or_default();
+        if !files.contains(&origin) {
+            files.push(origin.clone());
+        }
+        let origins = &mut self.origins;
+        let mut record = |piece: String| {
+            origins.insert(piece, origin.clone());
+        };
+        match self.specs.get_mut(&name) {
+            Some(base) => merge_spec(base, spec, &name, &mut record),
+            None => {
+                record_all(&spec, &name, &mut record);
+                self.specs.insert(name, spec);
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn get(&self, name: &str) -> Option<&CommandSpec> {
+        self.specs.get(name)
+    }
+
+    /// Files that contributed to a command, in load order
+    pub fn files(&self, name: &str) -> &[Origin] {
+        self.files.get(name).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    /// Every piece of a command (or subcommand path) with the file it came from
+    pub fn which(&self, path: &[&str]) -> Option<Vec<(String, &Origin)>> {
+        let (first, rest) = path.split_first()?;
+        let mut spec = self.specs.get(*first)?;
+        let mut key = first.to_string();
+        for part in rest {
+            spec = spec.subcommands.iter().find(|s| s.name == *part)?;
+            key = format!("{} {}", key, part);
+        }
+        let mut pieces = Vec::new();
+        self.collect_pieces(spec, &key, &mut pieces);
+        Some(pieces)
+    }
+
+    fn collect_pieces<'a>(
+        &'a self,
+        spec: &CommandSpec,
+        key: &str,
+        pieces: &mut Vec<(String, &'a Origin)>,
+    ) {
+        let mut push = |piece: String| {
+            if let Some(origin) = self.origins.get(&piece) {
+                pieces.push((piece, origin));
+            }
+        };
+        push(key.to_string());
+        for flag in &spec.flags {
+            push(format!("{} {}", key, flag_key(flag)));
+        }
+        for arg in &spec.arguments {
+            push(format!("{} {}", key, argument_key(arg)));
+        }
+        for example in &spec.examples {
+            push(example_key(key, example));
+        }
+        for sub in &spec.subcommands {
+            self.collect_pieces(sub, &format!("{} {}", key, sub.name), pieces);
+        }
+    }
+
+    pub fn into_specs(self) -> Vec<CommandSpec> {
+        self.specs.into_values().collect()
+    }
+}
+
+fn flag_key(flag: &FlagSpec) -> String {
+    match (&flag.long, flag.short) {
+        (Some(long), _) => format!("--{}", long),
+        (None, Some(short)) => format!("-{}", short),
+        (None, None) => "-".to_string(),
+    }
+}
+
+fn same_flag(a: &FlagSpec, b: &FlagSpec) -> bool {
+    match (&a.long, &b.long) {
+        (Some(x), Some(y)) => x == y,
+        _ => a.short.is_some() && a.short == b.short,
+    }
+}
+
+fn argument_key(arg: &ArgumentSpec) -> String {
+    match (&arg.name, arg.position) {
+        (Some(name), _) => format!("<{}>", name),
+        (None, Some(pos)) => format!("<#{}>", pos),
+        (None, None) => "<arg>".to_string(),
+    }
+}
+
+fn same_argument(a: &ArgumentSpec, b: &ArgumentSpec) -> bool {
+    match (&a.name, &b.name) {
+        (Some(x), Some(y)) => x == y,
+        _ => a.position.is_some() && a.position == b.position,
+    }
+}
+
+fn example_key(key: &str, example: &Example) -> String {
+    format!("{} example: {}", key, example.cmd)
+}
+
+/// Report every piece of a spec and everything below it
+fn record_all(spec: &CommandSpec, key: &str, record: &mut dyn FnMut(String)) {
+    record(key.to_string());
+    for flag in &spec.flags {
+        record(format!("{} {}", key, flag_key(flag)));
+    }
+    for arg in &spec.arguments {
+        record(format!("{} {}", key, argument_key(arg)));
+    }
+    for example in &spec.examples {
+        record(example_key(key, example));
+    }
+    for sub in &spec.subcommands {
+        record_all(sub, &format!("{} {}", key, sub.name), record);
+    }
+}
+
+/// Merge `overlay` into `base`, reporting each piece the overlay supplied
+fn merge_spec(
+    base: &mut CommandSpec,
+    overlay: CommandSpec,
+    key: &str,
+    record: &mut dyn FnMut(String),
+) {
+    base.description = overlay.description;
+    base.is_path_completion |= overlay.is_path_completion;
+    base.flags_complete |= overlay.flags_complete;
+    base.wraps |= overlay.wraps;
+    if overlay.default_timeout.is_some() {
+        base.default_timeout = overlay.default_timeout;
+    }
+    if overlay.wraps_after.is_some() {
+        base.wraps_after = overlay.wraps_after;
+    }
+    base.flag_sets.extend(overlay.flag_sets);
+    base.argument_sets.extend(overlay.argument_sets);
+    for name in overlay.uses {
+        if !base.uses.contains(&name) {
+            base.uses.push(name);
+        }
+    }
+    if overlay.extends.is_some() {
+        base.extends = overlay.extends;
+    }
+    for (field, value) in [
+        (&mut base.version_command, overlay.version_command),
+        (&mut base.version_regex, overlay.version_regex),
+        (&mut base.since, overlay.since),
+        (&mut base.until, overlay.until),
+        (&mut base.deprecated, overlay.deprecated),
+    ] {
+        if value.is_some() {
+            *field = value;
+        }
+    }
+    record(key.to_string());
+
+    for flag in overlay.flags {
+        record(format!("{} {}", key, flag_key(&flag)));
+        match base.flags.iter_mut().find(|f| same_flag(f, &flag)) {
+            Some(existing) => *existing = flag,
+            None => base.flags.push(flag),
+        }
+    }
+
+    for arg in overlay.arguments {
+        record(format!("{} {}", key, argument_key(&arg)));
+        match base.arguments.iter_mut().find(|a| same_argument(a, &arg)) {
+            Some(existing) => *existing = arg,
+            None => base.arguments.push(arg),
+        }
+    }
+
+    for example in overlay.examples {
+        record(example_key(key, &example));
+        match base.examples.iter_mut().find(|e| e.cmd == example.cmd) {
+            Some(existing) => *existing = example,
+            None => base.examples.push(example),
+        }
+    }
+
+    for sub in overlay.subcommands {
+        let sub_key = format!("{} {}", key, sub.name);
+        match base.subcommands.iter_mut().find(|s| s.name == sub.name) {
+            Some(existing) => merge_spec(existing, sub, &sub_key, record),
+            None => {
+                record_all(&sub, &sub_key, record);
+                base.subcommands.push(sub);
             }
         }
     }
+}
 
-    loaded
+/// Merge one more layer on top of already merged specs
+///
+/// Used for the project layer, whose origins are not tracked.
+pub fn overlay_layer(
+    base: &HashMap<String, CommandSpec>,
+    layer: &Layer,
+) -> HashMap<String, CommandSpec> {
+    let mut specs = base.clone();
+    for (spec, _) in load_layer(layer) {
+        let name = spec.name.clone();
+        match specs.get_mut(&name) {
+            Some(existing) => merge_spec(existing, spec, &name, &mut |_| {}),
+            None => {
+                specs.insert(name, spec);
+            }
+        }
+    }
+    for error in resolve(specs.values_mut()) {
+        eprintln!("Warning: {}", error);
+    }
+    specs
+}
+
+/// Expand `use:` and `extends:` in every spec into plain flags, arguments,
+/// examples and subcommands
+///
+/// Sets are kept so later layers can still refer to them; `use` and
+/// `extends` are cleared, so resolving twice changes nothing. Returns one
+/// message per reference that could not be resolved.
+pub fn resolve<'s>(specs: impl IntoIterator<Item = &'s mut CommandSpec>) -> Vec<String> {
+    let mut specs: Vec<&mut CommandSpec> = specs.into_iter().collect();
+    let unresolved: HashMap<String, CommandSpec> = specs
+        .iter()
+        .map(|spec| (spec.name.clone(), (**spec).clone()))
+        .collect();
+    let mut resolver = Resolver {
+        specs: &unresolved,
+        errors: Vec::new(),
+    };
+    for spec in &mut specs {
+        let original = &unresolved[&spec.name];
+        **spec = resolver.resolve(original, &original.name, &[], &mut Vec::new());
+    }
+    resolver.errors
 }
 
-/// Load commands from the first available definitions directory
-pub fn load_commands<P: AsRef<Path>>(fallback_dir: P) -> Vec<CommandSpec> {
-    let mut commands = Vec::new();
+struct Resolver<'a> {
+    /// Specs before resolution, for references
+    specs: &'a HashMap<String, CommandSpec>,
+    errors: Vec<String>,
+}
 
-    // Try to find definitions directory
-    let definitions_dir =
-        find_definitions_dir().unwrap_or_else(|| fallback_dir.as_ref().to_path_buf());
+impl<'a> Resolver<'a> {
+    /// A spec by command path, with its parents outermost first
+    fn find(&self, path: &str) -> Option<(&'a CommandSpec, Vec<&'a CommandSpec>)> {
+        let mut parts = path.split_whitespace();
+        let mut spec = self.specs.get(parts.next()?)?;
+        let mut parents = Vec::new();
+        for part in parts {
+            let sub = spec.subcommands.iter().find(|s| s.name == part)?;
+            parents.push(spec);
+            spec = sub;
+        }
+        Some((spec, parents))
+    }
 
-    if definitions_dir.exists() {
-        let count = load_from_dir(&definitions_dir, &mut commands);
-        if count > 0 {
-            println!(
-                "Loaded {} commands from: {}",
-                count,
-                definitions_dir.display()
-            );
+    /// Flags and arguments of a set, looked up from the innermost scope out
+    fn set(
+        &self,
+        name: &str,
+        spec: &'a CommandSpec,
+        parents: &[&'a CommandSpec],
+    ) -> Option<(Vec<FlagSpec>, Vec<ArgumentSpec>)> {
+        let lookup = |scope: &CommandSpec, set: &str| {
+            let flags = scope.flag_sets.get(set);
+            let arguments = scope.argument_sets.get(set);
+            (flags.is_some() || arguments.is_some()).then(|| {
+                (
+                    flags.cloned().unwrap_or_default(),
+                    arguments.cloned().unwrap_or_default(),
+                )
+            })
+        };
+        if let Some((command, set)) = name.rsplit_once('.') {
+            return lookup(self.specs.get(command)?, set);
         }
-    } else {
+        std::iter::once(spec)
+            .chain(parents.iter().rev().copied())
+            .find_map(|scope| lookup(scope, name))
+    }
+
+    fn resolve(
+        &mut self,
+        spec: &'a CommandSpec,
+        path: &str,
+        parents: &[&'a CommandSpec],
+        extending: &mut Vec<String>,
+    ) -> CommandSpec {
+        let mut resolved = spec.clone();
+        resolved.uses.clear();
+        resolved.extends = None;
+
+        // Set members come first; the spec's own flags and arguments win
+        let mut flags = Vec::new();
+        let mut arguments = Vec::new();
+        for name in &spec.uses {
+            match self.set(name, spec, parents) {
+                Some((set_flags, set_arguments)) => {
+                    flags.extend(set_flags);
+                    arguments.extend(set_arguments);
+                }
+                None => self
+                    .errors
+                    .push(format!("{}: unknown set `{}`", path, name)),
+            }
+        }
+        for flag in std::mem::take(&mut resolved.flags) {
+            match flags.iter_mut().find(|f| same_flag(f, &flag)) {
+                Some(existing) => *existing = flag,
+                None => flags.push(flag),
+            }
+        }
+        for arg in std::mem::take(&mut resolved.arguments) {
+            match arguments.iter_mut().find(|a| same_argument(a, &arg)) {
+                Some(existing) => *existing = arg,
+                None => arguments.push(arg),
+            }
+        }
+        resolved.flags = flags;
+        resolved.arguments = arguments;
+
+        let scope: Vec<&'a CommandSpec> = parents.iter().copied().chain([spec]).collect();
+        resolved.subcommands = spec
+            .subcommands
+            .iter()
+            .map(|sub| self.resolve(sub, &format!("{} {}", path, sub.name), &scope, extending))
+            .collect();
+
+        let Some(target) = &spec.extends else {
+            return resolved;
+        };
+        if target == path || extending.iter().any(|p| p == path || p == target) {
+            self.errors
+                .push(format!("{}: `extends: {}` is circular", path, target));
+            return resolved;
+        }
+        let Some((base, base_parents)) = self.find(target) else {
+            self.errors
+                .push(format!("{}: `extends: {}` not found", path, target));
+            return resolved;
+        };
+        extending.push(path.to_string());
+        let mut inherited = self.resolve(base, target, &base_parents, extending);
+        extending.pop();
+
+        inherited.name = resolved.name.clone();
+        merge_spec(&mut inherited, resolved, path, &mut |_| {});
+        inherited
+    }
+}
+
+/// Load the global command set, through the definition cache
+pub fn load_commands<P: AsRef<Path>>(custom_dir: P) -> Catalog {
+    load_layers(&definition_layers(custom_dir))
+}
+
+/// Load the global command set from layers resolved earlier
+pub fn load_layers(layers: &[Layer]) -> Catalog {
+    let catalog = catalog::load(layers, catalog::cache_path().as_deref());
+    report_layers(layers, catalog.specs.len());
+    catalog
+}
+
+fn report_layers(layers: &[Layer], count: usize) {
+    if layers.is_empty() {
         eprintln!("Warning: definitions directory not found.");
         eprintln!("Searched paths:");
         eprintln!("  • ./definitions/");
         eprintln!("  • ~/.config/smart-command/definitions/");
         eprintln!("  • /usr/share/smart-command/definitions/");
+    } else if count > 0 {
+        let dirs: Vec<String> = layers.iter().map(|l| l.dir.display().to_string()).collect();
+        println!("Loaded {} commands from: {}", count, dirs.join(", "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin(name: &str) -> Origin {
+        Origin {
+            kind: LayerKind::User,
+            file: PathBuf::from(name),
+        }
+    }
+
+    fn spec(yaml: &str) -> CommandSpec {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_merge_adds_and_overrides() {
+        let mut defs = Definitions::default();
+        defs.add(
+            spec(
+                r#"
+name: git
+description: Git
+flags:
+  - long: version
+    description: Show version
+subcommands:
+  - name: commit
+    description: Record changes
+    flags:
+      - long: amend
+        short: a
+        description: Amend
+examples:
+  - scenario: Log
+    cmd: git log
+"#,
+            ),
+            origin("system/git.yaml"),
+        );
+        defs.add(
+            spec(
+                r#"
+name: git
+description: Git (custom)
+subcommands:
+  - name: commit
+    description: Commit
+    flags:
+      - long: amend
+        description: Amend the last commit
+      - long: fixup
+        description: Fixup commit
+  - name: sync
+    description: Custom alias
+"#,
+            ),
+            origin("user/git.yaml"),
+        );
+
+        let git = defs.get("git").unwrap();
+        assert_eq!(git.description.get("en"), "Git (custom)");
+        assert_eq!(git.flags.len(), 1);
+        assert_eq!(git.examples.len(), 1);
+        assert_eq!(git.subcommands.len(), 2);
+        let commit = &git.subcommands[0];
+        assert_eq!(commit.flags.len(), 2);
+        assert_eq!(
+            commit.flags[0].description.get("en"),
+            "Amend the last commit"
+        );
+        assert_eq!(defs.files("git").len(), 2);
+
+        let pieces = defs.which(&["git"]).unwrap();
+        let from = |piece: &str| {
+            pieces
+                .iter()
+                .find(|(p, _)| p == piece)
+                .map(|(_, o)| o.file.to_str().unwrap())
+        };
+        assert_eq!(from("git"), Some("user/git.yaml"));
+        assert_eq!(from("git --version"), Some("system/git.yaml"));
+        assert_eq!(from("git commit --amend"), Some("user/git.yaml"));
+        assert_eq!(from("git commit --fixup"), Some("user/git.yaml"));
+        assert_eq!(from("git example: git log"), Some("system/git.yaml"));
+        assert_eq!(from("git sync"), Some("user/git.yaml"));
+
+        let commit_pieces = defs.which(&["git", "commit"]).unwrap();
+        assert_eq!(commit_pieces[0].0, "git commit");
+        assert!(defs.which(&["git", "missing"]).is_none());
+    }
+
+    #[test]
+    fn test_definition_layers_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let layers = definition_layers(dir.path());
+        let last = layers.last().unwrap();
+        assert_eq!(last.kind, LayerKind::Custom);
+        assert_eq!(last.dir, dir.path());
+        // The local `./definitions` layer is pinned to the directory sc started in
+        assert!(layers.iter().all(|layer| layer.dir.is_absolute()));
+    }
+
+    #[test]
+    fn test_project_definitions_stop_at_git_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        let nested = repo.join("src").join("deep");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        // Above the git root, so never picked up
+        fs::create_dir_all(dir.path().join(PROJECT_DEFINITIONS_DIR)).unwrap();
+        assert_eq!(find_project_definitions(&nested), None);
+
+        fs::create_dir_all(repo.join(PROJECT_DEFINITIONS_DIR)).unwrap();
+        assert_eq!(
+            find_project_definitions(&nested),
+            Some(repo.join(PROJECT_DEFINITIONS_DIR))
+        );
     }
 
-    commands
+    #[test]
+    fn test_project_definitions_outside_git_only_in_cwd() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("downloads");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(dir.path().join(PROJECT_DEFINITIONS_DIR)).unwrap();
+
+        assert_eq!(find_project_definitions(&nested), None);
+        assert_eq!(
+            find_project_definitions(dir.path()),
+            Some(dir.path().join(PROJECT_DEFINITIONS_DIR))
+        );
+    }
+
+    #[test]
+    fn test_overlay_layer_merges_project_specs() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("git.yaml"),
+            "name: git\ndescription: Git\nsubcommands:\n  - name: ship\n    description: Deploy\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("deploy.yaml"),
+            "name: deploy\ndescription: In-house deploy tool\n",
+        )
+        .unwrap();
+
+        let mut base = HashMap::new();
+        base.insert(
+            "git".to_string(),
+            spec("name: git\ndescription: Git\nsubcommands:\n  - name: log\n    description: Log\n"),
+        );
+        let layer = Layer {
+            kind: LayerKind::Project,
+            dir: dir.path().to_path_buf(),
+        };
+        let merged = overlay_layer(&base, &layer);
+
+        assert_eq!(merged["git"].subcommands.len(), 2);
+        assert!(merged.contains_key("deploy"));
+        assert_eq!(base["git"].subcommands.len(), 1);
+    }
+
+    #[test]
+    fn test_checkout_layers_cannot_run_version_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("tool.yaml"),
+            "name: tool\ndescription: Tool\nversion_command: tool --version\n",
+        )
+        .unwrap();
+        let layer = |kind| Layer {
+            kind,
+            dir: dir.path().to_path_buf(),
+        };
+
+        let project = overlay_layer(&HashMap::new(), &layer(LayerKind::Project));
+        assert_eq!(project["tool"].version_command, None);
+        let local = Definitions::load(&[layer(LayerKind::Local)]);
+        assert_eq!(local.specs["tool"].version_command, None);
+        let user = Definitions::load(&[layer(LayerKind::User)]);
+        assert!(user.specs["tool"].version_command.is_some());
+    }
+
+    fn flag_names(spec: &CommandSpec) -> Vec<&str> {
+        spec.flags
+            .iter()
+            .filter_map(|f| f.long.as_deref())
+            .collect()
+    }
+
+    #[test]
+    fn test_use_expands_sets_across_files() {
+        let mut kubectl = spec(
+            r#"
+name: kubectl
+description: Kubernetes
+flag_sets:
+  kube:
+    - long: namespace
+      short: n
+      description: Namespace
+      takes_value: true
+subcommands:
+  - name: logs
+    description: Logs
+    use: [kube]
+    flags:
+      - long: follow
+        description: Follow
+"#,
+        );
+        let mut helm = spec(
+            r#"
+name: helm
+description: Helm
+subcommands:
+  - name: install
+    description: Install
+    use: [kubectl.kube]
+    flags:
+      - long: namespace
+        description: Release namespace
+        takes_value: true
+"#,
+        );
+        let errors = resolve([&mut kubectl, &mut helm]);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let logs = &kubectl.subcommands[0];
+        assert_eq!(flag_names(logs), ["namespace", "follow"]);
+        assert!(logs.uses.is_empty());
+
+        // The spec's own flag overrides the one from the set
+        let install = &helm.subcommands[0];
+        assert_eq!(flag_names(install), ["namespace"]);
+        assert_eq!(install.flags[0].description.get("en"), "Release namespace");
+
+        // Resolving again changes nothing
+        assert!(resolve([&mut kubectl]).is_empty());
+        assert_eq!(flag_names(&kubectl.subcommands[0]), ["namespace", "follow"]);
+    }
+
+    #[test]
+    fn test_extends_inherits_and_reports_cycles() {
+        let mut tool = spec(
+            r#"
+name: tool
+description: Tool
+subcommands:
+  - name: get
+    description: Get
+    flags:
+      - long: output
+        description: Output format
+    examples:
+      - scenario: YAML
+        cmd: tool get -o yaml
+  - name: fetch
+    description: Alias of get
+    extends: tool get
+    flags:
+      - long: watch
+        description: Watch
+  - name: a
+    description: A
+    extends: tool b
+  - name: b
+    description: B
+    extends: tool a
+  - name: c
+    description: C
+    use: [missing]
+"#,
+        );
+        let errors = resolve([&mut tool]);
+
+        let fetch = &tool.subcommands[1];
+        assert_eq!(fetch.name, "fetch");
+        assert_eq!(fetch.description.get("en"), "Alias of get");
+        assert_eq!(flag_names(fetch), ["output", "watch"]);
+        assert_eq!(fetch.examples.len(), 1);
+        assert!(fetch.extends.is_none());
+
+        assert!(errors.iter().any(|e| e.contains("circular")));
+        assert!(errors.iter().any(|e| e == "tool c: unknown set `missing`"));
+    }
+
+    #[test]
+    fn test_which_credits_expanded_flags() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("tool.yaml"),
+            "name: tool\ndescription: Tool\nflag_sets:\n  common:\n    - long: verbose\n      description: Verbose\nsubcommands:\n  - name: run\n    description: Run\n    use: [common]\n",
+        )
+        .unwrap();
+        let defs = Definitions::load(&[Layer {
+            kind: LayerKind::User,
+            dir: dir.path().to_path_buf(),
+        }]);
+
+        let pieces = defs.which(&["tool", "run"]).unwrap();
+        let (_, origin) = pieces
+            .iter()
+            .find(|(piece, _)| piece == "tool run --verbose")
+            .unwrap();
+        assert_eq!(origin.file, dir.path().join("tool.yaml"));
+    }
 }
@@ -1,61 +1,75 @@
 use reedline::{
-    default_emacs_keybindings, ColumnarMenu, Emacs, FileBackedHistory, MenuBuilder, Prompt,
-    PromptEditMode, PromptHistorySearch, PromptHistorySearchStatus, Reedline, ReedlineEvent,
-    ReedlineMenu, Signal,
+    default_emacs_keybindings, ColumnarMenu, EditCommand, Emacs, FileBackedHistory, HistoryItem,
+    MenuBuilder, Prompt, PromptEditMode, PromptHistorySearch, PromptHistorySearchStatus, Reedline,
+    ReedlineEvent, ReedlineMenu, SearchDirection, SearchQuery, Signal,
 };
 use std::borrow::Cow;
-use std::io::{self, Write};
-use std::path::PathBuf;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::Mutex;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use tracing::{debug, info, warn};
 use tracing_subscriber::EnvFilter;
 
 mod ai;
 mod aliases;
 mod argument;
+mod backend;
 mod bookmarks;
+mod builtins;
+mod catalog;
 mod cli;
 mod command_def;
 mod completer;
 mod config;
 mod context;
 mod definitions;
+mod defs;
+mod dirstack;
+mod environment;
 mod error;
+mod executor;
+mod flags;
+mod generate;
 mod highlighter;
+mod history_expand;
+mod import;
 mod hinter;
 mod install;
+mod jobs;
+mod lint;
 mod loader;
 mod output;
 mod pipeline;
 mod providers;
+mod rc;
+mod script;
 mod snippets;
 mod timer;
+mod tokenizer;
 mod ui;
 mod validator;
+mod versions;
 mod watcher;
 mod plugins;
 mod upgrade;
 
 use ai::{NaturalLanguageTemplates, TypoCorrector};
-use aliases::AliasManager;
-use bookmarks::BookmarkManager;
+use backend::Backend;
+use builtins::ShellBuiltins;
 use cli::{Cli, Commands, ConfigAction};
 use completer::SmartCompleter;
-use config::AppConfig;
+use config::{AppConfig, ConfigOverrides};
+use executor::{ExecOutcome, Executor, Timeouts};
 use highlighter::{SmartHighlighter, SyntaxTheme};
 use hinter::SmartHinter;
 use install::InstallOptions;
 use nu_ansi_term::{Color, Style};
 use output::Output;
-use snippets::SnippetManager;
-use timer::CommandTimer;
+use script::ScriptOptions;
 use validator::SmartValidator;
-use plugins::PluginManager;
-
-// Track previous directory for `cd -`
-static OLDPWD: Mutex<Option<PathBuf>> = Mutex::new(None);
+use watcher::{HotReloadManager, WatchEvent};
 
 /// Maximum number of example search results to display
 const MAX_EXAMPLE_RESULTS: usize = 20;
@@ -67,14 +81,19 @@ struct AppState {
     danger_protection: bool,
 }
 
-/// Custom prompt showing current directory and git branch
+/// Custom prompt showing current directory, git branch and last exit status
 struct SmartPrompt {
     config: AppConfig,
+    /// Exit status of the previous command line
+    last_status: i32,
 }
 
 impl SmartPrompt {
     fn new(config: AppConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            last_status: 0,
+        }
     }
 
     fn get_cwd_display() -> String {
@@ -124,9 +143,16 @@ impl Prompt for SmartPrompt {
             }
         }
 
+        let status = self
+            .config
+            .prompt
+            .show_exit_status
+            .then_some(self.last_status);
+
         Cow::Owned(output::Output::prompt(
             &parts.join(" "),
             Self::get_git_branch().as_deref(),
+            status,
         ))
     }
 
@@ -160,6 +186,8 @@ impl Prompt for SmartPrompt {
 enum ShellState {
     Normal,
     SelectingSearchResult(Vec<(String, String, String)>),
+    /// A line sent back to the prompt to be fixed
+    Editing(String),
 }
 
 fn main() -> anyhow::Result<()> {
@@ -173,15 +201,12 @@ fn main() -> anyhow::Result<()> {
     });
 
     // CLI overrides
-    if let Some(lang) = &cli.lang {
-        config.lang = lang.clone();
-    }
-    if let Some(definitions_dir) = &cli.definitions {
-        config.definitions_dir = Some(definitions_dir.clone());
-    }
-    if cli.no_danger_protection {
-        config.danger_protection = false;
-    }
+    let overrides = ConfigOverrides {
+        lang: cli.lang.clone(),
+        definitions_dir: cli.definitions.clone(),
+        no_danger_protection: cli.no_danger_protection,
+    };
+    overrides.apply(&mut config);
 
     // Initialize tracing
     let log_level = if cli.verbose {
@@ -194,6 +219,7 @@ fn main() -> anyhow::Result<()> {
             EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(log_level)),
         )
         .with_target(false)
+        .with_writer(io::stderr)
         .init();
 
     info!("Starting Smart Command v{}", env!("CARGO_PKG_VERSION"));
@@ -203,6 +229,11 @@ fn main() -> anyhow::Result<()> {
         return handle_subcommand(subcommand, &config);
     }
 
+    // Handle shebang mode (`#!/usr/bin/env sc`)
+    if let Some(script) = &cli.script {
+        return run_script_file(script, ScriptOptions::default(), &config);
+    }
+
     // Handle single command execution
     if let Some(cmd) = cli.command {
         let definitions_dir = config
@@ -218,12 +249,14 @@ fn main() -> anyhow::Result<()> {
             config: config.clone(),
             danger_protection: config.danger_protection,
         };
-        execute_command(&cmd, &current_lang, &state, &typo_corrector);
-        return Ok(());
+        let mut executor = new_executor(&config, &completer);
+        let mut builtins = ShellBuiltins::new(completer, current_lang);
+        let outcome = execute_command(&cmd, &state, &mut executor, &mut builtins, &typo_corrector);
+        std::process::exit(outcome.status);
     }
 
     // Start REPL
-    run_repl(config)
+    run_repl(config, overrides, cli.norc)
 }
 
 fn handle_subcommand(cmd: Commands, config: &AppConfig) -> anyhow::Result<()> {
@@ -297,6 +330,17 @@ fn handle_subcommand(cmd: Commands, config: &AppConfig) -> anyhow::Result<()> {
             };
             install::run_install(opts)?;
         }
+        Commands::Run {
+            script,
+            continue_on_error,
+            dry_run,
+        } => {
+            let options = ScriptOptions {
+                continue_on_error,
+                dry_run,
+            };
+            run_script_file(&script, options, config)?;
+        }
         Commands::Upgrade { check, force, yes, target_version } => {
             handle_upgrade(config, check, force, yes, target_version.as_deref())?;
         }
@@ -313,23 +357,58 @@ fn handle_subcommand(cmd: Commands, config: &AppConfig) -> anyhow::Result<()> {
             // Handle search flag
             if let Some(query) = search {
                 let results = completer.search_examples(&query, &lang);
-                display_example_search_results(&results, &query);
+                display_example_search_results(&results, &query, &mut io::stdout())?;
                 return Ok(());
             }
 
             // Handle command argument
             if command.is_empty() {
-                display_commands_with_examples(&completer, true);
+                display_commands_with_examples(&completer, true, &mut io::stdout())?;
             } else {
                 let command_path = command.join(" ");
                 let examples = completer.get_examples(&command_path, &lang);
-                display_command_examples(&examples, &command_path, true);
+                display_command_examples(&examples, &command_path, true, &mut io::stdout())?;
             }
         }
+        Commands::Defs { action } => {
+            defs::run(action, config)?;
+        }
     }
     Ok(())
 }
 
+/// Run a script file (`sc run` or shebang mode) and exit with its status
+fn run_script_file(path: &Path, options: ScriptOptions, config: &AppConfig) -> anyhow::Result<()> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Cannot read script {}: {}", path.display(), e))?;
+
+    let definitions_dir = config
+        .definitions_dir
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("definitions"));
+    let commands = loader::load_commands(&definitions_dir);
+    let current_lang = Arc::new(RwLock::new(config.lang.clone()));
+    let completer = SmartCompleter::new(commands, current_lang.clone());
+    let typo_corrector = TypoCorrector::new(completer.get_command_names());
+    let state = AppState {
+        config: config.clone(),
+        danger_protection: config.danger_protection,
+    };
+    let mut executor = new_executor(config, &completer);
+    let mut builtins = ShellBuiltins::new(completer, current_lang);
+
+    let status = script::run_script(
+        &source,
+        &path.display().to_string(),
+        options,
+        &mut builtins,
+        |line, builtins| {
+            execute_command(line, &state, &mut executor, builtins, &typo_corrector).status
+        },
+    );
+    std::process::exit(status);
+}
+
 /// Handle upgrade command
 fn handle_upgrade(
     config: &AppConfig,
@@ -415,12 +494,14 @@ fn handle_upgrade(
     })
 }
 
-fn run_repl(config: AppConfig) -> anyhow::Result<()> {
+fn run_repl(config: AppConfig, overrides: ConfigOverrides, norc: bool) -> anyhow::Result<()> {
     let definitions_dir = config
         .definitions_dir
         .clone()
         .unwrap_or_else(|| PathBuf::from("definitions"));
-    let commands = loader::load_commands(&definitions_dir);
+    // Resolved once: hot reload must not follow `./definitions` after a `cd`
+    let definition_layers = loader::definition_layers(&definitions_dir);
+    let commands = loader::load_layers(&definition_layers);
     let current_lang = Arc::new(RwLock::new(config.lang.clone()));
     let completer = SmartCompleter::new(commands, current_lang.clone());
     let completer_for_editor = Box::new(completer.clone());
@@ -449,17 +530,11 @@ fn run_repl(config: AppConfig) -> anyhow::Result<()> {
     let nl_templates = NaturalLanguageTemplates::new();
 
     // Create SmartHighlighter with theme based on config
-    let theme = match config.theme.as_deref() {
-        Some("nord") => SyntaxTheme::nord(),
-        Some("dracula") => SyntaxTheme::dracula(),
-        _ => SyntaxTheme::default(),
-    };
-    let highlighter = Box::new(SmartHighlighter::new(command_names).with_theme(theme));
+    let highlighter = new_highlighter(&config, command_names, &completer);
 
-    // Create SmartHinter for inline suggestions
-    let hinter = Box::new(
-        SmartHinter::new().with_style(Style::new().italic().fg(Color::DarkGray)),
-    );
+    // Create SmartHinter for inline suggestions (the REPL keeps a handle to record commands)
+    let smart_hinter = SmartHinter::new().with_style(Style::new().italic().fg(Color::DarkGray));
+    let hinter = Box::new(smart_hinter.clone());
 
     // Create SmartValidator for syntax checking
     let validator = Box::new(SmartValidator::new());
@@ -478,7 +553,7 @@ fn run_repl(config: AppConfig) -> anyhow::Result<()> {
         .with_edit_mode(edit_mode)
         .with_history(Box::new(history));
 
-    let prompt = SmartPrompt::new(config.clone());
+    let mut prompt = SmartPrompt::new(config.clone());
     let mut shell_state = ShellState::Normal;
 
     let state = AppState {
@@ -486,12 +561,44 @@ fn run_repl(config: AppConfig) -> anyhow::Result<()> {
         danger_protection: config.danger_protection,
     };
 
-    // Initialize UX managers
-    let mut alias_manager = AliasManager::new();
-    let mut snippet_manager = SnippetManager::new();
-    let mut bookmark_manager = BookmarkManager::new();
-    let mut command_timer = CommandTimer::new();
-    let mut plugin_manager = PluginManager::new();
+    // Initialize builtins (with their UX managers) and the executor
+    let builtins = ShellBuiltins::new(completer.clone(), current_lang.clone());
+    let mut executor = new_executor(&config, &completer);
+    executor.enable_job_control();
+
+    let mut shell = Shell {
+        state,
+        executor,
+        builtins,
+        typo_corrector,
+        nl_templates,
+        hinter: smart_hinter,
+        validator: SmartValidator::new().with_completer(completer.clone()),
+        warned_stopped_jobs: false,
+        definition_layers,
+    };
+
+    // Reload definitions, config and the managers' files when they change
+    let mut reloader = HotReloadManager::new(
+        shell
+            .definition_layers
+            .iter()
+            .map(|layer| layer.dir.clone())
+            .collect(),
+        AppConfig::config_file_path()
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+        shell.builtins.plugin_manager.plugins_dir().to_path_buf(),
+    );
+    if let Err(e) = reloader.start() {
+        warn!("Hot reload disabled: {}", e);
+    }
+
+    // Run init.sc and the project's .sc-init before the first prompt
+    if !norc && !shell.run_startup_files() {
+        return Ok(());
+    }
 
     // Display startup banner
     output::Output::banner();
@@ -522,187 +629,41 @@ fn run_repl(config: AppConfig) -> anyhow::Result<()> {
     }
 
     loop {
+        shell.executor.notify_jobs();
+        // Pick up or drop project definitions after a `cd`; like `.sc-init`
+        // they come with the checkout, so they need the user's trust first
+        if let Some(dir) = shell.builtins.completer.untrusted_project() {
+            let source = rc::definitions_source(&dir);
+            let ask = |path: &Path| ask_trust(path, "Use its command definitions?");
+            let trusted = rc::allowed(&mut rc::TrustStore::new(), &dir, &source, ask);
+            shell.builtins.completer.set_project_trust(dir, trusted);
+        }
+        shell.builtins.completer.sync_project();
+        reloader.watch_project(shell.builtins.completer.project_dir());
+        line_editor = shell.hot_reload(reloader.poll(), line_editor, &mut prompt, &overrides);
+        prompt.last_status = shell.executor.last_status();
+        if let ShellState::Editing(line) = &shell_state {
+            line_editor.run_edit_commands(&[EditCommand::InsertString(line.clone())]);
+            shell_state = ShellState::Normal;
+        }
         let sig = line_editor.read_line(&prompt)?;
         match sig {
             Signal::Success(buffer) => {
                 let trimmed = buffer.trim();
 
                 match &shell_state {
-                    ShellState::Normal => {
-                        // Handle `/` prefix for command search
-                        if trimmed.starts_with('/') && trimmed.len() > 1 {
-                            let query = &trimmed[1..];
-                            let results = completer.search(query);
-
-                            if results.is_empty() {
-                                Output::warn(&format!("No results found for: {}", query));
-                            } else {
-                                println!();
-                                Output::info(&format!("Search results for '{}':", query));
-                                for (i, (cmd, desc, match_type)) in results.iter().enumerate() {
-                                    Output::search_result(i + 1, cmd, match_type, desc);
-                                }
-                                Output::dim("\nType a number to execute, 'e<num>' to edit, or Enter to cancel:");
-                                shell_state = ShellState::SelectingSearchResult(results);
-                            }
-                            continue;
-                        }
-
-                        // Handle `?` prefix for natural language queries
-                        if trimmed.starts_with('?') && trimmed.len() > 1 {
-                            let query = &trimmed[1..];
-                            let matches = nl_templates.find(query);
-
-                            if matches.is_empty() {
-                                Output::warn(&format!("No matching commands for: {}", query));
-                                Output::dim("Try keywords like: large files, disk space, git history, etc.");
-                            } else {
-                                println!();
-                                Output::info(&format!("Commands for '{}':", query));
-                                for (i, (cmd, desc)) in matches.iter().enumerate() {
-                                    println!("  {}. {} - {}", i + 1, Output::command(cmd), desc);
-                                }
-                                print!("\nExecute command? [1-{}/n]: ", matches.len());
-                                io::stdout().flush().ok();
-
-                                let mut input = String::new();
-                                if io::stdin().read_line(&mut input).is_ok() {
-                                    let input = input.trim();
-                                    if let Ok(num) = input.parse::<usize>() {
-                                        if num > 0 && num <= matches.len() {
-                                            let cmd = matches[num - 1].0;
-                                            Output::info(&format!("Executing: {}", cmd));
-                                            execute_command(cmd, &current_lang, &state, &typo_corrector);
-                                        }
-                                    }
-                                }
-                            }
-                            continue;
-                        }
-
-                        if trimmed == "exit" {
-                            break;
-                        }
-                        if trimmed.is_empty() {
-                            continue;
-                        }
-
-                        // Handle snippet expansion (`:snippet` prefix)
-                        if trimmed.starts_with(':') {
-                            if let Some(expanded) = snippet_manager.try_expand(trimmed) {
-                                Output::info(&format!("Expanded: {}", expanded));
-                                print!("Execute? [Y/n]: ");
-                                io::stdout().flush().ok();
-                                let mut input = String::new();
-                                if io::stdin().read_line(&mut input).is_ok() {
-                                    let response = input.trim().to_lowercase();
-                                    if response.is_empty() || response == "y" || response == "yes" {
-                                        command_timer.start(&expanded);
-                                        execute_command(&expanded, &current_lang, &state, &typo_corrector);
-                                        if let Some(dur) = command_timer.stop(None) {
-                                            if let Some(formatted) = command_timer.format_duration(dur) {
-                                                Output::dim(&format!("⏱  {}", formatted));
-                                            }
-                                        }
-                                    }
-                                }
-                            } else {
-                                // Try as snippet command
-                                let parts: Vec<&str> = trimmed[1..].split_whitespace().collect();
-                                if let Some(output) = snippets::handle_snippet_command(&mut snippet_manager, "snippet", &parts) {
-                                    println!("{}", output);
-                                } else {
-                                    Output::warn(&format!("Unknown snippet: {}", trimmed));
-                                }
+                    ShellState::Normal | ShellState::Editing(_) => {
+                        let line = match expand_history(&mut line_editor, trimmed) {
+                            Ok(line) => line,
+                            Err(e) => {
+                                Output::error(&e);
+                                shell.executor.set_last_status(1);
+                                continue;
                             }
-                            continue;
-                        }
-
-                        // Handle bookmark jump (`@bookmark` syntax)
-                        if trimmed.starts_with('@') && !trimmed.contains(' ') {
-                            let name = &trimmed[1..];
-                            if let Some(path) = bookmark_manager.try_resolve(trimmed) {
-                                let path_str = path.display().to_string();
-                                Output::dim(&path_str);
-                                if let Err(e) = std::env::set_current_dir(path) {
-                                    Output::error(&format!("cd: {}: {}", path.display(), e));
-                                } else {
-                                    bookmark_manager.record_visit(name);
-                                }
-                            } else {
-                                Output::error(&format!("Bookmark @{} not found", name));
-                            }
-                            continue;
-                        }
-
-                        // Handle built-in UX commands
-                        let parts: Vec<&str> = trimmed.split_whitespace().collect();
-                        let cmd = parts.first().map(|s| *s).unwrap_or("");
-
-                        // Alias command
-                        if cmd == "alias" || cmd == "unalias" {
-                            if let Some(output) = aliases::handle_alias_command(&mut alias_manager, cmd, &parts[1..]) {
-                                println!("{}", output);
-                            }
-                            continue;
-                        }
-
-                        // Bookmark command
-                        if cmd == "bookmark" || cmd == "bm" || cmd == "unbookmark" || cmd == "unbm" {
-                            let cwd = std::env::current_dir().unwrap_or_default();
-                            if let Some(output) = bookmarks::handle_bookmark_command(&mut bookmark_manager, cmd, &parts[1..], &cwd) {
-                                println!("{}", output);
-                            }
-                            continue;
-                        }
-
-                        // Snippet command
-                        if cmd == "snippet" || cmd == "snip" {
-                            if let Some(output) = snippets::handle_snippet_command(&mut snippet_manager, cmd, &parts[1..]) {
-                                println!("{}", output);
-                            }
-                            continue;
-                        }
-
-                        // Timer command
-                        if cmd == "time" || cmd == "timer" {
-                            if let Some(output) = timer::handle_timer_command(&command_timer, cmd, &parts[1..]) {
-                                println!("{}", output);
-                            }
-                            continue;
-                        }
-
-                        // Plugin command
-                        if cmd == "plugin" || cmd == "plugins" {
-                            if let Some(output) = plugins::handle_plugin_command(&mut plugin_manager, cmd, &parts[1..]) {
-                                println!("{}", output);
-                            }
-                            continue;
-                        }
-
-                        // Example command
-                        if cmd == "example" || cmd == "examples" || cmd == "ex" {
-                            let lang = current_lang.read().unwrap().clone();
-                            handle_example_command(&completer, &parts[1..], &lang);
-                            continue;
-                        }
-
-                        // Expand aliases before execution
-                        let expanded = alias_manager.expand(trimmed);
-                        let final_cmd = if expanded != trimmed {
-                            Output::dim(&format!("→ {}", expanded));
-                            expanded
-                        } else {
-                            trimmed.to_string()
                         };
-
-                        // Time the command execution
-                        command_timer.start(&final_cmd);
-                        execute_command(&final_cmd, &current_lang, &state, &typo_corrector);
-                        if let Some(dur) = command_timer.stop(None) {
-                            if let Some(formatted) = command_timer.format_duration(dur) {
-                                Output::dim(&format!("⏱  {}", formatted));
-                            }
+                        match shell.dispatch(&line, true) {
+                            Some(next) => shell_state = next,
+                            None => break,
                         }
                     }
 
@@ -725,7 +686,7 @@ fn run_repl(config: AppConfig) -> anyhow::Result<()> {
                             if num > 0 && num <= results.len() {
                                 let selected = &results[num - 1].0;
                                 Output::info(&format!("Executing: {}", selected));
-                                execute_command(selected, &current_lang, &state, &typo_corrector);
+                                shell.execute(selected);
                                 shell_state = ShellState::Normal;
                             } else {
                                 Output::error(&format!(
@@ -759,111 +720,514 @@ fn run_repl(config: AppConfig) -> anyhow::Result<()> {
     Ok(())
 }
 
-/// Execute a command and handle special built-in commands
-fn execute_command(
-    command: &str,
-    current_lang: &Arc<RwLock<String>>,
-    state: &AppState,
-    typo_corrector: &TypoCorrector,
-) {
-    let parts: Vec<&str> = command.split_whitespace().collect();
-
-    if let Some(cmd) = parts.first() {
-        // Handle 'cd' command
-        if *cmd == "cd" {
-            handle_cd(&parts);
-            return;
+/// Everything a REPL line needs to run
+struct Shell {
+    state: AppState,
+    executor: Executor,
+    builtins: ShellBuiltins,
+    typo_corrector: TypoCorrector,
+    nl_templates: NaturalLanguageTemplates,
+    hinter: SmartHinter,
+    validator: SmartValidator,
+    warned_stopped_jobs: bool,
+    /// Global definition layers as resolved at startup, reloaded on changes
+    definition_layers: Vec<loader::Layer>,
+}
+
+impl Shell {
+    /// Apply file changes picked up by the watcher, with a notice per file
+    ///
+    /// Takes the line editor by value because a new theme or command list
+    /// means swapping in a new highlighter.
+    fn hot_reload(
+        &mut self,
+        events: Vec<WatchEvent>,
+        mut line_editor: Reedline,
+        prompt: &mut SmartPrompt,
+        overrides: &ConfigOverrides,
+    ) -> Reedline {
+        let mut config_changed = false;
+        let mut definitions_changed = false;
+        let mut plugins_changed = false;
+        let mut reloaded = Vec::new();
+        for event in events {
+            match event {
+                WatchEvent::ConfigChanged => match AppConfig::load() {
+                    Ok(mut config) => {
+                        overrides.apply(&mut config);
+                        if config.lang != self.state.config.lang {
+                            *self.builtins.current_lang.write().unwrap() = config.lang.clone();
+                        }
+                        prompt.config = config.clone();
+                        self.state.danger_protection = config.danger_protection;
+                        self.state.config = config;
+                        config_changed = true;
+                        reloaded.push("config.toml".to_string());
+                    }
+                    Err(e) => Output::warn(&format!("config.toml not reloaded: {}", e)),
+                },
+                WatchEvent::DefinitionChanged(path) => {
+                    definitions_changed = true;
+                    reloaded.push(file_name(&path));
+                }
+                WatchEvent::AliasesChanged => {
+                    if self.builtins.alias_manager.reload() {
+                        reloaded.push("aliases.yaml".to_string());
+                    }
+                }
+                WatchEvent::SnippetsChanged => {
+                    if self.builtins.snippet_manager.reload() {
+                        reloaded.push("snippets.yaml".to_string());
+                    }
+                }
+                WatchEvent::BookmarksChanged => {
+                    if self.builtins.bookmark_manager.reload() {
+                        reloaded.push("bookmarks.yaml".to_string());
+                    }
+                }
+                WatchEvent::PluginChanged(path) => {
+                    plugins_changed = true;
+                    let plugins_dir = self.builtins.plugin_manager.plugins_dir();
+                    let name = match path.strip_prefix(plugins_dir) {
+                        Ok(relative) => relative.iter().next().map(PathBuf::from),
+                        Err(_) => None,
+                    };
+                    let name = format!("plugin {}", file_name(&name.unwrap_or(path)));
+                    if !reloaded.contains(&name) {
+                        reloaded.push(name);
+                    }
+                }
+                WatchEvent::Error(e) => debug!("File watcher error: {}", e),
+            }
         }
 
-        // Handle 'config' command
-        if *cmd == "config" {
-            handle_config(&parts, current_lang);
-            return;
+        if plugins_changed {
+            self.builtins.plugin_manager.reload();
+        }
+        let config = &self.state.config;
+        if definitions_changed {
+            self.builtins
+                .completer
+                .reload(loader::load_layers(&self.definition_layers));
+            self.typo_corrector = TypoCorrector::new(self.builtins.completer.get_command_names());
+        }
+        if config_changed || definitions_changed {
+            self.executor
+                .set_timeouts(timeouts(config, &self.builtins.completer));
+            line_editor = line_editor.with_highlighter(new_highlighter(
+                config,
+                self.builtins.completer.get_command_names(),
+                &self.builtins.completer,
+            ));
+        }
+        for name in reloaded {
+            Output::dim(&format!("reloaded {}", name));
         }
+        line_editor
+    }
+
+    /// Handle one line typed at the prompt (or read from a startup file)
+    ///
+    /// Returns the next REPL state, or `None` when the shell should exit.
+    /// Non-interactive lines never wait for a confirmation on stdin.
+    fn dispatch(&mut self, trimmed: &str, interactive: bool) -> Option<ShellState> {
+        // Handle `/` prefix for command search
+        if trimmed.starts_with('/') && trimmed.len() > 1 {
+            let query = &trimmed[1..];
+            let results = self.builtins.completer.search(query);
 
-        // Check for dangerous commands
-        if state.danger_protection {
-            if let Some(warning) = output::get_danger_warning(command) {
-                warn!("Dangerous command detected: {}", command);
-                Output::warn(&warning);
-                print!("Are you sure you want to execute this command? [y/N] ");
+            if results.is_empty() {
+                Output::warn(&format!("No results found for: {}", query));
+            } else {
+                println!();
+                Output::info(&format!("Search results for '{}':", query));
+                for (i, (cmd, desc, match_type)) in results.iter().enumerate() {
+                    Output::search_result(i + 1, cmd, match_type, desc);
+                }
+                if interactive {
+                    Output::dim("\nType a number to execute, 'e<num>' to edit, or Enter to cancel:");
+                    return Some(ShellState::SelectingSearchResult(results));
+                }
+            }
+            return Some(ShellState::Normal);
+        }
+
+        // Handle `?` prefix for natural language queries
+        if trimmed.starts_with('?') && trimmed.len() > 1 {
+            let query = &trimmed[1..];
+            let matches = self.nl_templates.find(query);
+
+            if matches.is_empty() {
+                Output::warn(&format!("No matching commands for: {}", query));
+                Output::dim("Try keywords like: large files, disk space, git history, etc.");
+            } else {
+                println!();
+                Output::info(&format!("Commands for '{}':", query));
+                for (i, (cmd, desc)) in matches.iter().enumerate() {
+                    println!("  {}. {} - {}", i + 1, Output::command(cmd), desc);
+                }
+                if !interactive {
+                    return Some(ShellState::Normal);
+                }
+                print!("\nExecute command? [1-{}/n]: ", matches.len());
                 io::stdout().flush().ok();
 
                 let mut input = String::new();
                 if io::stdin().read_line(&mut input).is_ok() {
-                    let response = input.trim().to_lowercase();
-                    if response != "y" && response != "yes" {
-                        Output::dim("Command cancelled.");
-                        return;
+                    let input = input.trim();
+                    if let Ok(num) = input.parse::<usize>() {
+                        if num > 0 && num <= matches.len() {
+                            let cmd = matches[num - 1].0;
+                            Output::info(&format!("Executing: {}", cmd));
+                            self.execute(cmd);
+                        }
                     }
+                }
+            }
+            return Some(ShellState::Normal);
+        }
+
+        if trimmed == "exit" {
+            if self.executor.jobs().has_stopped() && !self.warned_stopped_jobs {
+                Output::warn("There are stopped jobs.");
+                self.warned_stopped_jobs = true;
+                return Some(ShellState::Normal);
+            }
+            return None;
+        }
+        if trimmed.is_empty() {
+            return Some(ShellState::Normal);
+        }
+
+        // Handle snippet expansion (`:snippet` prefix)
+        if trimmed.starts_with(':') {
+            if let Some(expanded) = self.builtins.snippet_manager.try_expand(trimmed) {
+                Output::info(&format!("Expanded: {}", expanded));
+                let confirmed = !interactive || {
+                    print!("Execute? [Y/n]: ");
+                    io::stdout().flush().ok();
+                    let mut input = String::new();
+                    io::stdin().read_line(&mut input).is_ok() && {
+                        let response = input.trim().to_lowercase();
+                        response.is_empty() || response == "y" || response == "yes"
+                    }
+                };
+                if confirmed {
+                    self.run_timed(&expanded);
+                }
+            } else {
+                // Try as snippet command
+                let parts: Vec<&str> = trimmed.trim_start_matches(':').split_whitespace().collect();
+                if let Some(output) = snippets::handle_snippet_command(&mut self.builtins.snippet_manager, "snippet", &parts) {
+                    println!("{}", output);
                 } else {
-                    Output::dim("Command cancelled.");
-                    return;
+                    Output::warn(&format!("Unknown snippet: {}", trimmed));
                 }
             }
+            return Some(ShellState::Normal);
         }
 
-        debug!("Executing command: {}", command);
+        // Handle bookmark jump (`@bookmark` syntax)
+        if trimmed.starts_with('@') && !trimmed.contains(' ') {
+            let name = &trimmed[1..];
+            if let Some(path) = self.builtins.bookmark_manager.try_resolve(trimmed) {
+                let path_str = path.display().to_string();
+                Output::dim(&path_str);
+                if let Err(e) = dirstack::dir_stack().change_dir(path) {
+                    Output::error(&format!("cd: {}", e));
+                    self.executor.set_last_status(1);
+                } else {
+                    self.builtins.bookmark_manager.record_visit(name);
+                    self.executor.set_last_status(0);
+                }
+            } else {
+                Output::error(&format!("Bookmark @{} not found", name));
+                self.executor.set_last_status(1);
+            }
+            return Some(ShellState::Normal);
+        }
 
-        // Execute external command
-        let status = Command::new("sh").arg("-c").arg(command).status();
+        // Expand aliases before execution
+        let expanded = self.builtins.alias_manager.expand(trimmed);
+        let final_cmd = if expanded != trimmed {
+            Output::dim(&format!("→ {}", expanded));
+            expanded
+        } else {
+            trimmed.to_string()
+        };
 
-        match status {
-            Ok(exit_status) => {
-                match exit_status.code() {
-                    Some(0) => {} // Success, silent
-                    Some(127) => {
-                        // Command not found - suggest typo corrections
-                        Output::exit_code(127);
-                        if let Some(message) = typo_corrector.did_you_mean(cmd) {
-                            Output::info(&message);
-                        }
+        if interactive && self.state.config.check_arguments {
+            let problems = self.validator.check_arguments(&final_cmd);
+            if !problems.is_empty() {
+                match ask_run_anyway(&problems) {
+                    RunAnyway::Yes => {}
+                    RunAnyway::Edit => return Some(ShellState::Editing(trimmed.to_string())),
+                    RunAnyway::No => {
+                        Output::dim("Command cancelled.");
+                        self.executor.set_last_status(1);
+                        return Some(ShellState::Normal);
                     }
-                    Some(code) => Output::exit_code(code),
-                    None => Output::error("Process terminated by signal"),
                 }
             }
-            Err(e) => Output::error(&format!("Error executing command: {}", e)),
         }
+
+        self.run_timed(&final_cmd);
+        Some(ShellState::Normal)
+    }
+
+    /// Run `init.sc` and a trusted `.sc-init` through [`Shell::dispatch`]
+    ///
+    /// Returns `false` if a startup file ran `exit`.
+    fn run_startup_files(&mut self) -> bool {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let mut store = rc::TrustStore::new();
+        let files = rc::startup_files(&cwd, &mut store, |path| ask_trust(path, "Run it?"));
+
+        for file in files {
+            debug!("Running startup file: {}", file.path.display());
+            for line in script::script_lines(&file.source) {
+                if self.dispatch(&line.text, false).is_none() {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn execute(&mut self, command: &str) -> ExecOutcome {
+        execute_command(
+            command,
+            &self.state,
+            &mut self.executor,
+            &mut self.builtins,
+            &self.typo_corrector,
+        )
+    }
+
+    fn run_timed(&mut self, command: &str) -> ExecOutcome {
+        run_timed(
+            command,
+            &self.state,
+            &mut self.executor,
+            &mut self.builtins,
+            &self.typo_corrector,
+            &self.hinter,
+        )
     }
 }
 
-/// Handle 'cd' command with OLDPWD support
-fn handle_cd(parts: &[&str]) {
-    let current_dir = std::env::current_dir().ok();
+/// Expand `!!`, `!$`, `^old^new` and friends against the REPL history
+///
+/// The expanded line is added to history so the arrow keys recall the real command.
+fn expand_history(line_editor: &mut Reedline, line: &str) -> Result<String, String> {
+    if !history_expand::has_references(line) {
+        return Ok(line.to_string());
+    }
 
-    let target_path: Option<PathBuf> = if let Some(path) = parts.get(1) {
-        if *path == "-" {
-            let old = OLDPWD.lock().unwrap().clone();
-            if let Some(ref old_path) = old {
-                Output::dim(&old_path.display().to_string());
-                Some(old_path.clone())
-            } else {
-                Output::error("cd: OLDPWD not set");
-                None
-            }
-        } else if path.starts_with('~') {
-            dirs::home_dir().map(|home| {
-                if *path == "~" {
-                    home
-                } else {
-                    home.join(&path[2..])
-                }
-            })
-        } else {
-            Some(PathBuf::from(path))
+    let mut entries: Vec<String> = line_editor
+        .history()
+        .search(SearchQuery::everything(SearchDirection::Forward, None))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|item| item.command_line)
+        .collect();
+    // reedline has already saved the line being expanded
+    if entries.last().is_some_and(|last| last.trim() == line) {
+        entries.pop();
+    }
+    // Unexpanded lines are followed by their expansion, so skip them
+    entries.retain(|entry| !history_expand::has_references(entry));
+
+    match history_expand::expand(line, &entries)? {
+        Some(expanded) => {
+            Output::dim(&format!("→ {}", expanded));
+            let _ = line_editor
+                .history_mut()
+                .save(HistoryItem::from_command_line(&expanded));
+            Ok(expanded)
         }
-    } else {
-        dirs::home_dir()
+        None => Ok(line.to_string()),
+    }
+}
+
+/// Ask whether an untrusted `.sc-init` may run, or project definitions be
+/// used; `question` says which
+fn ask_trust(path: &Path, question: &str) -> rc::Trust {
+    Output::warn(&format!("{} is not trusted yet.", path.display()));
+    if !io::stdin().is_terminal() {
+        Output::dim("Skipping it (no terminal to ask on).");
+        return rc::Trust::Deny;
+    }
+
+    print!("{} [y]es/[a]lways/[N]o: ", question);
+    io::stdout().flush().ok();
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input).is_err() {
+        return rc::Trust::Deny;
+    }
+    match input.trim().to_lowercase().as_str() {
+        "y" | "yes" => rc::Trust::Once,
+        "a" | "always" => rc::Trust::Always,
+        _ => rc::Trust::Deny,
+    }
+}
+
+/// Answer to [`ask_run_anyway`]
+enum RunAnyway {
+    Yes,
+    Edit,
+    No,
+}
+
+/// Show a line's argument problems and ask whether to run it anyway
+///
+/// Definitions can be wrong or out of date, so the line runs unless the
+/// answer is no.
+fn ask_run_anyway(problems: &[String]) -> RunAnyway {
+    for problem in problems {
+        Output::warn(problem);
+    }
+    print!("Run anyway? [Y]es/[e]dit/[n]o: ");
+    io::stdout().flush().ok();
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input).is_err() {
+        return RunAnyway::Yes;
+    }
+    match input.trim().to_lowercase().as_str() {
+        "n" | "no" => RunAnyway::No,
+        "e" | "edit" => RunAnyway::Edit,
+        _ => RunAnyway::Yes,
+    }
+}
+
+/// Configured timeouts, with the per-command ones from definitions
+fn timeouts(config: &AppConfig, completer: &SmartCompleter) -> Timeouts {
+    Timeouts {
+        default: Some(Duration::from_secs(config.exec.timeout_secs)),
+        commands: completer.command_timeouts(),
+    }
+}
+
+/// Syntax highlighter with the configured theme
+fn new_highlighter(
+    config: &AppConfig,
+    command_names: Vec<String>,
+    completer: &SmartCompleter,
+) -> Box<SmartHighlighter> {
+    let theme = match config.theme.as_deref() {
+        Some("nord") => SyntaxTheme::nord(),
+        Some("dracula") => SyntaxTheme::dracula(),
+        _ => SyntaxTheme::default(),
     };
+    Box::new(
+        SmartHighlighter::new(command_names)
+            .with_theme(theme)
+            .with_completer(completer.clone()),
+    )
+}
 
-    if let Some(target) = target_path {
-        if let Err(e) = std::env::set_current_dir(&target) {
-            Output::error(&format!("cd: {}: {}", target.display(), e));
-        } else if let Some(old) = current_dir {
-            *OLDPWD.lock().unwrap() = Some(old);
+/// Final component of a path, for notices
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Create an executor with the configured backend and timeouts
+fn new_executor(config: &AppConfig, completer: &SmartCompleter) -> Executor {
+    let mut executor = Executor::new();
+    executor.set_timeouts(timeouts(config, completer));
+    match Backend::parse(&config.exec.shell) {
+        Ok(backend) => executor.set_backend(backend, config.exec.persistent),
+        Err(e) => Output::warn(&format!("{}; using the native executor", e)),
+    }
+    executor
+}
+
+/// Execute a command line, time it and record successful commands for hints
+fn run_timed(
+    command: &str,
+    state: &AppState,
+    executor: &mut Executor,
+    builtins: &mut ShellBuiltins,
+    typo_corrector: &TypoCorrector,
+    hinter: &SmartHinter,
+) -> ExecOutcome {
+    builtins.command_timer.start(command);
+    let outcome = execute_command(command, state, executor, builtins, typo_corrector);
+
+    let segments: Vec<_> = outcome
+        .segments
+        .iter()
+        .map(|s| (s.command.clone(), s.duration, Some(s.status)))
+        .collect();
+    if let Some(dur) = builtins.command_timer.stop_segments(&segments) {
+        if let Some(formatted) = builtins.command_timer.format_duration(dur) {
+            Output::dim(&format!("⏱  {}", formatted));
         }
     }
+
+    for segment in outcome.segments.iter().filter(|s| s.status == 0) {
+        hinter.record_command(&segment.command);
+    }
+
+    outcome
+}
+
+/// Execute a command line through the native executor
+fn execute_command(
+    command: &str,
+    state: &AppState,
+    executor: &mut Executor,
+    builtins: &mut ShellBuiltins,
+    typo_corrector: &TypoCorrector,
+) -> ExecOutcome {
+    // Check for dangerous commands
+    if state.danger_protection {
+        if let Some(warning) = output::get_danger_warning(command) {
+            warn!("Dangerous command detected: {}", command);
+            Output::warn(&warning);
+            print!("Are you sure you want to execute this command? [y/N] ");
+            io::stdout().flush().ok();
+
+            let mut input = String::new();
+            let confirmed = io::stdin().read_line(&mut input).is_ok() && {
+                let response = input.trim().to_lowercase();
+                response == "y" || response == "yes"
+            };
+            if !confirmed {
+                Output::dim("Command cancelled.");
+                executor.set_last_status(1);
+                return ExecOutcome {
+                    status: 1,
+                    ..Default::default()
+                };
+            }
+        }
+    }
+
+    debug!("Executing command: {}", command);
+
+    let outcome = executor.execute(command, builtins);
+
+    // Command not found - suggest typo corrections
+    for segment in &outcome.segments {
+        if segment.status == executor::STATUS_NOT_FOUND {
+            if let Some(cmd) = segment.command.split_whitespace().next() {
+                if let Some(message) = typo_corrector.did_you_mean(cmd) {
+                    Output::info(&message);
+                }
+            }
+        }
+    }
+
+    match &outcome.error {
+        Some(error) => Output::error(&error.to_string()),
+        // A suspended job already printed its own notice
+        None if outcome.status == jobs::STATUS_STOPPED => {}
+        None => Output::exit_code(outcome.status),
+    }
+    outcome
 }
 
 /// Start background version check
@@ -893,31 +1257,17 @@ fn start_background_version_check(
     rx
 }
 
-/// Handle 'config' command
-fn handle_config(parts: &[&str], current_lang: &Arc<RwLock<String>>) {
-    if let Some(sub) = parts.get(1) {
-        if *sub == "set-lang" {
-            if let Some(lang) = parts.get(2) {
-                *current_lang.write().unwrap() = lang.to_string();
-                Output::success(&format!("Language switched to: {}", lang));
-            } else {
-                Output::info("Usage: config set-lang <lang>");
-                Output::dim("Available languages: en, zh");
-            }
-        } else {
-            Output::error(&format!("Unknown config subcommand: {}", sub));
-            Output::dim("Available: set-lang");
-        }
-    } else {
-        Output::info("Usage: config set-lang <lang>");
-    }
-}
-
 /// Display a list of commands that have examples
-fn display_commands_with_examples(completer: &SmartCompleter, is_cli: bool) {
+fn display_commands_with_examples(
+    completer: &SmartCompleter,
+    is_cli: bool,
+    out: &mut dyn Write,
+) -> io::Result<()> {
     let commands = completer.get_commands_with_examples();
-    Output::info("Commands with examples:");
-    println!();
+    let info_style = Style::new().fg(Color::Blue);
+    let dim_style = Style::new().fg(Color::DarkGray);
+    writeln!(out, "{} {}", info_style.paint("ℹ"), info_style.paint("Commands with examples:"))?;
+    writeln!(out)?;
 
     // Group by root command for better display
     let mut current_root = String::new();
@@ -925,60 +1275,79 @@ fn display_commands_with_examples(completer: &SmartCompleter, is_cli: bool) {
         let root = cmd.split_whitespace().next().unwrap_or(cmd);
         if root != current_root {
             if !current_root.is_empty() {
-                println!();
+                writeln!(out)?;
             }
             current_root = root.to_string();
         }
-        println!("  {}", Output::command(cmd));
+        writeln!(out, "  {}", Output::command(cmd))?;
     }
 
-    println!();
-    Output::dim("Usage:");
-    if is_cli {
-        Output::dim("  sc example <command>         Show examples for a command");
-        Output::dim("  sc example <cmd> <subcmd>    Show examples for a subcommand");
-        Output::dim("  sc example -s <query>        Search all examples");
+    writeln!(out)?;
+    let usage: &[&str] = if is_cli {
+        &[
+            "  sc example <command>         Show examples for a command",
+            "  sc example <cmd> <subcmd>    Show examples for a subcommand",
+            "  sc example -s <query>        Search all examples",
+        ]
     } else {
-        Output::dim("  example <command>           Show examples for a command");
-        Output::dim("  example <cmd> <subcmd>      Show examples for a subcommand");
-        Output::dim("  example search <query>      Search all examples");
+        &[
+            "  example <command>           Show examples for a command",
+            "  example <cmd> <subcmd>      Show examples for a subcommand",
+            "  example search <query>      Search all examples",
+        ]
+    };
+    writeln!(out, "{}", dim_style.paint("Usage:"))?;
+    for line in usage {
+        writeln!(out, "{}", dim_style.paint(*line))?;
     }
+    Ok(())
 }
 
 /// Display search results for examples
-fn display_example_search_results(results: &[(String, String, String)], query: &str) {
+fn display_example_search_results(
+    results: &[(String, String, String)],
+    query: &str,
+    out: &mut dyn Write,
+) -> io::Result<()> {
     if results.is_empty() {
         Output::warn(&format!("No examples found for: {}", query));
-        return;
+        return Ok(());
     }
 
-    Output::info(&format!("Examples matching '{}':", query));
-    println!();
+    let info_style = Style::new().fg(Color::Blue);
+    let num_style = Style::new().fg(Color::DarkGray);
+    let path_style = Style::new().fg(Color::Cyan);
 
-    let num_style = nu_ansi_term::Style::new().fg(nu_ansi_term::Color::DarkGray);
-    let path_style = nu_ansi_term::Style::new().fg(nu_ansi_term::Color::Cyan);
+    let heading = format!("Examples matching '{}':", query);
+    writeln!(out, "{} {}", info_style.paint("ℹ"), info_style.paint(heading))?;
+    writeln!(out)?;
 
     for (i, (path, cmd, scenario)) in results.iter().take(MAX_EXAMPLE_RESULTS).enumerate() {
-        println!(
+        writeln!(
+            out,
             "  {}. {} {}",
             num_style.paint(format!("{:>2}", i + 1)),
             path_style.paint(format!("[{}]", path)),
             Output::command(cmd)
-        );
-        Output::dim(&format!("      → {}", scenario));
+        )?;
+        writeln!(out, "{}", num_style.paint(format!("      → {}", scenario)))?;
     }
 
     if results.len() > MAX_EXAMPLE_RESULTS {
-        println!();
-        Output::dim(&format!(
-            "  ... and {} more results",
-            results.len() - MAX_EXAMPLE_RESULTS
-        ));
+        writeln!(out)?;
+        let more = format!("  ... and {} more results", results.len() - MAX_EXAMPLE_RESULTS);
+        writeln!(out, "{}", num_style.paint(more))?;
     }
+    Ok(())
 }
 
 /// Display examples for a specific command
-fn display_command_examples(examples: &[(String, String)], command_path: &str, is_cli: bool) {
+fn display_command_examples(
+    examples: &[(String, String)],
+    command_path: &str,
+    is_cli: bool,
+    out: &mut dyn Write,
+) -> io::Result<()> {
     if examples.is_empty() {
         Output::warn(&format!("No examples found for: {}", command_path));
         if is_cli {
@@ -990,47 +1359,56 @@ fn display_command_examples(examples: &[(String, String)], command_path: &str, i
                 "Try 'example' to see commands with examples, or 'example search <query>' to search.",
             );
         }
-        return;
+        return Ok(());
     }
 
-    Output::info(&format!("Examples for '{}':", command_path));
-    println!();
+    let info_style = Style::new().fg(Color::Blue);
+    let num_style = Style::new().fg(Color::DarkGray);
 
-    let num_style = nu_ansi_term::Style::new().fg(nu_ansi_term::Color::DarkGray);
+    let heading = format!("Examples for '{}':", command_path);
+    writeln!(out, "{} {}", info_style.paint("ℹ"), info_style.paint(heading))?;
+    writeln!(out)?;
 
     for (i, (cmd, scenario)) in examples.iter().enumerate() {
-        println!(
+        writeln!(
+            out,
             "  {}. {}",
             num_style.paint(format!("{:>2}", i + 1)),
             Output::command(cmd)
-        );
-        Output::dim(&format!("      → {}", scenario));
+        )?;
+        writeln!(out, "{}", num_style.paint(format!("      → {}", scenario)))?;
     }
-    println!();
+    writeln!(out)
 }
 
 /// Handle 'example' command - display examples for commands (REPL version)
-fn handle_example_command(completer: &SmartCompleter, args: &[&str], lang: &str) {
+///
+/// Examples go to `out` so the builtin can be piped or redirected;
+/// warnings still go to the terminal.
+fn handle_example_command(
+    completer: &SmartCompleter,
+    args: &[&str],
+    lang: &str,
+    out: &mut dyn Write,
+) -> io::Result<()> {
     if args.is_empty() {
-        display_commands_with_examples(completer, false);
-        return;
+        return display_commands_with_examples(completer, false, out);
     }
 
     // Handle search subcommand
     if args[0] == "search" || args[0] == "s" {
         if args.len() < 2 {
             Output::warn("Usage: example search <query>");
-            return;
+            return Ok(());
         }
 
         let query = args[1..].join(" ");
         let results = completer.search_examples(&query, lang);
-        display_example_search_results(&results, &query);
-        return;
+        return display_example_search_results(&results, &query, out);
     }
 
     // Show examples for a specific command
     let command_path = args.join(" ");
     let examples = completer.get_examples(&command_path, lang);
-    display_command_examples(&examples, &command_path, false);
+    display_command_examples(&examples, &command_path, false, out)
 }
@@ -81,15 +81,22 @@ impl Output {
     }
 
     /// Styled prompt display
-    pub fn prompt(cwd: &str, branch: Option<&str>) -> String {
+    pub fn prompt(cwd: &str, branch: Option<&str>, status: Option<i32>) -> String {
         let cwd_style = Style::new().fg(Color::Cyan).bold();
         let branch_style = Style::new().fg(Color::Magenta);
+        let status_style = Style::new().fg(Color::Red).bold();
 
         let branch_info = branch
             .map(|b| format!(" {}", branch_style.paint(format!("({})", b))))
             .unwrap_or_default();
 
-        format!("{}{}", cwd_style.paint(cwd), branch_info)
+        // Only failures are shown
+        let status_info = status
+            .filter(|code| *code != 0)
+            .map(|code| format!(" {}", status_style.paint(format!("✗ {}", code))))
+            .unwrap_or_default();
+
+        format!("{}{}{}", cwd_style.paint(cwd), branch_info, status_info)
     }
 
     /// Format a command suggestion
@@ -5,6 +5,8 @@
 
 #![allow(dead_code)]
 
+use crate::tokenizer::read_substitution;
+
 /// Pipeline operators
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PipelineOperator {
@@ -98,6 +100,31 @@ impl Pipeline {
         let mut current_token = String::new();
 
         while let Some(c) = chars.next() {
+            // Backslash escapes the next character (except inside single quotes)
+            if c == '\\' && in_quotes != Some('\'') {
+                current_token.push(c);
+                if let Some(next) = chars.next() {
+                    current_token.push(next);
+                }
+                continue;
+            }
+
+            // Command substitutions stay in the word, operators inside and all
+            if in_quotes != Some('\'') && (c == '`' || (c == '$' && chars.peek() == Some(&'('))) {
+                let backtick = c == '`';
+                current_token.push(c);
+                if !backtick {
+                    current_token.push('(');
+                    chars.next();
+                }
+                let (body, closed) = read_substitution(&mut chars, backtick);
+                current_token.push_str(&body);
+                if closed {
+                    current_token.push(if backtick { '`' } else { ')' });
+                }
+                continue;
+            }
+
             // Handle quotes
             if c == '"' || c == '\'' {
                 if in_quotes == Some(c) {
@@ -143,7 +170,7 @@ impl Pipeline {
                     }
                 }
                 '<' => Some(PipelineOperator::RedirectIn),
-                '2' if chars.peek() == Some(&'>') => {
+                '2' if current_token.is_empty() && chars.peek() == Some(&'>') => {
                     chars.next();
                     if chars.peek() == Some(&'&') {
                         chars.next();
@@ -180,6 +207,18 @@ impl Pipeline {
                     });
                     current_command.clear();
                     current_args.clear();
+                } else if segments
+                    .last()
+                    .is_some_and(|s: &PipelineSegment| {
+                        s.operator_after == Some(PipelineOperator::RedirectErrToOut)
+                    })
+                {
+                    // `2>&1` takes no target, so keep the following operator on an empty segment
+                    segments.push(PipelineSegment {
+                        command: String::new(),
+                        args: Vec::new(),
+                        operator_after: Some(op),
+                    });
                 }
                 continue;
             }
@@ -533,6 +572,39 @@ mod tests {
         assert_eq!(pipeline.segments[0].args, vec!["\"hello | world\""]);
     }
 
+    #[test]
+    fn test_escaped_operators() {
+        let pipeline = Pipeline::parse("echo a\\|b my\\ file");
+        assert_eq!(pipeline.segments.len(), 1);
+        assert_eq!(pipeline.segments[0].args, vec!["a\\|b", "my\\ file"]);
+    }
+
+    #[test]
+    fn test_command_substitution_stays_whole() {
+        let pipeline = Pipeline::parse("export X=$(git log | head -1) Y=`date; id` && ls");
+        assert_eq!(pipeline.segments.len(), 2);
+        assert_eq!(
+            pipeline.segments[0].args,
+            vec!["X=$(git log | head -1)", "Y=`date; id`"]
+        );
+    }
+
+    #[test]
+    fn test_stderr_to_stdout_keeps_pipe() {
+        let pipeline = Pipeline::parse("make 2>&1 | grep error");
+        assert_eq!(pipeline.segments.len(), 3);
+        assert_eq!(
+            pipeline.segments[0].operator_after,
+            Some(PipelineOperator::RedirectErrToOut)
+        );
+        assert_eq!(pipeline.segments[1].command, "");
+        assert_eq!(
+            pipeline.segments[1].operator_after,
+            Some(PipelineOperator::Pipe)
+        );
+        assert_eq!(pipeline.segments[2].command, "grep");
+    }
+
     #[test]
     fn test_is_after_pipe() {
         let pipeline = Pipeline::parse("ls | ");
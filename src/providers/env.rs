@@ -1,9 +1,10 @@
 //! Environment variable completion provider
 //!
-//! Provides completions for $VAR environment variables.
+//! Provides completions for $VAR variables of the session environment,
+//! including variables set with `export` or `NAME=value` in the REPL.
 
 use super::{CompletionProvider, ProviderContext, ProviderSuggestion};
-use std::env;
+use crate::environment::session_env;
 use std::time::Duration;
 
 /// Provides environment variable completions
@@ -15,7 +16,7 @@ impl EnvVarProvider {
     }
 
     fn get_env_vars(&self) -> Vec<(String, String)> {
-        env::vars().collect()
+        session_env().vars()
     }
 }
 
@@ -39,10 +40,10 @@ impl CompletionProvider for EnvVarProvider {
         let vars = self.get_env_vars();
 
         // Handle ${ prefix
-        let (prefix, var_partial) = if partial.starts_with("${") {
-            ("${", &partial[2..])
-        } else if partial.starts_with('$') {
-            ("$", &partial[1..])
+        let (prefix, var_partial) = if let Some(rest) = partial.strip_prefix("${") {
+            ("${", rest)
+        } else if let Some(rest) = partial.strip_prefix('$') {
+            ("$", rest)
         } else {
             return vec![];
         };
@@ -50,7 +51,7 @@ impl CompletionProvider for EnvVarProvider {
         let var_partial_upper = var_partial.to_uppercase();
 
         vars.into_iter()
-            .filter(|(name, _)| name.starts_with(&var_partial_upper))
+            .filter(|(name, _)| name.to_uppercase().starts_with(&var_partial_upper))
             .map(|(name, value)| {
                 // Truncate long values for display
                 let display_value = if value.len() > 50 {
@@ -130,4 +131,16 @@ mod tests {
         // HOME should be in results on most systems
         assert!(results.iter().any(|s| s.value == "$HOME"));
     }
+
+    #[test]
+    fn test_env_var_completion_session_vars() {
+        session_env().set("sc_test_session_var", "value");
+
+        let provider = EnvVarProvider::new();
+        let ctx = ProviderContext::new(PathBuf::from("."), "echo", vec![], "${sc_test_s");
+        let results = provider.complete("${sc_test_s", &ctx);
+        assert!(results.iter().any(|s| s.value == "${sc_test_session_var}"));
+
+        session_env().unset("sc_test_session_var");
+    }
 }
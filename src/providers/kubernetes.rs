@@ -68,7 +68,7 @@ impl KubernetesResourceProvider {
                     .lines()
                     .filter_map(|line| {
                         // Output format: type/name
-                        line.split('/').last().map(|name| (name.to_string(), None))
+                        line.split('/').next_back().map(|name| (name.to_string(), None))
                     })
                     .collect()
             }
@@ -320,7 +320,8 @@ impl CompletionProvider for KubernetesContextProvider {
     }
 }
 
-/// Provider for Kubernetes namespaces (for -n flag)
+/// Provider for Kubernetes namespaces, for flags declaring
+/// `value_type: { type: provider, name: k8s_namespace }` (kubectl's `-n`)
 pub struct KubernetesNamespaceProvider;
 
 impl KubernetesNamespaceProvider {
@@ -336,7 +337,7 @@ impl KubernetesNamespaceProvider {
         match output {
             Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
                 .lines()
-                .filter_map(|line| line.split('/').last().map(|s| s.to_string()))
+                .filter_map(|line| line.split('/').next_back().map(|s| s.to_string()))
                 .collect(),
             _ => Vec::new(),
         }
@@ -354,16 +355,8 @@ impl CompletionProvider for KubernetesNamespaceProvider {
         "k8s_namespace"
     }
 
-    fn matches(&self, cmd: &str, _arg_position: usize, context: &ProviderContext) -> bool {
-        if cmd != "kubectl" && cmd != "k" {
-            return false;
-        }
-
-        // Match when previous arg is -n or --namespace
-        if let Some(prev) = context.args.last() {
-            return prev == "-n" || prev == "--namespace";
-        }
-
+    fn matches(&self, _cmd: &str, _arg_position: usize, _context: &ProviderContext) -> bool {
+        // Only used by name, through the flag's value type
         false
     }
 
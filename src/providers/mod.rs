@@ -201,7 +201,12 @@ impl ProviderRegistry {
     pub fn register(&mut self, provider: Box<dyn CompletionProvider>) {
         self.providers.push(provider);
         // Sort by priority (higher first)
-        self.providers.sort_by(|a, b| b.priority().cmp(&a.priority()));
+        self.providers.sort_by_key(|p| std::cmp::Reverse(p.priority()));
+    }
+
+    /// Names of all registered providers
+    pub fn provider_names(&self) -> Vec<&str> {
+        self.providers.iter().map(|p| p.name()).collect()
     }
 
     /// Enable/disable specific providers
@@ -227,45 +232,78 @@ impl ProviderRegistry {
                 continue;
             }
 
-            // Check cache first
-            let cache_key = format!(
-                "{}:{}:{}:{}",
-                provider.name(),
-                context.command,
-                context.arg_position,
-                context.partial_input
-            );
+            all_suggestions.extend(self.cached_complete(provider.as_ref(), context));
+        }
 
-            let mut cache = self.cache.lock().unwrap();
-            if let Some(cached) = cache.get(&cache_key) {
-                if cached.expires_at > Instant::now() {
-                    all_suggestions.extend(cached.suggestions.clone());
-                    continue;
-                }
-            }
-            drop(cache);
-
-            // Get fresh completions
-            let suggestions = provider.complete(&context.partial_input, context);
-
-            // Cache if provider supports it
-            if let Some(ttl) = provider.cache_ttl() {
-                let mut cache = self.cache.lock().unwrap();
-                cache.put(
-                    cache_key,
-                    CachedResult {
-                        suggestions: suggestions.clone(),
-                        expires_at: Instant::now() + ttl,
-                    },
-                );
+        // Sort by score
+        all_suggestions.sort_by_key(|s| std::cmp::Reverse(s.score));
+        all_suggestions
+    }
+
+    /// Get completions from the provider a definition names, whether or not
+    /// it would match the context by itself
+    ///
+    /// Returns `None` when no enabled provider has that name.
+    pub fn complete_named(
+        &self,
+        name: &str,
+        context: &ProviderContext,
+    ) -> Option<Vec<ProviderSuggestion>> {
+        let category = name.split('_').next().unwrap_or(name);
+        let enabled = self
+            .enabled_providers
+            .read()
+            .unwrap()
+            .iter()
+            .any(|e| category.starts_with(e.as_str()));
+        let provider = self.providers.iter().find(|p| p.name() == name)?;
+        if !enabled {
+            return None;
+        }
+
+        let mut suggestions = self.cached_complete(provider.as_ref(), context);
+        suggestions.sort_by_key(|s| std::cmp::Reverse(s.score));
+        Some(suggestions)
+    }
+
+    /// Completions of one provider, from the cache while they are fresh
+    fn cached_complete(
+        &self,
+        provider: &dyn CompletionProvider,
+        context: &ProviderContext,
+    ) -> Vec<ProviderSuggestion> {
+        let cache_key = format!(
+            "{}:{}:{}:{}",
+            provider.name(),
+            context.command,
+            context.arg_position,
+            context.partial_input
+        );
+
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(cached) = cache.get(&cache_key) {
+            if cached.expires_at > Instant::now() {
+                return cached.suggestions.clone();
             }
+        }
+        drop(cache);
 
-            all_suggestions.extend(suggestions);
+        // Get fresh completions
+        let suggestions = provider.complete(&context.partial_input, context);
+
+        // Cache if provider supports it
+        if let Some(ttl) = provider.cache_ttl() {
+            let mut cache = self.cache.lock().unwrap();
+            cache.put(
+                cache_key,
+                CachedResult {
+                    suggestions: suggestions.clone(),
+                    expires_at: Instant::now() + ttl,
+                },
+            );
         }
 
-        // Sort by score
-        all_suggestions.sort_by(|a, b| b.score.cmp(&a.score));
-        all_suggestions
+        suggestions
     }
 
     /// Clear all cached results
@@ -347,4 +385,17 @@ mod tests {
         let registry = ProviderRegistry::new();
         assert!(!registry.providers.is_empty());
     }
+
+    #[test]
+    fn test_complete_named() {
+        let registry = ProviderRegistry::new();
+        let ctx = ProviderContext::new(PathBuf::from("."), "mytool", vec![], "$HO");
+
+        let found = registry.complete_named("env_var", &ctx).unwrap();
+        assert!(found.iter().any(|s| s.value == "$HOME"));
+
+        assert!(registry.complete_named("no_such", &ctx).is_none());
+        registry.set_enabled(vec!["git".to_string()]);
+        assert!(registry.complete_named("env_var", &ctx).is_none());
+    }
 }
@@ -1,8 +1,11 @@
 //! Enhanced path completion provider
 //!
 //! Provides advanced path completions with filtering, bookmarks, and fuzzy matching.
+//! Also completes directory stack entries for `cd -N` and `pushd`/`popd +N`.
 
 use super::{CompletionProvider, ProviderContext, ProviderSuggestion};
+use crate::command_def::PathFilterConfig;
+use crate::dirstack::{self, dir_stack};
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
 use std::collections::HashMap;
@@ -26,6 +29,18 @@ pub struct PathFilter {
     pub dirs_only: bool,
 }
 
+impl From<&PathFilterConfig> for PathFilter {
+    fn from(config: &PathFilterConfig) -> Self {
+        Self {
+            extensions: config.extensions.clone(),
+            exclude_patterns: config.exclude_patterns.clone(),
+            include_hidden: config.include_hidden,
+            files_only: config.files_only,
+            dirs_only: config.dirs_only,
+        }
+    }
+}
+
 /// Enhanced path completion provider
 pub struct PathProvider {
     /// User-defined bookmarks (@name -> path)
@@ -89,6 +104,31 @@ impl PathProvider {
         self.bookmarks.read().unwrap().get(name).cloned()
     }
 
+    /// Prefix used to reference stack entries for a command (`cd -2`, `pushd +2`)
+    fn stack_prefix(command: &str) -> Option<char> {
+        match command {
+            "cd" => Some('-'),
+            "pushd" | "popd" => Some('+'),
+            _ => None,
+        }
+    }
+
+    /// Complete directory stack entries (`-1`, `-2`, ... or `+1`, `+2`, ...)
+    fn complete_stack(&self, prefix: char, partial: &str, entries: &[PathBuf]) -> Vec<ProviderSuggestion> {
+        entries
+            .iter()
+            .enumerate()
+            .map(|(i, dir)| (format!("{}{}", prefix, i + 1), dir))
+            .filter(|(value, _)| value.starts_with(partial))
+            .map(|(value, dir)| {
+                ProviderSuggestion::new(value)
+                    .with_description(dirstack::display_path(dir))
+                    .with_category("stack")
+                    .with_score(160)
+            })
+            .collect()
+    }
+
     /// Check if a path should be excluded
     fn should_exclude(&self, name: &str, filter: &PathFilter) -> bool {
         // Hidden files
@@ -234,8 +274,8 @@ impl PathProvider {
     /// Parse path and handle special prefixes
     fn parse_path(&self, partial: &str, cwd: &Path) -> (PathBuf, String) {
         // Handle @ bookmark prefix
-        if partial.starts_with('@') {
-            let parts: Vec<&str> = partial[1..].splitn(2, '/').collect();
+        if let Some(rest) = partial.strip_prefix('@') {
+            let parts: Vec<&str> = rest.splitn(2, '/').collect();
             let bookmark_name = parts[0];
 
             if let Some(bookmark_path) = self.get_bookmark(bookmark_name) {
@@ -266,7 +306,7 @@ impl PathProvider {
                     .unwrap_or_default();
                 return (parent.to_path_buf(), filename);
             }
-            return (PathBuf::from("/"), partial[1..].to_string());
+            return (PathBuf::from("/"), partial.trim_start_matches('/').to_string());
         }
 
         // Relative path
@@ -302,6 +342,13 @@ impl CompletionProvider for PathProvider {
 
         let partial = &context.partial_input;
 
+        // Directory stack references
+        if let Some(prefix) = Self::stack_prefix(&context.command) {
+            if partial.starts_with(prefix) || context.command == "pushd" {
+                return true;
+            }
+        }
+
         // Explicit path indicators
         if partial.starts_with('/')
             || partial.starts_with('~')
@@ -322,7 +369,33 @@ impl CompletionProvider for PathProvider {
     }
 
     fn complete(&self, partial: &str, context: &ProviderContext) -> Vec<ProviderSuggestion> {
-        let filter = PathFilter::default();
+        self.complete_filtered(partial, context, &PathFilter::default())
+    }
+
+    fn cache_ttl(&self) -> Option<Duration> {
+        // Don't cache path completions - filesystem changes frequently
+        None
+    }
+
+    fn priority(&self) -> i32 {
+        10 // Low priority - acts as fallback
+    }
+}
+
+impl PathProvider {
+    /// Complete paths, keeping only those `filter` lets through
+    pub fn complete_filtered(
+        &self,
+        partial: &str,
+        context: &ProviderContext,
+        filter: &PathFilter,
+    ) -> Vec<ProviderSuggestion> {
+        // Handle directory stack completion
+        if let Some(prefix) = Self::stack_prefix(&context.command) {
+            if partial.starts_with(prefix) {
+                return self.complete_stack(prefix, partial, &dir_stack().entries());
+            }
+        }
 
         // Handle bookmark completion
         if partial.starts_with('@') && !partial.contains('/') {
@@ -346,7 +419,7 @@ impl CompletionProvider for PathProvider {
 
         // If the partial contains multiple /, try fuzzy path matching
         if partial.matches('/').count() > 1 && !filename_partial.is_empty() {
-            let results = self.fuzzy_path_match(&context.cwd, partial, &filter);
+            let results = self.fuzzy_path_match(&context.cwd, partial, filter);
             return results
                 .into_iter()
                 .map(|path| {
@@ -360,7 +433,7 @@ impl CompletionProvider for PathProvider {
         }
 
         // Standard directory listing
-        let entries = self.list_dir(&base_dir, &filter);
+        let entries = self.list_dir(&base_dir, filter);
         let partial_lower = filename_partial.to_lowercase();
 
         entries
@@ -392,15 +465,6 @@ impl CompletionProvider for PathProvider {
             })
             .collect()
     }
-
-    fn cache_ttl(&self) -> Option<Duration> {
-        // Don't cache path completions - filesystem changes frequently
-        None
-    }
-
-    fn priority(&self) -> i32 {
-        10 // Low priority - acts as fallback
-    }
 }
 
 #[cfg(test)]
@@ -442,6 +506,23 @@ mod tests {
         assert_eq!(partial, "main");
     }
 
+    #[test]
+    fn test_stack_completion() {
+        let provider = PathProvider::new();
+        let entries = vec![PathBuf::from("/srv/api"), PathBuf::from("/srv/web")];
+
+        let suggestions = provider.complete_stack('-', "-", &entries);
+        let values: Vec<&str> = suggestions.iter().map(|s| s.value.as_str()).collect();
+        assert_eq!(values, vec!["-1", "-2"]);
+        assert_eq!(suggestions[1].description.as_deref(), Some("/srv/web"));
+
+        let suggestions = provider.complete_stack('+', "+2", &entries);
+        assert_eq!(suggestions.len(), 1);
+
+        let ctx = ProviderContext::new(PathBuf::from("."), "cd", vec![], "-");
+        assert!(provider.matches("cd", 0, &ctx));
+    }
+
     #[test]
     fn test_default_excludes() {
         let provider = PathProvider::new();
@@ -451,4 +532,35 @@ mod tests {
         assert!(provider.should_exclude(".git", &filter));
         assert!(!provider.should_exclude("src", &filter));
     }
+
+    #[test]
+    fn test_complete_filtered() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("main.rs"), "").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+        let provider = PathProvider::new();
+        let ctx = ProviderContext::new(dir.path().to_path_buf(), "rustc", vec![], "");
+        let values = |filter: &PathFilterConfig| {
+            let mut values: Vec<String> = provider
+                .complete_filtered("", &ctx, &PathFilter::from(filter))
+                .into_iter()
+                .map(|s| s.value)
+                .collect();
+            values.sort();
+            values
+        };
+
+        let rust = PathFilterConfig {
+            extensions: Some(vec![".rs".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(values(&rust), ["main.rs", "src/"]);
+        let dirs = PathFilterConfig {
+            dirs_only: true,
+            ..Default::default()
+        };
+        assert_eq!(values(&dirs), ["src/"]);
+    }
 }
@@ -0,0 +1,249 @@
+//! Startup Files
+//!
+//! Command files executed before the first prompt:
+//! - `~/.config/smart-command/init.sc` (always trusted)
+//! - `.sc-init` in the current directory or the nearest parent (per project)
+//!
+//! Project files can come from any checkout, so they only run after the user
+//! trusts them. "Always" decisions are stored in `trusted.yaml` together with
+//! a hash of the file, so an edited file has to be trusted again.
+//! `sc --norc` skips both files.
+//!
+//! A project's `.smart-command/definitions` can run commands too (providers,
+//! `version_command`), so it goes through the same trust check, hashed over
+//! all of its yaml files.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File name of the per-project startup file
+pub const PROJECT_RC_NAME: &str = ".sc-init";
+
+/// A user's answer to the trust prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trust {
+    /// Run this time only
+    Once,
+    /// Run now and remember the decision for this file content
+    Always,
+    /// Do not run
+    Deny,
+}
+
+/// A startup file ready to run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartupFile {
+    pub path: PathBuf,
+    pub source: String,
+}
+
+/// Trusted project startup files (path -> content hash)
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TrustStore {
+    #[serde(skip)]
+    config_path: PathBuf,
+    #[serde(default)]
+    trusted: BTreeMap<PathBuf, String>,
+}
+
+impl TrustStore {
+    /// Load the trust store from `~/.config/smart-command/trusted.yaml`
+    pub fn new() -> Self {
+        let config_path = dirs::config_dir()
+            .map(|p| p.join("smart-command").join("trusted.yaml"))
+            .unwrap_or_else(|| PathBuf::from("~/.config/smart-command/trusted.yaml"));
+        Self::with_path(config_path)
+    }
+
+    /// Load a trust store from a specific file
+    pub fn with_path(config_path: PathBuf) -> Self {
+        let mut store: TrustStore = fs::read_to_string(&config_path)
+            .ok()
+            .and_then(|content| serde_yaml::from_str(&content).ok())
+            .unwrap_or_default();
+        store.config_path = config_path;
+        store
+    }
+
+    /// Save the trust store
+    pub fn save(&self) -> std::io::Result<()> {
+        if let Some(parent) = self.config_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_yaml::to_string(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        fs::write(&self.config_path, content)
+    }
+
+    /// Check whether a file with this content was trusted before
+    pub fn is_trusted(&self, path: &Path, source: &str) -> bool {
+        self.trusted.get(path) == Some(&content_hash(source))
+    }
+
+    /// Remember a file's current content as trusted
+    pub fn trust(&mut self, path: &Path, source: &str) {
+        self.trusted
+            .insert(path.to_path_buf(), content_hash(source));
+    }
+}
+
+/// SHA-256 of a file's content, hex encoded
+pub fn content_hash(source: &str) -> String {
+    Sha256::digest(source.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Path of the global startup file
+pub fn global_rc_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|p| p.join("smart-command").join("init.sc"))
+}
+
+/// Find the nearest `.sc-init` in `cwd` or one of its parents
+pub fn find_project_rc(cwd: &Path) -> Option<PathBuf> {
+    cwd.ancestors()
+        .map(|dir| dir.join(PROJECT_RC_NAME))
+        .find(|path| path.is_file())
+}
+
+/// The content a project definitions directory is trusted by: the name and
+/// content of each of its yaml files
+pub fn definitions_source(dir: &Path) -> String {
+    let mut source = String::new();
+    for path in crate::loader::yaml_files(dir) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        source.push_str(&format!("# {}\n", name));
+        source.push_str(&fs::read_to_string(&path).unwrap_or_default());
+    }
+    source
+}
+
+/// Whether a project file (or directory) with this content may be used:
+/// it was trusted before, or `ask` allows it now
+pub fn allowed(
+    store: &mut TrustStore,
+    path: &Path,
+    source: &str,
+    ask: impl FnOnce(&Path) -> Trust,
+) -> bool {
+    store.is_trusted(path, source)
+        || match ask(path) {
+            Trust::Once => true,
+            Trust::Always => {
+                store.trust(path, source);
+                let _ = store.save();
+                true
+            }
+            Trust::Deny => false,
+        }
+}
+
+/// Collect the startup files to run, asking before running untrusted project files
+pub fn startup_files(
+    cwd: &Path,
+    store: &mut TrustStore,
+    mut ask: impl FnMut(&Path) -> Trust,
+) -> Vec<StartupFile> {
+    let mut files = Vec::new();
+
+    let global = global_rc_path();
+    if let Some(path) = &global {
+        if let Ok(source) = fs::read_to_string(path) {
+            files.push(StartupFile {
+                path: path.clone(),
+                source,
+            });
+        }
+    }
+
+    if let Some(path) = find_project_rc(cwd) {
+        let path = path.canonicalize().unwrap_or(path);
+        let is_global = global
+            .as_ref()
+            .is_some_and(|g| g.canonicalize().ok().as_ref() == Some(&path));
+
+        if let (false, Ok(source)) = (is_global, fs::read_to_string(&path)) {
+            if allowed(store, &path, &source, &mut ask) {
+                files.push(StartupFile { path, source });
+            }
+        }
+    }
+
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_project_rc_walks_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(dir.path().join(PROJECT_RC_NAME), "echo hi\n").unwrap();
+
+        assert_eq!(
+            find_project_rc(&nested),
+            Some(dir.path().join(PROJECT_RC_NAME))
+        );
+    }
+
+    #[test]
+    fn test_trust_is_tied_to_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROJECT_RC_NAME);
+        let mut store = TrustStore::with_path(dir.path().join("trusted.yaml"));
+
+        assert!(!store.is_trusted(&path, "export A=1"));
+        store.trust(&path, "export A=1");
+        store.save().unwrap();
+
+        let store = TrustStore::with_path(dir.path().join("trusted.yaml"));
+        assert!(store.is_trusted(&path, "export A=1"));
+        assert!(!store.is_trusted(&path, "export A=2"));
+    }
+
+    #[test]
+    fn test_untrusted_project_file_asks() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(PROJECT_RC_NAME), "echo project\n").unwrap();
+        let mut store = TrustStore::with_path(dir.path().join("trusted.yaml"));
+
+        let mut asked = 0;
+        let files = startup_files(dir.path(), &mut store, |_| {
+            asked += 1;
+            Trust::Deny
+        });
+        assert_eq!(asked, 1);
+        assert!(files.iter().all(|f| !f.path.ends_with(PROJECT_RC_NAME)));
+
+        let files = startup_files(dir.path(), &mut store, |_| Trust::Always);
+        assert!(files.iter().any(|f| f.source == "echo project\n"));
+
+        // Trusted now, so no prompt
+        let files = startup_files(dir.path(), &mut store, |_| panic!("should not ask"));
+        assert!(files.iter().any(|f| f.path.ends_with(PROJECT_RC_NAME)));
+    }
+
+    #[test]
+    fn test_definitions_trust_covers_every_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("deploy.yaml"), "name: deploy\n").unwrap();
+        let mut store = TrustStore::with_path(dir.path().join("trusted.yaml"));
+
+        let source = definitions_source(dir.path());
+        assert!(allowed(&mut store, dir.path(), &source, |_| Trust::Always));
+        let ask: fn(&Path) -> Trust = |_| panic!("should not ask");
+        assert!(allowed(&mut store, dir.path(), &source, ask));
+
+        // A new file has to be trusted again
+        fs::write(dir.path().join("tool.yaml"), "name: tool\n").unwrap();
+        let source = definitions_source(dir.path());
+        assert!(!allowed(&mut store, dir.path(), &source, |_| Trust::Deny));
+    }
+}
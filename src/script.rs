@@ -0,0 +1,252 @@
+//! Script Mode
+//!
+//! Runs `.sc` files non-interactively through the same expansions as the REPL:
+//! - `alias` expansion of the first word
+//! - `:snippet key=value` expansion
+//! - `@bookmark` lines change into the bookmarked directory
+//!
+//! Each line is one command line (a trailing `\` continues it on the next
+//! line). Blank lines, `#` comments and a leading `#!` shebang are skipped.
+//! Execution stops at the first failing line unless `continue_on_error` is
+//! set, and the script's status is the status of the last failed line.
+
+use crate::builtins::ShellBuiltins;
+use crate::output::Output;
+
+/// Options for running a script
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScriptOptions {
+    /// Keep going after a line fails
+    pub continue_on_error: bool,
+    /// Print each expanded line instead of running it
+    pub dry_run: bool,
+}
+
+/// A logical line of a script with its (1-based) starting line number
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptLine {
+    pub number: usize,
+    pub text: String,
+}
+
+/// Split a script into logical lines, joining `\` continuations and
+/// dropping blank lines, comments and the shebang
+pub fn script_lines(source: &str) -> Vec<ScriptLine> {
+    let mut lines = Vec::new();
+    let mut pending: Option<ScriptLine> = None;
+
+    for (i, raw) in source.lines().enumerate() {
+        let line = match pending.take() {
+            Some(mut partial) => {
+                partial.text.push_str(raw.trim_start());
+                partial
+            }
+            None => ScriptLine {
+                number: i + 1,
+                text: raw.trim_start().to_string(),
+            },
+        };
+
+        if let Some(stripped) = line.text.strip_suffix('\\') {
+            pending = Some(ScriptLine {
+                number: line.number,
+                text: format!("{} ", stripped.trim_end()),
+            });
+            continue;
+        }
+
+        let text = line.text.trim_end();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        lines.push(ScriptLine {
+            number: line.number,
+            text: text.to_string(),
+        });
+    }
+
+    if let Some(line) = pending {
+        if !line.text.trim().is_empty() {
+            lines.push(ScriptLine {
+                number: line.number,
+                text: line.text.trim_end().to_string(),
+            });
+        }
+    }
+
+    lines
+}
+
+/// Expand snippets, bookmarks and aliases in one line
+///
+/// Returns the command line to execute.
+pub fn expand_line(line: &str, builtins: &ShellBuiltins) -> Result<String, String> {
+    if line.starts_with(':') {
+        return builtins
+            .snippet_manager
+            .try_expand(line)
+            .ok_or_else(|| format!("Unknown snippet: {}", line));
+    }
+
+    if line.starts_with('@') && !line.contains(' ') {
+        return builtins
+            .bookmark_manager
+            .try_resolve(line)
+            .map(|path| format!("cd '{}'", path.display().to_string().replace('\'', "'\\''")))
+            .ok_or_else(|| format!("Bookmark {} not found", line));
+    }
+
+    Ok(builtins.alias_manager.expand(line))
+}
+
+/// Run a script, calling `execute` for every expanded line
+///
+/// `origin` names the script in error messages. Returns the exit status of
+/// the last failed line, or 0 when every line succeeded.
+pub fn run_script(
+    source: &str,
+    origin: &str,
+    options: ScriptOptions,
+    builtins: &mut ShellBuiltins,
+    mut execute: impl FnMut(&str, &mut ShellBuiltins) -> i32,
+) -> i32 {
+    let mut last_failure = 0;
+
+    for line in script_lines(source) {
+        // `exit [n]` ends the script early
+        if let Some(rest) = line.text.strip_prefix("exit") {
+            if rest.is_empty() || rest.starts_with(' ') {
+                if !options.dry_run {
+                    return rest.trim().parse().unwrap_or(last_failure);
+                }
+                println!("{}", line.text);
+                continue;
+            }
+        }
+
+        let status = match expand_line(&line.text, builtins) {
+            Ok(expanded) if options.dry_run => {
+                println!("{}", expanded);
+                0
+            }
+            Ok(expanded) => execute(&expanded, builtins),
+            Err(e) => {
+                Output::error(&format!("{}:{}: {}", origin, line.number, e));
+                1
+            }
+        };
+
+        if status != 0 {
+            last_failure = status;
+            if !options.continue_on_error {
+                Output::error(&format!(
+                    "{}:{}: '{}' failed with status {}",
+                    origin, line.number, line.text, status
+                ));
+                return status;
+            }
+        }
+    }
+
+    last_failure
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::Catalog;
+    use crate::completer::SmartCompleter;
+    use std::sync::{Arc, RwLock};
+
+    fn builtins() -> ShellBuiltins {
+        let lang = Arc::new(RwLock::new("en".to_string()));
+        ShellBuiltins::new(SmartCompleter::new(Catalog::default(), lang.clone()), lang)
+    }
+
+    #[test]
+    fn test_script_lines() {
+        let source = "#!/usr/bin/env sc\n\n# build\ncargo build \\\n  --release\n  echo done\n";
+        let lines = script_lines(source);
+        assert_eq!(
+            lines,
+            vec![
+                ScriptLine {
+                    number: 4,
+                    text: "cargo build --release".to_string()
+                },
+                ScriptLine {
+                    number: 6,
+                    text: "echo done".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stop_on_first_error() {
+        let mut builtins = builtins();
+        let mut ran = Vec::new();
+        let status = run_script(
+            "true\nfalse\nnever",
+            "test.sc",
+            ScriptOptions::default(),
+            &mut builtins,
+            |line, _| {
+                ran.push(line.to_string());
+                if line == "false" {
+                    3
+                } else {
+                    0
+                }
+            },
+        );
+        assert_eq!(status, 3);
+        assert_eq!(ran, vec!["true", "false"]);
+    }
+
+    #[test]
+    fn test_continue_on_error() {
+        let mut builtins = builtins();
+        let options = ScriptOptions {
+            continue_on_error: true,
+            ..Default::default()
+        };
+        let status = run_script(
+            "fail 2\ntrue\nfail 5\ntrue",
+            "test.sc",
+            options,
+            &mut builtins,
+            |line, _| line.strip_prefix("fail ").map_or(0, |n| n.parse().unwrap()),
+        );
+        assert_eq!(status, 5);
+    }
+
+    #[test]
+    fn test_dry_run_does_not_execute() {
+        let mut builtins = builtins();
+        let options = ScriptOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        let status = run_script("rm -rf build\nexit 4", "test.sc", options, &mut builtins, |_, _| {
+            panic!("dry run must not execute")
+        });
+        assert_eq!(status, 0);
+    }
+
+    #[test]
+    fn test_exit_status() {
+        let mut builtins = builtins();
+        let status = run_script(
+            "true\nexit 7\nnever",
+            "test.sc",
+            ScriptOptions::default(),
+            &mut builtins,
+            |line, _| {
+                assert_ne!(line, "never");
+                0
+            },
+        );
+        assert_eq!(status, 7);
+    }
+}
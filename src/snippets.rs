@@ -13,7 +13,7 @@ use std::fs;
 use std::path::PathBuf;
 
 /// A command snippet with placeholders
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Snippet {
     /// Unique trigger keyword
     pub trigger: String,
@@ -290,6 +290,16 @@ impl SnippetManager {
         }
     }
 
+    /// Re-read the config file, dropping snippets it no longer has
+    ///
+    /// Returns whether anything changed.
+    pub fn reload(&mut self) -> bool {
+        let before = std::mem::take(&mut self.snippets);
+        self.load();
+        self.add_default_snippets();
+        self.snippets != before
+    }
+
     /// Save snippets to config file
     pub fn save(&self) -> std::io::Result<()> {
         if let Some(parent) = self.config_path.parent() {
@@ -78,25 +78,57 @@ impl CommandTimer {
         if let (Some(start), Some(command)) = (self.start_time.take(), self.current_command.take())
         {
             let duration = start.elapsed();
+            self.push_record(command, duration, exit_code, start);
+            Some(duration)
+        } else {
+            None
+        }
+    }
 
-            // Record this command
-            let record = CommandRecord {
-                command,
-                duration,
-                exit_code,
-                timestamp: start,
-            };
+    /// Stop timing a command line that ran as several segments
+    ///
+    /// Each `(command, duration, exit_code)` segment is recorded separately so
+    /// statistics reflect the individual commands of a chain or pipeline.
+    /// Returns the total elapsed time of the line.
+    pub fn stop_segments(&mut self, segments: &[(String, Duration, Option<i32>)]) -> Option<Duration> {
+        if !self.enabled {
+            return None;
+        }
 
-            self.records.push_back(record);
+        let start = self.start_time.take()?;
+        let command = self.current_command.take()?;
+        let duration = start.elapsed();
 
-            // Keep only max_records
-            while self.records.len() > self.max_records {
-                self.records.pop_front();
+        match segments {
+            [] => self.push_record(command, duration, None, start),
+            [(_, _, exit_code)] => self.push_record(command, duration, *exit_code, start),
+            _ => {
+                for (segment, segment_duration, exit_code) in segments {
+                    self.push_record(segment.clone(), *segment_duration, *exit_code, start);
+                }
             }
+        }
 
-            Some(duration)
-        } else {
-            None
+        Some(duration)
+    }
+
+    fn push_record(
+        &mut self,
+        command: String,
+        duration: Duration,
+        exit_code: Option<i32>,
+        timestamp: Instant,
+    ) {
+        self.records.push_back(CommandRecord {
+            command,
+            duration,
+            exit_code,
+            timestamp,
+        });
+
+        // Keep only max_records
+        while self.records.len() > self.max_records {
+            self.records.pop_front();
         }
     }
 
@@ -144,7 +176,7 @@ impl CommandTimer {
     /// Get the slowest commands
     pub fn slowest(&self, count: usize) -> Vec<&CommandRecord> {
         let mut sorted: Vec<_> = self.records.iter().collect();
-        sorted.sort_by(|a, b| b.duration.cmp(&a.duration));
+        sorted.sort_by_key(|r| std::cmp::Reverse(r.duration));
         sorted.into_iter().take(count).collect()
     }
 
@@ -160,11 +192,23 @@ impl CommandTimer {
         let max_record = self.records.iter().max_by_key(|r| r.duration);
         let min_record = self.records.iter().min_by_key(|r| r.duration);
 
-        let failed_count = self
+        // Records without an exit code count in neither group
+        let average = |records: &[&CommandRecord]| {
+            let total: Duration = records.iter().map(|r| r.duration).sum();
+            total
+                .checked_div(records.len() as u32)
+                .unwrap_or(Duration::ZERO)
+        };
+        let succeeded: Vec<&CommandRecord> = self
+            .records
+            .iter()
+            .filter(|r| r.exit_code == Some(0))
+            .collect();
+        let failed: Vec<&CommandRecord> = self
             .records
             .iter()
-            .filter(|r| r.exit_code.map(|c| c != 0).unwrap_or(false))
-            .count();
+            .filter(|r| r.exit_code.is_some_and(|c| c != 0))
+            .collect();
 
         TimerStats {
             total_commands: self.records.len(),
@@ -173,7 +217,10 @@ impl CommandTimer {
             max_time: max_record.map(|r| r.duration).unwrap_or(Duration::ZERO),
             max_command: max_record.map(|r| r.command.clone()),
             min_time: min_record.map(|r| r.duration).unwrap_or(Duration::ZERO),
-            failed_commands: failed_count,
+            succeeded_commands: succeeded.len(),
+            success_average_time: average(&succeeded),
+            failed_commands: failed.len(),
+            failure_average_time: average(&failed),
         }
     }
 
@@ -198,7 +245,10 @@ pub struct TimerStats {
     pub max_time: Duration,
     pub max_command: Option<String>,
     pub min_time: Duration,
+    pub succeeded_commands: usize,
+    pub success_average_time: Duration,
     pub failed_commands: usize,
+    pub failure_average_time: Duration,
 }
 
 impl std::fmt::Display for TimerStats {
@@ -213,11 +263,25 @@ impl std::fmt::Display for TimerStats {
             writeln!(f, "Slowest: {}", cmd)?;
         }
         writeln!(f, "Min time: {:.3}s", self.min_time.as_secs_f64())?;
-        writeln!(f, "Failed: {}", self.failed_commands)?;
+        writeln!(
+            f,
+            "Succeeded: {} (avg {:.3}s)",
+            self.succeeded_commands,
+            self.success_average_time.as_secs_f64()
+        )?;
+        writeln!(
+            f,
+            "Failed: {} (avg {:.3}s)",
+            self.failed_commands,
+            self.failure_average_time.as_secs_f64()
+        )?;
         Ok(())
     }
 }
 
+/// Arguments `time` takes as the builtin; anything else is a command to time
+pub const TIME_SUBCOMMANDS: &[&str] = &["stats", "slow", "slowest", "avg"];
+
 /// Handle timer-related commands
 pub fn handle_timer_command(timer: &CommandTimer, cmd: &str, args: &[&str]) -> Option<String> {
     match cmd {
@@ -322,6 +386,43 @@ mod tests {
 
         let stats = timer.stats();
         assert_eq!(stats.total_commands, 5);
+        assert_eq!(stats.succeeded_commands, 5);
         assert_eq!(stats.failed_commands, 0);
     }
+
+    #[test]
+    fn test_stats_split_by_exit_code() {
+        let mut timer = CommandTimer::new();
+        timer.start("true; false; missing");
+        timer.stop_segments(&[
+            ("true".to_string(), Duration::from_millis(10), Some(0)),
+            ("false".to_string(), Duration::from_millis(30), Some(1)),
+            ("missing".to_string(), Duration::from_millis(50), Some(127)),
+        ]);
+        timer.start("unknown");
+        timer.stop(None);
+
+        let stats = timer.stats();
+        assert_eq!(stats.total_commands, 4);
+        assert_eq!(stats.succeeded_commands, 1);
+        assert_eq!(stats.failed_commands, 2);
+        assert_eq!(stats.success_average_time, Duration::from_millis(10));
+        assert_eq!(stats.failure_average_time, Duration::from_millis(40));
+    }
+
+    #[test]
+    fn test_stop_segments() {
+        let mut timer = CommandTimer::new();
+
+        timer.start("make && make test");
+        let segments = vec![
+            ("make".to_string(), Duration::from_millis(5), Some(0)),
+            ("make test".to_string(), Duration::from_millis(8), Some(2)),
+        ];
+        assert!(timer.stop_segments(&segments).is_some());
+
+        let stats = timer.stats();
+        assert_eq!(stats.total_commands, 2);
+        assert_eq!(stats.failed_commands, 1);
+    }
 }
@@ -0,0 +1,423 @@
+//! Shell Tokenizer
+//!
+//! Splits the line being completed into words the way the executor will read
+//! them:
+//! - Single quotes, double quotes and backslash escapes
+//! - `$(...)` and backticks stay inside their word, whatever they contain
+//! - Operators (`|`, `&&`, `;`, `>`, `2>&1`, ...) are tokens of their own
+//! - `#` at the start of a word comments out the rest of the line
+//!
+//! Every token keeps its byte span, so a completion replaces exactly the word
+//! under the cursor. [`quote`] goes the other way and writes a value so that
+//! it reads back unchanged.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Word,
+    Operator,
+}
+
+/// A word or operator of a command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// The word as the command sees it, quotes and escapes removed
+    /// (substitutions are kept as written)
+    pub text: String,
+    /// Byte range in the line, quotes included
+    pub span: Range<usize>,
+    /// Quote still open at the end of the line (`'` or `"`)
+    pub open_quote: Option<char>,
+    /// Where the contents of a `$(` still open at the end of the line start
+    pub open_substitution: Option<usize>,
+}
+
+impl Token {
+    fn word(start: usize) -> Self {
+        Self {
+            kind: TokenKind::Word,
+            text: String::new(),
+            span: start..start,
+            open_quote: None,
+            open_substitution: None,
+        }
+    }
+}
+
+/// Two-character operators
+const OPERATOR_PAIRS: &str = "|| |& && &> ;; >> >& >| << <&";
+
+/// Characters a bare word has to escape
+const SPECIAL: &str = " \t\n'\"\\$`|&;<>()*?[]!";
+
+/// Split `line` into words and operators
+pub fn tokenize(line: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut word: Option<Token> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if quote == Some('\'') {
+            match c {
+                '\'' => quote = None,
+                _ => push(&mut word, i, c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if quote == Some('"') => quote = None,
+            '\'' | '"' if quote.is_none() => {
+                word.get_or_insert_with(|| Token::word(i));
+                quote = Some(c);
+            }
+            '\\' => match chars.next() {
+                Some((_, '\n')) => {}
+                Some((_, next)) if quote.is_none() || matches!(next, '$' | '`' | '"' | '\\') => {
+                    push(&mut word, i, next)
+                }
+                Some((_, next)) => {
+                    push(&mut word, i, '\\');
+                    push(&mut word, i, next);
+                }
+                None => {
+                    word.get_or_insert_with(|| Token::word(i));
+                }
+            },
+            '$' if chars.peek().is_some_and(|&(_, next)| next == '(') => {
+                let token = word.get_or_insert_with(|| Token::word(i));
+                match substitution_end(line, i + 2) {
+                    Some(end) => {
+                        token.text.push_str(&line[i..end]);
+                        while chars.next_if(|&(j, _)| j < end).is_some() {}
+                    }
+                    None => {
+                        token.text.push_str(&line[i..]);
+                        token.open_substitution = Some(i + 2);
+                        while chars.next().is_some() {}
+                    }
+                }
+            }
+            '`' => {
+                let token = word.get_or_insert_with(|| Token::word(i));
+                let end = backtick_end(line, i + 1).unwrap_or(line.len());
+                token.text.push_str(&line[i..end]);
+                while chars.next_if(|&(j, _)| j < end).is_some() {}
+            }
+            _ if quote.is_some() => push(&mut word, i, c),
+            c if c.is_whitespace() => finish(&mut tokens, &mut word, i),
+            '#' if word.is_none() => break,
+            '|' | '&' | ';' | '<' | '>' | '(' | ')' => {
+                // A file descriptor number belongs to its redirection (`2>`)
+                let start = match &word {
+                    Some(w)
+                        if matches!(c, '<' | '>')
+                            && w.span.start + w.text.len() == i
+                            && w.text.chars().all(|d| d.is_ascii_digit()) =>
+                    {
+                        word.take().unwrap().span.start
+                    }
+                    _ => {
+                        finish(&mut tokens, &mut word, i);
+                        i
+                    }
+                };
+                let mut end = i + 1;
+                let pair = chars.next_if(|&(_, next)| {
+                    OPERATOR_PAIRS
+                        .split(' ')
+                        .any(|op| op.starts_with(c) && op.ends_with(next))
+                });
+                if let Some((_, next)) = pair {
+                    end += 1;
+                    // `>&2`, `2>&1`, `<&-`
+                    if next == '&' && c != '|' && c != '&' {
+                        while let Some((j, d)) =
+                            chars.next_if(|&(_, d)| d.is_ascii_digit() || d == '-')
+                        {
+                            end = j + d.len_utf8();
+                        }
+                    }
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Operator,
+                    text: line[start..end].to_string(),
+                    span: start..end,
+                    open_quote: None,
+                    open_substitution: None,
+                });
+            }
+            _ => push(&mut word, i, c),
+        }
+    }
+
+    if let Some(token) = word.as_mut() {
+        token.open_quote = quote;
+    }
+    finish(&mut tokens, &mut word, line.len());
+    tokens
+}
+
+/// The last command of a line: what follows its last `|`, `&&`, `;`, ...
+#[derive(Debug, Clone)]
+pub struct Command<'a> {
+    /// Its words, without redirections and their targets
+    pub words: Vec<&'a Token>,
+    /// Index of its first token
+    pub start: usize,
+    /// The operator before it, if any
+    pub after: Option<&'a str>,
+    /// The line ends with a redirection, or with the word it writes to
+    pub redirect: bool,
+}
+
+/// Split off the command `tokens` end with
+pub fn last_command(tokens: &[Token]) -> Command<'_> {
+    let is_separator = |t: &Token| {
+        t.kind == TokenKind::Operator
+            && matches!(
+                t.text.as_str(),
+                "|" | "||" | "&&" | ";" | "&" | "|&" | ";;" | "(" | ")"
+            )
+    };
+    let start = tokens.iter().rposition(is_separator).map_or(0, |i| i + 1);
+    let mut command = Command {
+        words: Vec::new(),
+        start,
+        after: start.checked_sub(1).map(|i| tokens[i].text.as_str()),
+        redirect: false,
+    };
+    let mut target = false;
+    for token in &tokens[start..] {
+        command.redirect = token.kind == TokenKind::Operator || target;
+        if token.kind == TokenKind::Operator {
+            // `2>&1` and `>&2` have their target already
+            target = !token
+                .text
+                .ends_with(|c: char| c.is_ascii_digit() || c == '-');
+        } else if std::mem::take(&mut target) {
+            command.redirect = true;
+        } else {
+            command.words.push(token);
+        }
+    }
+    command
+}
+
+/// Add `c`, found at byte `i`, to the current word
+fn push(word: &mut Option<Token>, i: usize, c: char) {
+    word.get_or_insert_with(|| Token::word(i)).text.push(c);
+}
+
+/// End the current word at byte `end`
+fn finish(tokens: &mut Vec<Token>, word: &mut Option<Token>, end: usize) {
+    if let Some(mut token) = word.take() {
+        token.span.end = end;
+        tokens.push(token);
+    }
+}
+
+/// End of the `$(...)` whose contents start at `from`: the byte after its
+/// `)`, or `None` while it is still open
+fn substitution_end(line: &str, from: usize) -> Option<usize> {
+    let mut rest = line[from..].chars();
+    let (_, closed) = read_substitution(&mut rest, false);
+    closed.then(|| line.len() - rest.as_str().len())
+}
+
+/// End of the backtick substitution whose contents start at `from`
+fn backtick_end(line: &str, from: usize) -> Option<usize> {
+    let mut rest = line[from..].chars();
+    let (_, closed) = read_substitution(&mut rest, true);
+    closed.then(|| line.len() - rest.as_str().len())
+}
+
+/// Read the body of a command substitution, the opening `$(` (or backtick)
+/// already consumed
+///
+/// Quotes and nested parentheses are skipped over, so `$(a | b)` and
+/// `$(echo ")")` come back whole. Returns the body and whether the closing
+/// `)` (or backtick) was found.
+pub fn read_substitution(chars: &mut impl Iterator<Item = char>, backtick: bool) -> (String, bool) {
+    let mut body = String::new();
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quote != Some('\'') => {
+                body.push(c);
+                match chars.next() {
+                    Some(next) => body.push(next),
+                    None => return (body, false),
+                }
+                continue;
+            }
+            '`' if backtick => return (body, true),
+            _ if backtick => {}
+            '\'' | '"' if quote == Some(c) => quote = None,
+            _ if quote.is_some() => {}
+            '\'' | '"' => quote = Some(c),
+            '(' => depth += 1,
+            ')' if depth == 0 => return (body, true),
+            ')' => depth -= 1,
+            _ => {}
+        }
+        body.push(c);
+    }
+    (body, false)
+}
+
+/// The quote a word opens with: its first character, or a quote left open
+/// at its end (`--message="fix`)
+pub fn opening_quote(raw: &str) -> Option<char> {
+    match raw.chars().next() {
+        Some(c @ ('\'' | '"')) => Some(c),
+        _ => tokenize(raw).last().and_then(|t| t.open_quote),
+    }
+}
+
+/// `value` written so that it reads back unchanged inside `quote` (`'`, `"`,
+/// or `None` for a bare word)
+///
+/// The quote is closed only when `close` is set, so that a directory can be
+/// completed further. A leading `~/` stays outside the quotes, where it still
+/// expands.
+pub fn quote(value: &str, quote: Option<char>, close: bool) -> String {
+    if let (Some(rest), Some(_)) = (value.strip_prefix("~/"), quote) {
+        return format!("~/{}", self::quote(rest, quote, close));
+    }
+    let mut out = String::with_capacity(value.len() + 2);
+    match quote {
+        Some('\'') => {
+            out.push('\'');
+            out.push_str(&value.replace('\'', "'\\''"));
+        }
+        Some(q) => {
+            out.push(q);
+            for c in value.chars() {
+                if matches!(c, '"' | '\\' | '$' | '`') {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+        }
+        None => {
+            for (i, c) in value.chars().enumerate() {
+                if SPECIAL.contains(c) || (i == 0 && c == '#') {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+            return out;
+        }
+    }
+    if close {
+        out.extend(quote);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(line: &str) -> Vec<String> {
+        tokenize(line).into_iter().map(|t| t.text).collect()
+    }
+
+    #[test]
+    fn test_quotes_and_escapes() {
+        assert_eq!(
+            texts(r#"git commit -m "fix bug" --am"#),
+            ["git", "commit", "-m", "fix bug", "--am"]
+        );
+        assert_eq!(
+            texts(r"cat my\ file 'it''s' a\"),
+            ["cat", "my file", "its", "a"]
+        );
+        assert_eq!(
+            texts(r#"echo "a\"b\n" x#y # comment"#),
+            ["echo", "a\"b\\n", "x#y"]
+        );
+
+        let tokens = tokenize(r#"cat "my fi"#);
+        assert_eq!(tokens[1].text, "my fi");
+        assert_eq!(tokens[1].span, 4..10);
+        assert_eq!(tokens[1].open_quote, Some('"'));
+        assert_eq!(tokenize("ls ").last().unwrap().span, 0..2);
+    }
+
+    #[test]
+    fn test_operators_and_substitutions() {
+        let tokens = tokenize("make 2>&1|grep -v x&&echo $(date +%s) `id -u`;ls >>log");
+        let kinds: Vec<(&str, TokenKind)> =
+            tokens.iter().map(|t| (t.text.as_str(), t.kind)).collect();
+        use TokenKind::*;
+        assert_eq!(
+            kinds,
+            [
+                ("make", Word),
+                ("2>&1", Operator),
+                ("|", Operator),
+                ("grep", Word),
+                ("-v", Word),
+                ("x", Word),
+                ("&&", Operator),
+                ("echo", Word),
+                ("$(date +%s)", Word),
+                ("`id -u`", Word),
+                (";", Operator),
+                ("ls", Word),
+                (">>", Operator),
+                ("log", Word),
+            ]
+        );
+
+        let tokens = tokenize("git checkout $(git branch --list ma");
+        let last = tokens.last().unwrap();
+        assert_eq!(last.open_substitution, Some(15));
+        assert_eq!(last.span, 13..35);
+        assert_eq!(tokenize("a $(b \"c)\" (d)) e").len(), 3);
+
+        let mut chars = "echo \")\" $(pwd))/x".chars();
+        assert_eq!(
+            read_substitution(&mut chars, false),
+            ("echo \")\" $(pwd)".to_string(), true)
+        );
+        assert_eq!(chars.as_str(), "/x");
+    }
+
+    #[test]
+    fn test_last_command() {
+        let tokens = tokenize("cat log 2>&1 | sudo grep -i x > out.txt");
+        let command = last_command(&tokens);
+        let words: Vec<&str> = command.words.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(words, ["sudo", "grep", "-i", "x"]);
+        assert_eq!((command.start, command.after), (4, Some("|")));
+        assert!(command.redirect);
+
+        let tokens = tokenize("make >log 2>&1 && ls -la");
+        let command = last_command(&tokens);
+        assert_eq!(command.words.len(), 2);
+        assert!(!command.redirect);
+        assert!(last_command(&tokenize("make >log 2>&1")).words.len() == 1);
+        assert!(last_command(&tokenize("sort <")).redirect);
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("my file (1).txt", None, true), r"my\ file\ \(1\).txt");
+        assert_eq!(quote("#tag~x", None, true), r"\#tag~x");
+        assert_eq!(quote("it's", Some('\''), true), r"'it'\''s'");
+        assert_eq!(quote("a \"$b\"", Some('"'), true), r#""a \"\$b\"""#);
+        assert_eq!(quote("My Dir/", Some('"'), false), "\"My Dir/");
+        assert_eq!(quote("~/My Dir/", Some('\''), false), "~/'My Dir/");
+
+        assert_eq!(opening_quote("'abc"), Some('\''));
+        assert_eq!(opening_quote("--message=\"fix"), Some('"'));
+        assert_eq!(opening_quote("plain"), None);
+    }
+}
@@ -5,15 +5,66 @@
 //! - Unclosed brackets/braces/parentheses
 //! - Incomplete pipes and redirects
 //! - Backslash line continuations
+//!
+//! With a completer it also checks a finished line against the definitions
+//! of its commands (see [`SmartValidator::check_arguments`]); the REPL asks
+//! before running a line that has problems.
 
+use crate::completer::SmartCompleter;
+use crate::executor::{self, CommandList};
 use reedline::{ValidationResult, Validator};
 
 /// Smart validator that checks for common syntax errors
-pub struct SmartValidator;
+pub struct SmartValidator {
+    completer: Option<SmartCompleter>,
+}
 
 impl SmartValidator {
     pub fn new() -> Self {
-        Self
+        Self { completer: None }
+    }
+
+    /// Check arguments against the definitions `completer` knows
+    pub fn with_completer(mut self, completer: SmartCompleter) -> Self {
+        self.completer = Some(completer);
+        self
+    }
+
+    /// Problems with the flags and arguments of each command on `line`
+    ///
+    /// Only commands with a definition are checked, and words with
+    /// variables, globs or command substitutions are taken on trust.
+    pub fn check_arguments(&self, line: &str) -> Vec<String> {
+        let (Some(completer), Ok(list)) = (&self.completer, CommandList::parse(line)) else {
+            return Vec::new();
+        };
+
+        let mut problems = Vec::new();
+        let commands = list.jobs.iter().flat_map(|(_, job)| &job.commands);
+        for command in commands {
+            let literal: Vec<bool> = command
+                .words
+                .iter()
+                .map(|w| !w.contains(['$', '`', '*', '?', '[']))
+                .collect();
+            let words: Vec<String> = command
+                .words
+                .iter()
+                .map(|w| {
+                    // Substitutions are not run, just kept as typed
+                    let substitute = &mut |body: &str| format!("$({})", body);
+                    executor::expand_word(w, &|_| None, substitute).join(" ")
+                })
+                .collect();
+            let words: Vec<&str> = words.iter().map(String::as_str).collect();
+
+            for violation in completer.argument_violations(&words) {
+                if literal.get(violation.word) != Some(&false) {
+                    problems.push(format!("{}: {}", words[0], violation.message));
+                }
+            }
+        }
+        problems
     }
 
     /// Check if quotes are balanced
@@ -234,4 +285,58 @@ mod tests {
             ValidationResult::Incomplete
         ));
     }
+
+    #[test]
+    fn test_check_arguments() {
+        let restart: crate::command_def::CommandSpec = serde_yaml::from_str(
+            r#"
+name: restart
+description: Restart
+flags:
+  - long: delay
+    description: Seconds to wait
+    takes_value: true
+    value_type:
+      type: number
+      min: 0
+arguments:
+  - name: service
+    required: true
+"#,
+        )
+        .unwrap();
+        let completer = SmartCompleter::new(
+            crate::catalog::Catalog::new(vec![restart]),
+            std::sync::Arc::new(std::sync::RwLock::new("en".to_string())),
+        );
+        let validator = SmartValidator::new().with_completer(completer);
+        let check = |line| validator.check_arguments(line);
+
+        assert!(check("restart web --delay 5").is_empty());
+        assert_eq!(
+            check("echo hi && restart --delay -1 | cat"),
+            [
+                "restart: --delay: Value -1 is less than minimum 0",
+                "restart: missing <service>",
+            ]
+        );
+        // Unknown commands and words only known at run time are not checked
+        assert!(check("unknown-tool --x").is_empty());
+        assert!(check("restart web --delay $DELAY").is_empty());
+        assert!(SmartValidator::new().check_arguments("restart").is_empty());
+
+        // The bundled definitions list only the common flags; others are real
+        let layers = [crate::loader::Layer {
+            kind: crate::loader::LayerKind::Bundled,
+            dir: "definitions".into(),
+        }];
+        let bundled = SmartValidator::new().with_completer(SmartCompleter::new(
+            crate::catalog::load(&layers, None),
+            std::sync::Arc::new(std::sync::RwLock::new("en".to_string())),
+        ));
+        assert!(bundled.check_arguments("git commit -s -m x").is_empty());
+        assert!(bundled.check_arguments("git commit --fixup HEAD").is_empty());
+        // Also finishes a merge, cherry-pick or revert, not only --amend
+        assert!(bundled.check_arguments("git commit --no-edit").is_empty());
+    }
 }
@@ -0,0 +1,262 @@
+//! Tool versions, for definitions whose subcommands and flags come and go
+//!
+//! A spec with a `version_command` is asked for its version the first time a
+//! completion has to check `since` or `until`. The answer is cached for a few
+//! minutes, like provider results, so upgrading a tool is picked up without
+//! restarting sc. When the version cannot be found everything is offered.
+//!
+//! Version commands run while the user waits for completions, so one that
+//! takes longer than [`TIMEOUT`] is killed and counts as not found. Only
+//! definitions the user installed may declare one: the loader drops
+//! `version_command` from `./definitions` and project definitions.
+
+use crate::command_def::CommandSpec;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How long a detected version is trusted
+const TTL: Duration = Duration::from_secs(300);
+
+/// How long a version command may run
+pub const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// First dotted number in the output, when the spec has no `version_regex`
+const DEFAULT_REGEX: &str = r"\d+(?:\.\d+)+";
+
+/// A dotted version number; missing parts count as 0 (`2.23` == `2.23.0`)
+#[derive(Debug, Clone)]
+pub struct Version(Vec<u64>);
+
+impl Version {
+    /// Parse the leading dotted number of `text` (`v2.23.1-rc1` is 2.23.1)
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().trim_start_matches('v');
+        let parts: Vec<u64> = text
+            .split('.')
+            .map_while(|part| {
+                let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+                digits.parse().ok()
+            })
+            .collect();
+        (!parts.is_empty()).then_some(Self(parts))
+    }
+
+    /// Whether something available from `since` until (not including)
+    /// `until` exists in this version
+    ///
+    /// Bounds that don't parse are ignored.
+    pub fn within(&self, since: Option<&str>, until: Option<&str>) -> bool {
+        let since = since.and_then(Version::parse);
+        let until = until.and_then(Version::parse);
+        since.is_none_or(|since| *self >= since) && until.is_none_or(|until| *self < until)
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.0.len().max(other.0.len());
+        let part = |v: &Version, i: usize| v.0.get(i).copied().unwrap_or(0);
+        (0..len)
+            .map(|i| part(self, i).cmp(&part(other, i)))
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Version {}
+
+/// Whether a subcommand or flag with these bounds should be offered
+///
+/// Without a known version nothing is hidden.
+pub fn supported(version: Option<&Version>, since: Option<&str>, until: Option<&str>) -> bool {
+    version.is_none_or(|v| v.within(since, until))
+}
+
+/// Pick the version out of a version command's output
+pub fn extract(output: &str, pattern: Option<&str>) -> Option<Version> {
+    let regex = Regex::new(pattern.unwrap_or(DEFAULT_REGEX)).ok()?;
+    let caps = regex.captures(output)?;
+    let found = caps.get(1).or_else(|| caps.get(0))?;
+    Version::parse(found.as_str())
+}
+
+struct Detected {
+    version: Option<Version>,
+    expires_at: Instant,
+}
+
+/// Versions detected so far, by version command
+pub struct VersionCache {
+    entries: Mutex<HashMap<String, Detected>>,
+}
+
+impl VersionCache {
+    fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Version of the tool `spec` describes, if it declares how to find it
+    pub fn detect(&self, spec: &CommandSpec) -> Option<Version> {
+        let command = spec.version_command.as_deref()?;
+        let key = format!(
+            "{}\n{}",
+            command,
+            spec.version_regex.as_deref().unwrap_or("")
+        );
+        if let Some(detected) = self.entries.lock().unwrap().get(&key) {
+            if detected.expires_at > Instant::now() {
+                return detected.version.clone();
+            }
+        }
+
+        let version = run(command).and_then(|out| extract(&out, spec.version_regex.as_deref()));
+        self.entries.lock().unwrap().insert(
+            key,
+            Detected {
+                version: version.clone(),
+                expires_at: Instant::now() + TTL,
+            },
+        );
+        version
+    }
+}
+
+/// Output of a version command; some tools print their version to stderr
+///
+/// `None` if it can't be started or doesn't finish within [`TIMEOUT`].
+fn run(command: &str) -> Option<String> {
+    let mut words = command.split_whitespace();
+    let mut child = Command::new(words.next()?)
+        .args(words)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+
+    let readers = [
+        read_all(child.stdout.take()?),
+        read_all(child.stderr.take()?),
+    ];
+
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+
+    let mut text = String::new();
+    for reader in readers {
+        text.push_str(&String::from_utf8_lossy(&reader.join().unwrap_or_default()));
+    }
+    Some(text)
+}
+
+/// Read a child's pipe to the end on another thread, so it can't fill up
+fn read_all(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    })
+}
+
+static VERSIONS: Lazy<VersionCache> = Lazy::new(VersionCache::new);
+
+/// Get the global version cache
+pub fn versions() -> &'static VersionCache {
+    &VERSIONS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_compare() {
+        let v = |s| Version::parse(s).unwrap();
+        assert_eq!(v("2.23"), v("2.23.0"));
+        assert!(v("2.9.1") < v("2.23"));
+        assert_eq!(v("v1.2.3-rc1"), v("1.2.3"));
+        assert!(Version::parse("latest").is_none());
+
+        let git = v("2.22.1");
+        assert!(!git.within(Some("2.23"), None));
+        assert!(git.within(None, Some("2.23")));
+        assert!(v("2.23").within(Some("2.23"), Some("3")));
+        assert!(!v("3.0").within(Some("2.23"), Some("3")));
+    }
+
+    #[test]
+    fn test_extract() {
+        assert_eq!(
+            extract("git version 2.39.2 (Apple Git-143)", None),
+            Version::parse("2.39.2")
+        );
+        assert_eq!(
+            extract(
+                "Docker version 24.0.7, build afdd53b",
+                Some(r"version (\d+\.\d+)")
+            ),
+            Version::parse("24.0")
+        );
+        assert!(extract("no version here", None).is_none());
+        assert!(supported(None, Some("99"), None));
+    }
+
+    #[test]
+    fn test_detect_caches_by_command() {
+        let mut spec = CommandSpec::new("sh", "Shell");
+        spec.version_command = Some("echo tool 1.4.2".to_string());
+        let cache = VersionCache::new();
+        assert_eq!(cache.detect(&spec), Version::parse("1.4.2"));
+        assert_eq!(cache.entries.lock().unwrap().len(), 1);
+
+        spec.version_command = None;
+        assert!(cache.detect(&spec).is_none());
+    }
+
+    #[test]
+    fn test_slow_version_command_is_killed() {
+        let mut spec = CommandSpec::new("slow", "Slow tool");
+        spec.version_command = Some("sleep 10".to_string());
+        let cache = VersionCache::new();
+        let started = Instant::now();
+        assert!(cache.detect(&spec).is_none());
+        assert!(started.elapsed() < TIMEOUT * 3);
+        // Cached, so it isn't waited for again
+        let started = Instant::now();
+        assert!(cache.detect(&spec).is_none());
+        assert!(started.elapsed() < TIMEOUT);
+    }
+}
@@ -5,7 +5,8 @@
 
 #![allow(dead_code)]
 
-use notify::{Config, Event, RecommendedWatcher, Watcher};
+use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver};
 use std::sync::{Arc, RwLock};
@@ -13,12 +14,18 @@ use std::thread;
 use std::time::Duration;
 
 /// Events from the file watcher
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum WatchEvent {
     /// Definition file changed
     DefinitionChanged(PathBuf),
     /// Config file changed
     ConfigChanged,
+    /// aliases.yaml changed
+    AliasesChanged,
+    /// snippets.yaml changed
+    SnippetsChanged,
+    /// bookmarks.yaml changed
+    BookmarksChanged,
     /// Plugin file changed
     PluginChanged(PathBuf),
     /// Error occurred
@@ -28,11 +35,13 @@ pub enum WatchEvent {
 /// File watcher for hot-reload functionality
 pub struct FileWatcher {
     /// Internal watcher instance
-    _watcher: RecommendedWatcher,
+    watcher: RecommendedWatcher,
     /// Receiver for watch events
     receiver: Receiver<WatchEvent>,
     /// Paths being watched
     watched_paths: Vec<PathBuf>,
+    /// Watched directories whose yaml files are command definitions
+    definition_dirs: Arc<RwLock<Vec<PathBuf>>>,
     /// Whether watcher is active
     active: Arc<RwLock<bool>>,
 }
@@ -43,6 +52,8 @@ impl FileWatcher {
         let (tx, rx) = channel();
         let active = Arc::new(RwLock::new(true));
         let active_clone = active.clone();
+        let definition_dirs = Arc::new(RwLock::new(Vec::new()));
+        let definition_dirs_clone = definition_dirs.clone();
 
         let watcher = RecommendedWatcher::new(
             move |res: Result<Event, notify::Error>| {
@@ -51,9 +62,14 @@ impl FileWatcher {
                 }
 
                 match res {
+                    // Reads don't change anything
+                    Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
                     Ok(event) => {
+                        let definition_dirs = definition_dirs_clone.read().unwrap();
                         for path in event.paths {
-                            let event = classify_event(&path);
+                            let Some(event) = classify_event(&path, &definition_dirs) else {
+                                continue;
+                            };
                             if tx.send(event).is_err() {
                                 break;
                             }
@@ -68,27 +84,39 @@ impl FileWatcher {
         )?;
 
         Ok(Self {
-            _watcher: watcher,
+            watcher,
             receiver: rx,
             watched_paths: Vec::new(),
+            definition_dirs,
             active,
         })
     }
 
     /// Watch a directory or file
-    pub fn watch(&mut self, path: impl AsRef<Path>) -> Result<(), notify::Error> {
+    pub fn watch(&mut self, path: impl AsRef<Path>, mode: RecursiveMode) -> Result<(), notify::Error> {
         let path = path.as_ref().to_path_buf();
-        // Note: In actual implementation, we would call self._watcher.watch() here
-        // For now, just track the path
+        self.watcher.watch(&path, mode)?;
         self.watched_paths.push(path);
         Ok(())
     }
 
+    /// Watch a definitions directory, reporting its yaml files as definitions
+    pub fn watch_definitions(&mut self, dir: impl AsRef<Path>) -> Result<(), notify::Error> {
+        let dir = dir.as_ref();
+        self.watch(dir, RecursiveMode::NonRecursive)?;
+        self.definition_dirs
+            .write()
+            .unwrap()
+            .push(dir.to_path_buf());
+        Ok(())
+    }
+
     /// Stop watching a path
     pub fn unwatch(&mut self, path: impl AsRef<Path>) -> Result<(), notify::Error> {
         let path = path.as_ref();
         self.watched_paths.retain(|p| p != path);
-        Ok(())
+        self.definition_dirs.write().unwrap().retain(|p| p != path);
+        self.watcher.unwatch(path)
     }
 
     /// Get pending events (non-blocking)
@@ -122,46 +150,66 @@ impl FileWatcher {
 }
 
 /// Classify a file change event based on path
-fn classify_event(path: &Path) -> WatchEvent {
+///
+/// Only yaml files directly inside one of `definition_dirs` are definitions;
+/// the config directory also holds state sc rewrites itself (`dirstack.yaml`,
+/// `trusted.yaml`). Returns `None` for files nothing is loaded from (history,
+/// editor swap files, ...).
+fn classify_event(path: &Path, definition_dirs: &[PathBuf]) -> Option<WatchEvent> {
     let filename = path
         .file_name()
         .and_then(|n| n.to_str())
         .unwrap_or("");
-    let parent = path
-        .parent()
-        .and_then(|p| p.file_name())
-        .and_then(|n| n.to_str())
-        .unwrap_or("");
 
-    if filename == "config.yaml" || filename == "smart-command.yaml" {
-        WatchEvent::ConfigChanged
-    } else if filename.ends_with(".yaml") && parent == "definitions" {
-        WatchEvent::DefinitionChanged(path.to_path_buf())
-    } else if parent == "plugins" || path.components().any(|c| c.as_os_str() == "plugins") {
-        WatchEvent::PluginChanged(path.to_path_buf())
-    } else {
-        WatchEvent::DefinitionChanged(path.to_path_buf())
+    if path.components().any(|c| c.as_os_str() == "plugins") {
+        return Some(WatchEvent::PluginChanged(path.to_path_buf()));
+    }
+    match filename {
+        "config.toml" => Some(WatchEvent::ConfigChanged),
+        "aliases.yaml" => Some(WatchEvent::AliasesChanged),
+        "snippets.yaml" => Some(WatchEvent::SnippetsChanged),
+        "bookmarks.yaml" => Some(WatchEvent::BookmarksChanged),
+        _ if filename.ends_with(".yaml")
+            && path
+                .parent()
+                .is_some_and(|dir| definition_dirs.iter().any(|d| d == dir)) =>
+        {
+            Some(WatchEvent::DefinitionChanged(path.to_path_buf()))
+        }
+        _ => None,
     }
 }
 
+/// Drop repeated events, keeping the first of each
+fn dedup_events(mut events: Vec<WatchEvent>) -> Vec<WatchEvent> {
+    let mut seen = HashSet::new();
+    events.retain(|e| seen.insert(e.clone()));
+    events
+}
+
 /// Hot-reload manager
+///
+/// Watches the definition layers, the config directory (config.toml,
+/// aliases, snippets, bookmarks) and the plugins directory. The REPL polls
+/// it between prompts and reloads whatever changed.
 pub struct HotReloadManager {
     watcher: Option<FileWatcher>,
-    definitions_dir: PathBuf,
-    config_path: PathBuf,
+    definition_dirs: Vec<PathBuf>,
+    config_dir: PathBuf,
     plugins_dir: PathBuf,
-    reload_callback: Option<Box<dyn Fn(WatchEvent) + Send + Sync>>,
+    /// Project definitions directory currently watched
+    project_dir: Option<PathBuf>,
 }
 
 impl HotReloadManager {
     /// Create a new hot-reload manager
-    pub fn new(definitions_dir: PathBuf, config_path: PathBuf, plugins_dir: PathBuf) -> Self {
+    pub fn new(definition_dirs: Vec<PathBuf>, config_dir: PathBuf, plugins_dir: PathBuf) -> Self {
         Self {
             watcher: None,
-            definitions_dir,
-            config_path,
+            definition_dirs,
+            config_dir,
             plugins_dir,
-            reload_callback: None,
+            project_dir: None,
         }
     }
 
@@ -169,27 +217,45 @@ impl HotReloadManager {
     pub fn start(&mut self) -> Result<(), notify::Error> {
         let mut watcher = FileWatcher::new()?;
 
-        // Watch definitions directory
-        if self.definitions_dir.exists() {
-            watcher.watch(&self.definitions_dir)?;
+        // Watch definitions directories
+        for dir in &self.definition_dirs {
+            if dir.exists() {
+                watcher.watch_definitions(dir)?;
+            }
         }
 
-        // Watch config file
-        if let Some(parent) = self.config_path.parent() {
-            if parent.exists() {
-                watcher.watch(parent)?;
-            }
+        // Watch the config directory itself: config.toml and the yaml stores
+        if self.config_dir.exists() {
+            watcher.watch(&self.config_dir, RecursiveMode::NonRecursive)?;
         }
 
         // Watch plugins directory
         if self.plugins_dir.exists() {
-            watcher.watch(&self.plugins_dir)?;
+            watcher.watch(&self.plugins_dir, RecursiveMode::Recursive)?;
         }
 
         self.watcher = Some(watcher);
         Ok(())
     }
 
+    /// Follow the project definitions directory as the cwd changes
+    pub fn watch_project(&mut self, dir: Option<PathBuf>) {
+        if self.project_dir == dir {
+            return;
+        }
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        if let Some(old) = self.project_dir.take() {
+            let _ = watcher.unwatch(&old);
+        }
+        if let Some(dir) = dir {
+            if watcher.watch_definitions(&dir).is_ok() {
+                self.project_dir = Some(dir);
+            }
+        }
+    }
+
     /// Stop watching
     pub fn stop(&mut self) {
         if let Some(watcher) = &self.watcher {
@@ -198,22 +264,11 @@ impl HotReloadManager {
         self.watcher = None;
     }
 
-    /// Set callback for reload events
-    pub fn on_reload<F>(&mut self, callback: F)
-    where
-        F: Fn(WatchEvent) + Send + Sync + 'static,
-    {
-        self.reload_callback = Some(Box::new(callback));
-    }
-
-    /// Check for pending events and trigger callbacks
-    pub fn check_events(&self) {
-        if let Some(watcher) = &self.watcher {
-            for event in watcher.poll_events() {
-                if let Some(callback) = &self.reload_callback {
-                    callback(event);
-                }
-            }
+    /// Changes since the last poll, one event per file
+    pub fn poll(&self) -> Vec<WatchEvent> {
+        match &self.watcher {
+            Some(watcher) => dedup_events(watcher.poll_events()),
+            None => Vec::new(),
         }
     }
 
@@ -244,8 +299,8 @@ impl DebouncedWatcher {
     }
 
     /// Watch a path
-    pub fn watch(&mut self, path: impl AsRef<Path>) -> Result<(), notify::Error> {
-        self.inner.watch(path)
+    pub fn watch(&mut self, path: impl AsRef<Path>, mode: RecursiveMode) -> Result<(), notify::Error> {
+        self.inner.watch(path, mode)
     }
 
     /// Get debounced events
@@ -253,21 +308,7 @@ impl DebouncedWatcher {
         // Collect events over debounce period
         thread::sleep(self.debounce_duration);
 
-        let mut events = self.inner.poll_events();
-
-        // Deduplicate by path
-        let mut seen_paths = std::collections::HashSet::new();
-        events.retain(|e| {
-            match e {
-                WatchEvent::DefinitionChanged(p) | WatchEvent::PluginChanged(p) => {
-                    seen_paths.insert(p.clone())
-                }
-                WatchEvent::ConfigChanged => seen_paths.insert(PathBuf::from("__config__")),
-                WatchEvent::Error(_) => true,
-            }
-        });
-
-        events
+        dedup_events(self.inner.poll_events())
     }
 
     /// Stop the watcher
@@ -282,17 +323,64 @@ mod tests {
 
     #[test]
     fn test_classify_event() {
-        let path = PathBuf::from("/home/user/.config/smart-command/config.yaml");
-        assert!(matches!(classify_event(&path), WatchEvent::ConfigChanged));
+        let dirs = [PathBuf::from("/home/user/definitions")];
+        let path = PathBuf::from("/home/user/.config/smart-command/config.toml");
+        assert_eq!(
+            classify_event(&path, &dirs),
+            Some(WatchEvent::ConfigChanged)
+        );
+
+        let path = PathBuf::from("/home/user/.config/smart-command/aliases.yaml");
+        assert_eq!(
+            classify_event(&path, &dirs),
+            Some(WatchEvent::AliasesChanged)
+        );
 
         let path = PathBuf::from("/home/user/definitions/git.yaml");
         assert!(matches!(
-            classify_event(&path),
-            WatchEvent::DefinitionChanged(_)
+            classify_event(&path, &dirs),
+            Some(WatchEvent::DefinitionChanged(_))
         ));
 
         let path = PathBuf::from("/home/user/plugins/my-plugin/main.sh");
-        assert!(matches!(classify_event(&path), WatchEvent::PluginChanged(_)));
+        assert!(matches!(
+            classify_event(&path, &dirs),
+            Some(WatchEvent::PluginChanged(_))
+        ));
+
+        // History and editor swap files are not loaded from
+        let path = PathBuf::from("/home/user/.config/smart-command/history.txt");
+        assert_eq!(classify_event(&path, &dirs), None);
+        let path = PathBuf::from("/home/user/definitions/.git.yaml.swp");
+        assert_eq!(classify_event(&path, &dirs), None);
+    }
+
+    #[test]
+    fn test_classify_event_ignores_state_files() {
+        // sc rewrites these itself, on every `cd` and trust prompt
+        let dirs = [PathBuf::from(
+            "/home/user/.config/smart-command/definitions",
+        )];
+        for name in ["dirstack.yaml", "trusted.yaml"] {
+            let path = PathBuf::from("/home/user/.config/smart-command").join(name);
+            assert_eq!(classify_event(&path, &dirs), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_watcher_reports_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let Ok(mut watcher) = FileWatcher::new() else {
+            return;
+        };
+        watcher.watch_definitions(dir.path()).unwrap();
+        std::fs::write(dir.path().join("git.yaml"), "name: git\n").unwrap();
+
+        let event = watcher.wait_event(Duration::from_secs(5));
+        assert_eq!(
+            event,
+            Some(WatchEvent::DefinitionChanged(dir.path().join("git.yaml")))
+        );
     }
 
     #[test]